@@ -0,0 +1,71 @@
+use std::io;
+use std::path::Path;
+
+use crate::format::{json_string, Format};
+
+// runs the --annotate-cmd template for one match through sh and returns what it printed, on one
+// line so it fits after the path
+#[cfg(unix)]
+pub fn run(template: &str, path: &Path) -> io::Result<String> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::process::{Command, Stdio};
+
+    let line = crate::script::command_line(template, path);
+    let mut command = Command::new("sh");
+    command.arg("-c")
+        .arg(OsStr::from_bytes(&line))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null());
+    // whatever the command leaves in $TMPDIR goes away with the rest of this run's temp files
+    crate::temp::for_child(&mut command);
+    let output = command.output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.split_whitespace().collect::<Vec<&str>>().join(" "))
+}
+
+#[cfg(not(unix))]
+pub fn run(_template: &str, _path: &Path) -> io::Result<String> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "--annotate-cmd needs a unix shell"))
+}
+
+// adds the annotation after a text line, or as the last field of a json record
+pub fn append(line: &str, format: Format, annotation: &str) -> String {
+    match format {
+        Format::Text => {
+            format!("{}\t{}", line, annotation)
+        },
+        Format::Json => {
+            format!("{},\"annotation\":{}}}", &line[..line.len() - 1], json_string(annotation))
+        },
+    }
+}
+
+#[test]
+fn test_append() {
+    assert_eq!(append("a.png", Format::Text, "PNG image data"), "a.png\tPNG image data");
+    assert_eq!(append("{\"path\":\"a.png\"}", Format::Json, "PNG \"image\""),
+               "{\"path\":\"a.png\",\"annotation\":\"PNG \\\"image\\\"\"}");
+}
+
+#[test]
+fn test_append_edge_cases() {
+    // a command that printed nothing still gets its column, so the fields stay aligned
+    assert_eq!(append("a.png", Format::Text, ""), "a.png\t");
+    assert_eq!(append("{\"path\":\"a\"}", Format::Json, ""), "{\"path\":\"a\",\"annotation\":\"\"}");
+    assert_eq!(append("{\"path\":\"a\"}", Format::Json, "\\ \u{1}"), "{\"path\":\"a\",\"annotation\":\"\\\\ \\u0001\"}");
+}
+
+#[test]
+fn test_run() {
+    // whatever the command prints is folded onto one line, and what it says on stderr is dropped
+    assert_eq!(run("printf 'a\\n  b\\tc\\n'; echo noise >&2; : {}", Path::new("x")).unwrap(), "a b c");
+    // the path is quoted, so spaces and quotes in it reach the command intact
+    assert_eq!(run("printf %s", Path::new("it's a file")).unwrap(), "it's a file");
+    // a failing command annotates with whatever it printed before failing
+    assert_eq!(run("echo partial; exit 3; : {}", Path::new("x")).unwrap(), "partial");
+    // it gets no stdin to wait on
+    assert_eq!(run("cat; : {}", Path::new("x")).unwrap(), "");
+    // tests have no temp::init to clean up at exit
+    std::fs::remove_dir_all(crate::temp::dir().unwrap()).unwrap();
+}
@@ -0,0 +1,270 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::image::{self, Entry};
+use crate::temp;
+use crate::time::Date;
+use crate::walk::WalkOptions;
+use crate::MyFile;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn kind(name: &str) -> Option<Kind> {
+    let name = name.to_lowercase();
+    if name.ends_with(".zip") {
+        Some(Kind::Zip)
+    } else if name.ends_with(".tar") {
+        Some(Kind::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(Kind::TarGz)
+    } else {
+        None
+    }
+}
+
+pub fn is_archive(name: &str) -> bool {
+    kind(name).is_some()
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "truncated zip central directory")
+}
+
+// a little-endian number of len bytes at the start of bytes[at..]
+fn le(bytes: &[u8], at: usize, len: usize) -> io::Result<u64> {
+    let field = bytes.get(at..at + len).ok_or_else(truncated)?;
+    Ok(field.iter().rev().fold(0, |n, &b| (n << 8) | b as u64))
+}
+
+// zip stores local time, in two second steps
+fn dos_time(date: u64, time: u64) -> i64 {
+    let date = Date { year: 1980 + (date >> 9) as i64, month: ((date >> 5) & 0xf) as u32, day: (date & 0x1f) as u32 };
+    if !(1..=12).contains(&date.month) || date.day == 0 {
+        return 0;
+    }
+    date.midnight(false) + (time >> 11) as i64 * 3600 + ((time >> 5) & 0x3f) as i64 * 60 + (time & 0x1f) as i64 * 2
+}
+
+// the members of a zip, from its central directory at the end of the file, so nothing is
+// decompressed. zip64 archives and sizes are understood
+fn read_zip<R: Read + Seek>(mut input: R) -> io::Result<Vec<Entry>> {
+    let len = input.seek(SeekFrom::End(0))?;
+    // the end of central directory record is 22 bytes, followed by a comment of up to 64k
+    let tail_len = len.min(22 + 0xffff);
+    input.seek(SeekFrom::Start(len - tail_len))?;
+    let mut tail = vec![0; tail_len as usize];
+    input.read_exact(&mut tail)?;
+    let end = tail.windows(4)
+        .rposition(|w| w == b"PK\x05\x06")
+        .filter(|&at| at + 22 <= tail.len())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a zip archive"))?;
+    let mut count = le(&tail, end + 10, 2)?;
+    let mut size = le(&tail, end + 12, 4)?;
+    let mut offset = le(&tail, end + 16, 4)?;
+    if (count == 0xffff || offset == 0xffff_ffff) && end >= 20 && &tail[end - 20..end - 16] == b"PK\x06\x07" {
+        input.seek(SeekFrom::Start(le(&tail, end - 12, 8)?))?;
+        let mut record = [0; 56];
+        input.read_exact(&mut record)?;
+        if &record[..4] == b"PK\x06\x06" {
+            count = le(&record, 32, 8)?;
+            size = le(&record, 40, 8)?;
+            offset = le(&record, 48, 8)?;
+        }
+    }
+
+    input.seek(SeekFrom::Start(offset))?;
+    let mut directory = Vec::new();
+    input.take(size).read_to_end(&mut directory)?;
+    let mut entries = Vec::new();
+    let mut at = 0;
+    for _ in 0..count {
+        if directory.get(at..at + 4) != Some(b"PK\x01\x02") {
+            return Err(truncated());
+        }
+        let made_by = le(&directory, at + 4, 2)?;
+        let modified = dos_time(le(&directory, at + 14, 2)?, le(&directory, at + 12, 2)?);
+        let mut size = le(&directory, at + 24, 4)?;
+        let name_len = le(&directory, at + 28, 2)? as usize;
+        let extra_len = le(&directory, at + 30, 2)? as usize;
+        let comment_len = le(&directory, at + 32, 2)? as usize;
+        let mode = le(&directory, at + 38, 4)? >> 16;
+        let name = directory.get(at + 46..at + 46 + name_len).ok_or_else(truncated)?;
+        let path = String::from_utf8_lossy(name).into_owned();
+        // the zip64 extra field holds the real size when the 32-bit one is maxed out, and it
+        // comes first there
+        if size == 0xffff_ffff {
+            let mut extra = at + 46 + name_len;
+            let extra_end = extra + extra_len;
+            while extra + 4 <= extra_end {
+                let id = le(&directory, extra, 2)?;
+                let len = le(&directory, extra + 2, 2)? as usize;
+                if id == 1 {
+                    size = le(&directory, extra + 4, 8)?;
+                    break;
+                }
+                extra += 4 + len;
+            }
+        }
+        // only archives made on unix keep the file type in the upper half of the attributes
+        let kind = if path.ends_with('/') {
+            b'5'
+        } else if made_by >> 8 == 3 && mode & 0o170000 == 0o120000 {
+            b'2'
+        } else {
+            b'0'
+        };
+        entries.push(Entry { path, size, modified, kind });
+        at += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+// there is no inflate here, so gzip does the decompressing and the tar is read as it streams out
+fn read_tar_gz(path: &Path, each: impl FnMut(&Entry, &mut dyn Read) -> io::Result<()>) -> io::Result<()> {
+    let mut child = temp::for_child(&mut Command::new("gzip"))
+        .arg("-dc")
+        .arg("--")
+        .arg(path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdout = child.stdout.take().ok_or_else(|| io::Error::other("could not read gzip output"))?;
+    let read = image::read_tar(stdout, each);
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "gzip could not decompress it"));
+    }
+    read
+}
+
+fn entries(path: &Path, kind: Kind) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut add = |entry: &Entry, _: &mut dyn Read| {
+        entries.push(entry.clone());
+        Ok(())
+    };
+    match kind {
+        Kind::Zip => {
+            return read_zip(File::open(path)?);
+        },
+        Kind::Tar => {
+            image::read_tar(File::open(path)?, &mut add)?;
+        },
+        Kind::TarGz => {
+            read_tar_gz(path, &mut add)?;
+        },
+    }
+    Ok(entries)
+}
+
+// the members of an archive found at depth, as files at paths like archive.zip!/dir/file.txt.
+// archives inside archives are listed as plain members
+pub fn list(path: &Path, depth: usize, options: &WalkOptions) -> io::Result<Vec<MyFile>> {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let Some(kind) = kind(&name) else {
+        return Ok(Vec::new());
+    };
+    let mut root = path.as_os_str().to_owned();
+    root.push("!");
+    let root = PathBuf::from(root);
+    Ok(entries(path, kind)?.iter().filter_map(|entry| image::member(&root, depth, entry, options)).collect())
+}
+
+#[cfg(test)]
+fn zip(entries: &[(&str, u32, u16)]) -> Vec<u8> {
+    let mut directory = Vec::new();
+    for (name, size, made_by) in entries {
+        directory.extend_from_slice(b"PK\x01\x02");
+        directory.extend_from_slice(&made_by.to_le_bytes());
+        directory.extend_from_slice(&[20, 0, 0, 0, 0, 0]);
+        // 2024-01-01 12:30:10
+        directory.extend_from_slice(&(12 << 11 | 30 << 5 | 5u16).to_le_bytes());
+        directory.extend_from_slice(&(44 << 9 | 1 << 5 | 1u16).to_le_bytes());
+        directory.extend_from_slice(&[0; 4]);
+        directory.extend_from_slice(&size.to_le_bytes());
+        directory.extend_from_slice(&size.to_le_bytes());
+        directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        directory.extend_from_slice(&[0; 8]);
+        let mode: u32 = if *made_by >> 8 == 3 { 0o120777 << 16 } else { 0 };
+        directory.extend_from_slice(&mode.to_le_bytes());
+        directory.extend_from_slice(&[0; 4]);
+        directory.extend_from_slice(name.as_bytes());
+    }
+    // the members' data isn't needed for listing, so a stand-in takes its place
+    let mut zip = b"PK\x03\x04 member data".to_vec();
+    let offset = zip.len() as u32;
+    zip.extend_from_slice(&directory);
+    zip.extend_from_slice(b"PK\x05\x06\0\0\0\0");
+    zip.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    zip.extend_from_slice(&offset.to_le_bytes());
+    zip.extend_from_slice(b"\x07\0comment");
+    zip
+}
+
+#[test]
+fn test_read_zip() {
+    let archive = zip(&[("docs/", 0, 20), ("docs/readme.txt", 1234, 20), ("bin/run", 12, 3 << 8 | 20)]);
+    let entries = read_zip(io::Cursor::new(archive)).unwrap();
+    let listed: Vec<(&str, u64, u8)> = entries.iter().map(|e| (e.path.as_str(), e.size, e.kind)).collect();
+    assert_eq!(listed, vec![("docs/", 0, b'5'), ("docs/readme.txt", 1234, b'0'), ("bin/run", 12, b'2')]);
+    assert!(entries[1].modified > Date { year: 2023, month: 12, day: 31 }.midnight(true));
+    assert!(read_zip(io::Cursor::new(b"not a zip".to_vec())).is_err());
+
+    let root = Path::new("backup/site.zip!");
+    let file = image::member(root, 2, &entries[1], &WalkOptions::default()).unwrap();
+    assert_eq!(file.path, Path::new("backup/site.zip!/docs/readme.txt"));
+    assert_eq!(file.depth, 4);
+    assert!(image::member(root, 2, &entries[0], &WalkOptions::default()).is_none());
+
+    assert_eq!(kind("logs.TAR.GZ"), Some(Kind::TarGz));
+    assert_eq!(kind("site.tgz"), Some(Kind::TarGz));
+    assert_eq!(kind("notes.txt"), None);
+}
+
+#[test]
+fn test_read_zip_edge_cases() {
+    // an empty archive is just the end record
+    assert_eq!(read_zip(io::Cursor::new(zip(&[]))).unwrap(), Vec::new());
+    // a central directory with fewer entries than the end record counts is an error, not a
+    // shorter listing
+    let mut archive = zip(&[("a.txt", 1, 20), ("b.txt", 2, 20)]);
+    let end = archive.windows(4).rposition(|w| w == b"PK\x05\x06").unwrap();
+    archive[end + 10] = 3;
+    assert!(read_zip(io::Cursor::new(archive)).is_err());
+    assert!(read_zip(io::Cursor::new(Vec::new())).is_err());
+
+    assert!(le(&[1, 2], 1, 2).is_err());
+    assert_eq!(le(&[0x34, 0x12, 0xff], 0, 2).unwrap(), 0x1234);
+    // a date zip can't hold becomes the epoch rather than something made up
+    assert_eq!(dos_time(44 << 9 | 13 << 5 | 1, 0), 0);
+    assert_eq!(dos_time(44 << 9 | 1 << 5, 0), 0);
+    // seconds come in two second steps
+    assert_eq!(dos_time(44 << 9 | 1 << 5 | 1, 1) - dos_time(44 << 9 | 1 << 5 | 1, 0), 2);
+}
+
+#[test]
+fn test_list() {
+    let dir = crate::temp::TestDir::new("archive");
+    let path = dir.join("site.ZIP");
+    std::fs::write(&path, zip(&[("index.html", 10, 20), (".env", 5, 20)])).unwrap();
+
+    // hidden members are left out like hidden files are
+    let files = list(&path, 1, &WalkOptions::default()).unwrap();
+    let paths: Vec<PathBuf> = files.iter().map(|f| f.path.clone()).collect();
+    assert_eq!(paths, vec![dir.join("site.ZIP!/index.html")]);
+    assert_eq!((files[0].size_bytes, files[0].depth), (10, 2));
+
+    // not an archive by name, or not one inside
+    assert!(list(&dir.join("notes.txt"), 1, &WalkOptions::default()).unwrap().is_empty());
+    std::fs::write(dir.join("fake.zip"), "not a zip").unwrap();
+    assert!(list(&dir.join("fake.zip"), 1, &WalkOptions::default()).is_err());
+}
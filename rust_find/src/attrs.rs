@@ -0,0 +1,144 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+// inode flags as set by chattr, from linux/fs.h (libc doesn't export these)
+const FS_IMMUTABLE_FL: u32 = 0x10;
+const FS_APPEND_FL: u32 = 0x20;
+const FS_NODUMP_FL: u32 = 0x40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attr {
+    Immutable,
+    AppendOnly,
+    NoDump,
+}
+
+const ALL: [Attr; 3] = [Attr::Immutable, Attr::AppendOnly, Attr::NoDump];
+
+impl Attr {
+    fn flag(self) -> u32 {
+        match self {
+            Attr::Immutable => FS_IMMUTABLE_FL,
+            Attr::AppendOnly => FS_APPEND_FL,
+            Attr::NoDump => FS_NODUMP_FL,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Attr::Immutable => "immutable",
+            Attr::AppendOnly => "append-only",
+            Attr::NoDump => "nodump",
+        }
+    }
+
+    pub fn is_set(self, flags: u32) -> bool {
+        flags & self.flag() != 0
+    }
+}
+
+impl FromStr for Attr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "immutable" | "i" => Ok(Attr::Immutable),
+            "append-only" | "append" | "a" => Ok(Attr::AppendOnly),
+            "nodump" | "d" => Ok(Attr::NoDump),
+            _ => Err(format!("invalid attribute '{}', expected immutable, append-only or nodump", s)),
+        }
+    }
+}
+
+impl fmt::Display for Attr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// names of the attributes we know about that are set in flags, in lsattr order
+pub fn names(flags: u32) -> Vec<&'static str> {
+    ALL.iter()
+        .filter(|a| a.is_set(flags))
+        .map(|a| a.name())
+        .collect()
+}
+
+// reads the chattr flags of a file. symlinks are not followed and report no flags
+#[cfg(target_os = "linux")]
+pub fn read(path: &Path) -> io::Result<u32> {
+    use std::fs::OpenOptions;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    let file = match OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_NOFOLLOW)
+        .open(path) {
+        Ok(f) => {
+            f
+        },
+        Err(e) if e.raw_os_error() == Some(libc::ELOOP) => {
+            return Ok(0);
+        },
+        Err(e) => {
+            return Err(e);
+        },
+    };
+    let mut flags: libc::c_int = 0;
+    if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) } != 0 {
+        let e = io::Error::last_os_error();
+        // filesystems without inode flags (tmpfs, nfs, ...) just don't have any set
+        if e.raw_os_error() == Some(libc::ENOTTY) || e.raw_os_error() == Some(libc::EOPNOTSUPP) {
+            return Ok(0);
+        }
+        return Err(e);
+    }
+    Ok(flags as u32)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read(_path: &Path) -> io::Result<u32> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "file attributes are only supported on linux"))
+}
+
+#[test]
+fn test_attr_names() {
+    assert_eq!("immutable".parse::<Attr>(), Ok(Attr::Immutable));
+    assert_eq!("a".parse::<Attr>(), Ok(Attr::AppendOnly));
+    assert!("hidden".parse::<Attr>().is_err());
+
+    assert!(Attr::NoDump.is_set(FS_NODUMP_FL | FS_IMMUTABLE_FL));
+    assert!(!Attr::AppendOnly.is_set(FS_NODUMP_FL | FS_IMMUTABLE_FL));
+    assert_eq!(names(FS_NODUMP_FL | FS_IMMUTABLE_FL), vec!["immutable", "nodump"]);
+    assert_eq!(names(0), Vec::<&str>::new());
+}
+
+#[test]
+fn test_attr_edge_cases() {
+    // names are matched as written by chattr's long names or lsattr's letters, with spaces around
+    // them allowed (as in --attr "immutable, nodump")
+    assert_eq!(" nodump ".parse::<Attr>(), Ok(Attr::NoDump));
+    assert_eq!("append".parse::<Attr>(), Ok(Attr::AppendOnly));
+    assert!("Immutable".parse::<Attr>().is_err());
+    assert!("".parse::<Attr>().is_err());
+    // flags we don't know about (compression, extents, ...) are left out
+    assert_eq!(names(0x80000 | 0x4 | FS_APPEND_FL), vec!["append-only"]);
+    assert_eq!(names(u32::MAX), vec!["immutable", "append-only", "nodump"]);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_read() {
+    let dir = crate::temp::TestDir::new("attrs");
+    let file = dir.join("plain");
+    std::fs::write(&file, "").unwrap();
+    // a fresh file has none of the attributes, wherever the temp dir is
+    assert_eq!(names(read(&file).unwrap()), Vec::<&str>::new());
+    // symlinks aren't followed to their target's flags
+    std::os::unix::fs::symlink(&file, dir.join("link")).unwrap();
+    assert_eq!(read(&dir.join("link")).unwrap(), 0);
+    assert!(read(&dir.join("missing")).is_err());
+}
@@ -0,0 +1,99 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::format::json_string;
+use crate::time;
+use crate::ActionSummary;
+
+// what one run did, appended as a single JSON line to the --audit-log file
+#[derive(Debug, Default)]
+pub struct Record {
+    pub matches: usize,
+    pub action: Option<&'static str>,
+    pub dry_run: bool,
+    pub summary: Option<ActionSummary>,
+    pub refused: Option<String>,
+}
+
+impl Record {
+    pub fn to_json(&self, time: i64, cwd: &str, args: &[String]) -> String {
+        let args: Vec<String> = args.iter().map(|a| json_string(a)).collect();
+        let mut json = format!("{{\"time\":{},\"cwd\":{},\"args\":[{}],\"matches\":{}",
+                               time,
+                               json_string(cwd),
+                               args.join(","),
+                               self.matches);
+        if let Some(action) = self.action {
+            json.push_str(&format!(",\"action\":{},\"dry_run\":{}", json_string(action), self.dry_run));
+        }
+        if let Some(summary) = self.summary {
+            json.push_str(&format!(",\"changed\":{},\"unchanged\":{},\"failed\":{}",
+                                   summary.changed,
+                                   summary.unchanged,
+                                   summary.failed));
+        }
+        if let Some(reason) = &self.refused {
+            json.push_str(&format!(",\"refused\":{}", json_string(reason)));
+        }
+        json.push('}');
+        json
+    }
+}
+
+pub fn append(path: &Path, record: &Record) -> io::Result<()> {
+    let args: Vec<String> = std::env::args_os().map(|a| a.to_string_lossy().into_owned()).collect();
+    let cwd = std::env::current_dir().map(|d| d.display().to_string()).unwrap_or_default();
+    let line = record.to_json(time::now(), &cwd, &args);
+    let mut log = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(log, "{}", line)
+}
+
+#[test]
+fn test_record_to_json() {
+    let args = vec!["rust_find".to_string(), "-p".to_string(), "\"quoted\"\\d".to_string()];
+    let record = Record {
+        matches: 3,
+        ..Default::default()
+    };
+    assert_eq!(record.to_json(10, "/tmp", &args),
+               r#"{"time":10,"cwd":"/tmp","args":["rust_find","-p","\"quoted\"\\d"],"matches":3}"#);
+
+    let record = Record {
+        matches: 2,
+        action: Some("chown-map"),
+        summary: Some(ActionSummary { changed: 1, unchanged: 1, failed: 0 }),
+        ..Default::default()
+    };
+    assert_eq!(record.to_json(10, "/", &[]),
+               r#"{"time":10,"cwd":"/","args":[],"matches":2,"action":"chown-map","dry_run":false,"changed":1,"unchanged":1,"failed":0}"#);
+}
+
+#[test]
+fn test_record_edge_cases() {
+    // a refused action is logged along with why, and a dry run says so
+    let record = Record {
+        matches: 5000,
+        action: Some("remove-quarantine"),
+        dry_run: true,
+        refused: Some(String::from("would affect 5000 files\n(more than 1000)")),
+        ..Default::default()
+    };
+    assert_eq!(record.to_json(-1, "C:\\work", &[String::from("é")]),
+               r#"{"time":-1,"cwd":"C:\\work","args":["é"],"matches":5000,"action":"remove-quarantine","dry_run":true,"refused":"would affect 5000 files\n(more than 1000)"}"#);
+}
+
+#[test]
+fn test_append() {
+    let dir = crate::temp::TestDir::new("audit");
+    let path = dir.join("audit.jsonl");
+    append(&path, &Record { matches: 1, ..Default::default() }).unwrap();
+    append(&path, &Record { matches: 2, ..Default::default() }).unwrap();
+    // one line per run, added to what is there
+    let log = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("{\"time\":") && lines[0].ends_with("\"matches\":1}"), "{}", lines[0]);
+    assert!(lines[1].ends_with("\"matches\":2}"), "{}", lines[1]);
+    assert!(append(&path.join("not a dir"), &Record::default()).is_err());
+}
@@ -0,0 +1,154 @@
+use crate::format::{json_string, SCHEMA_VERSIONS};
+use crate::queries;
+
+fn json_list(items: &[&str]) -> String {
+    let items: Vec<String> = items.iter().map(|i| json_string(i)).collect();
+    format!("[{}]", items.join(","))
+}
+
+// the filter flags this build has, without their dashes
+pub fn filters() -> Vec<&'static str> {
+    let mut filters = vec!["patterns", "ext", "glob", "depth-exactly", "depth-range", "dir-min-entries",
+                           "dir-max-entries", "size-min", "size-max", "changed-within", "changed-before",
+                           "accessed-within", "accessed-before", "created-within", "created-before",
+                           "modified-between", "modified-on", "first-committed-before", "broken-symlinks",
+                           "utc", "type", "empty", "min-entries"];
+    if cfg!(target_os = "linux") {
+        filters.push("attr");
+        filters.push("has-capabilities");
+    }
+    if cfg!(any(target_os = "linux", target_os = "macos")) {
+        filters.push("quarantined");
+    }
+    filters
+}
+
+// what this particular build can do, for wrapper tools that want to adapt instead of probing
+pub fn json() -> String {
+    let mut features = Vec::new();
+    if cfg!(feature = "color") {
+        features.push("color");
+    }
+    if cfg!(feature = "webhook") {
+        features.push("webhook");
+    }
+
+    let mut actions = vec!["emit-script", "find-name-collisions", "plan"];
+    if cfg!(unix) {
+        actions.push("exec");
+        actions.push("exec-batch");
+        actions.push("chown-map");
+    }
+    if cfg!(any(target_os = "linux", target_os = "macos")) {
+        actions.push("remove-quarantine");
+    }
+    if cfg!(unix) {
+        actions.push("scan-clamd");
+    }
+
+    let mut outputs = vec!["stdout", "file"];
+    if cfg!(unix) {
+        outputs.push("socket");
+    }
+    if cfg!(feature = "webhook") {
+        outputs.push("webhook");
+    }
+
+    // what can be added to each match's line or record
+    let mut annotations = vec!["show-attrs", "score-junk", "number"];
+    if cfg!(unix) {
+        annotations.push("annotate-cmd");
+    }
+
+    let mut signals = Vec::new();
+    if cfg!(unix) {
+        signals.push("SIGUSR1");
+    }
+
+    let profiles = queries::builtin_names();
+    format!("{{\"version\":{},\"features\":{},\"formats\":{},\"schema_versions\":{},\"inputs\":{},\"dir_schemes\":{},\"outputs\":{},\"filters\":{},\"annotations\":{},\"actions\":{},\"reports\":{},\"subcommands\":{},\"profiles\":{},\"status_signals\":{}}}",
+            json_string(env!("CARGO_PKG_VERSION")),
+            json_list(&features),
+            json_list(&["text", "json"]),
+            json_list(SCHEMA_VERSIONS),
+            json_list(&["stdin", "dirs-from-stdin", "search-archives"]),
+            json_list(&["docker://"]),
+            json_list(&outputs),
+            json_list(&filters()),
+            json_list(&annotations),
+            json_list(&actions),
+            json_list(&["audit-log", "action-report"]),
+            json_list(&["repl", "last", "merge", "homes", "apply"]),
+            json_list(&profiles),
+            json_list(&signals))
+}
+
+#[test]
+fn test_capabilities_json() {
+    let json = json();
+    assert!(json.starts_with("{\"version\":\""));
+    assert!(json.contains("\"formats\":[\"text\",\"json\"]"));
+    assert!(json.contains("\"schema_versions\":[\"1\",\"2\",\"3\",\"4\"]"));
+    assert!(json.contains("\"outputs\":[\"stdout\",\"file\""));
+    assert!(json.contains("\"profiles\":[\"big\",\"recent\",\"junk\"]"));
+    assert!(json.ends_with('}'));
+}
+
+#[test]
+fn test_capabilities_are_flags() {
+    use structopt::clap::ErrorKind;
+    use structopt::StructOpt;
+
+    // everything advertised has to be something the command line takes, or wrappers that trust
+    // this list get "unknown argument" back
+    let json = json();
+    let list = |key: &str| -> Vec<String> {
+        let start = json.find(&format!("\"{}\":[", key)).unwrap() + key.len() + 4;
+        let end = start + json[start..].find(']').unwrap();
+        json[start..end].split(',').filter(|s| !s.is_empty()).map(|s| s.trim_matches('"').to_string()).collect()
+    };
+    for key in ["inputs", "filters", "annotations", "actions", "reports"] {
+        for flag in list(key) {
+            if let Err(e) = crate::Cli::from_iter_safe(["rust_find", &format!("--{}", flag)]) {
+                assert_ne!(e.kind, ErrorKind::UnknownArgument, "--{} is advertised but unknown", flag);
+            }
+        }
+    }
+    for subcommand in list("subcommands") {
+        if let Err(e) = crate::Cli::from_iter_safe(["rust_find", &subcommand]) {
+            assert_ne!(e.kind, ErrorKind::UnrecognizedSubcommand, "{} is advertised but unknown", subcommand);
+        }
+    }
+
+    // and every filter the command line takes is advertised, apart from those that only work on
+    // some systems
+    let mut help = Vec::new();
+    crate::Filters::clap().write_long_help(&mut help).unwrap();
+    let help = String::from_utf8(help).unwrap();
+    let advertised = filters();
+    for line in help.lines() {
+        // flags start their line, descriptions are indented further and may mention flags too
+        let line = line.trim_start();
+        if !line.starts_with('-') {
+            continue;
+        }
+        let flag = match line.split([' ', ',']).find_map(|w| w.strip_prefix("--")) {
+            Some(flag) => {
+                flag
+            },
+            None => {
+                continue;
+            },
+        };
+        let platform_only = ["attr", "has-capabilities", "quarantined"].contains(&flag);
+        if !["help", "version"].contains(&flag) && !platform_only {
+            assert!(advertised.contains(&flag), "--{} is a filter but isn't advertised", flag);
+        }
+    }
+
+    // exec needs sh, so it only shows up where there is one
+    assert_eq!(list("actions").contains(&String::from("exec")), cfg!(unix));
+    assert_eq!(list("features").contains(&String::from("webhook")), cfg!(feature = "webhook"));
+    assert_eq!(json_list(&[]), "[]");
+    assert_eq!(json_list(&["a\"b"]), "[\"a\\\"b\"]");
+}
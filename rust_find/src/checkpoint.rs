@@ -0,0 +1,127 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::temp;
+
+const HEADER: &[u8] = b"rust-find checkpoint 1\n";
+
+// a directory an interrupted walk still has to read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub dir: PathBuf,
+    pub depth: usize,
+    // the device of its root, with --one-file-system
+    pub device: Option<u64>,
+    // its subdirectories are already part of the checkpoint, so only its files are left
+    pub files_only: bool,
+}
+
+// one record per entry, "DEPTH\tDEVICE\tKIND\tPATH" ended by a NUL byte, since paths can hold
+// anything but NUL
+fn encode(entries: &[Entry]) -> Vec<u8> {
+    let mut bytes = HEADER.to_vec();
+    for entry in entries {
+        let device = entry.device.map(|d| d.to_string()).unwrap_or_else(|| String::from("-"));
+        let kind = if entry.files_only { "files" } else { "tree" };
+        bytes.extend_from_slice(format!("{}\t{}\t{}\t", entry.depth, device, kind).as_bytes());
+        bytes.extend_from_slice(entry.dir.as_os_str().as_encoded_bytes());
+        bytes.push(0);
+    }
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> Option<Vec<Entry>> {
+    let records = bytes.strip_prefix(HEADER)?;
+    let mut entries = Vec::new();
+    for record in records.split(|&b| b == 0).filter(|r| !r.is_empty()) {
+        let mut fields = record.splitn(4, |&b| b == b'\t');
+        let mut text = || fields.next().and_then(|f| std::str::from_utf8(f).ok().map(String::from));
+        let depth = text()?.parse().ok()?;
+        let device = match text()?.as_str() {
+            "-" => None,
+            device => Some(device.parse().ok()?),
+        };
+        let files_only = match text()?.as_str() {
+            "files" => true,
+            "tree" => false,
+            _ => {
+                return None;
+            },
+        };
+        let dir = fields.next()?;
+        entries.push(Entry { dir: path_from_bytes(dir), depth, device, files_only });
+    }
+    Some(entries)
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+// written next to the checkpoint and renamed over it, so an interruption while saving leaves the
+// previous checkpoint intact
+pub fn save(path: &Path, entries: &[Entry]) -> io::Result<()> {
+    let partial = temp::partial(path);
+    fs::write(&partial, encode(entries))?;
+    fs::rename(&partial, path)
+}
+
+pub fn load(path: &Path) -> io::Result<Vec<Entry>> {
+    decode(&fs::read(path)?).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a rust-find checkpoint"))
+}
+
+#[test]
+fn test_encode_checkpoint() {
+    let entries = vec![
+        Entry { dir: PathBuf::from("/data/a\tb"), depth: 3, device: Some(2049), files_only: false },
+        Entry { dir: PathBuf::from("relative/dir"), depth: 1, device: None, files_only: true },
+    ];
+    assert_eq!(decode(&encode(&entries)), Some(entries));
+    assert_eq!(decode(&encode(&[])), Some(Vec::new()));
+    assert_eq!(decode(b"something else"), None);
+    assert_eq!(decode(b"rust-find checkpoint 1\nx\t-\ttree\t/a\0"), None);
+}
+
+#[test]
+fn test_checkpoint_edge_cases() {
+    // anything but NUL can be in a path, including what separates the fields
+    let entries = vec![Entry { dir: PathBuf::from("/a\tb\nc\\d"), depth: 0, device: Some(u64::MAX), files_only: true }];
+    assert_eq!(decode(&encode(&entries)), Some(entries));
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let entries = vec![Entry { dir: PathBuf::from(std::ffi::OsStr::from_bytes(b"/caf\xe9")), depth: 2, device: None, files_only: false }];
+        assert_eq!(decode(&encode(&entries)), Some(entries));
+    }
+    // a record it can't make sense of spoils the whole checkpoint rather than silently losing a
+    // directory
+    assert_eq!(decode(b"rust-find checkpoint 1\n1\t-\ttree\t/a\x002\tx\ttree\t/b\0"), None);
+    assert_eq!(decode(b"rust-find checkpoint 1\n1\t-\tsome\t/a\0"), None);
+    assert_eq!(decode(b"rust-find checkpoint 1\n1\t-\ttree\0"), None);
+    assert_eq!(decode(b"rust-find checkpoint 2\n"), None);
+    assert_eq!(decode(b""), None);
+}
+
+#[test]
+fn test_save_and_load() {
+    let dir = crate::temp::TestDir::new("checkpoint");
+    let path = dir.join("scan.checkpoint");
+    let entries = vec![Entry { dir: PathBuf::from("/srv"), depth: 1, device: None, files_only: false }];
+    save(&path, &entries).unwrap();
+    save(&path, &entries[..0]).unwrap();
+    // a later save replaces the earlier one, and leaves nothing next to it
+    assert_eq!(load(&path).unwrap(), Vec::new());
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+    fs::write(&path, "not a checkpoint").unwrap();
+    assert_eq!(load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    assert_eq!(load(&dir.join("missing")).unwrap_err().kind(), io::ErrorKind::NotFound);
+}
@@ -0,0 +1,144 @@
+use std::str::FromStr;
+
+use crate::output;
+use crate::report::{Operation, Outcome};
+use crate::MyFile;
+
+// one old_uid:new_uid pair from --chown-map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UidMapping {
+    pub from: u32,
+    pub to: u32,
+}
+
+impl FromStr for UidMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid uid mapping '{}', expected OLD_UID:NEW_UID", s);
+        let (from, to) = s.split_once(':').ok_or_else(invalid)?;
+        Ok(UidMapping {
+            from: from.trim().parse().map_err(|_| invalid())?,
+            to: to.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+// first mapping that applies to uid wins
+#[cfg_attr(not(unix), allow(dead_code))]
+pub fn new_owner(uid: u32, mappings: &[UidMapping]) -> Option<u32> {
+    mappings.iter().find(|m| m.from == uid).map(|m| m.to)
+}
+
+// remaps the owner of every file whose uid appears in the mappings. symlinks themselves are
+// changed rather than their targets, matching how the walker treats them
+#[cfg(unix)]
+pub fn run(files: &[&MyFile], mappings: &[UidMapping], dry_run: bool) -> Vec<Operation> {
+    use std::os::unix::fs::MetadataExt;
+
+    if !dry_run && unsafe { libc::geteuid() } != 0 {
+        output::warn("--chown-map requires root", "rerun with sudo or use --dry-run");
+        return files.iter()
+            .map(|file| Operation::new(&file.path, Outcome::Failed, Some(String::from("requires root"))))
+            .collect();
+    }
+
+    let mut operations = Vec::new();
+    for file in files {
+        let uid = match std::fs::symlink_metadata(&file.path) {
+            Ok(m) => {
+                m.uid()
+            },
+            Err(e) => {
+                output::warn("could not access file", format!("{}: {}", file.path.display(), e));
+                operations.push(Operation::new(&file.path, Outcome::Failed, Some(e.to_string())));
+                continue;
+            },
+        };
+        let to = match new_owner(uid, mappings) {
+            Some(to) if to != uid => {
+                to
+            },
+            _ => {
+                operations.push(Operation::new(&file.path, Outcome::Unchanged, None));
+                continue;
+            },
+        };
+        let change = format!("{} -> {}", uid, to);
+        if dry_run {
+            println!("would change owner of {}: {}", file.path.display(), change);
+            operations.push(Operation::new(&file.path, Outcome::Changed, Some(change)));
+            continue;
+        }
+        match std::os::unix::fs::lchown(&file.path, Some(to), None) {
+            Ok(()) => {
+                println!("changed owner of {}: {}", file.path.display(), change);
+                operations.push(Operation::new(&file.path, Outcome::Changed, Some(change)));
+            },
+            Err(e) => {
+                output::warn("could not change owner", format!("{}: {}", file.path.display(), e));
+                operations.push(Operation::new(&file.path, Outcome::Failed, Some(e.to_string())));
+            },
+        }
+    }
+    operations
+}
+
+#[cfg(not(unix))]
+pub fn run(files: &[&MyFile], _mappings: &[UidMapping], _dry_run: bool) -> Vec<Operation> {
+    output::warn("--chown-map is not supported on this platform", "no owners were changed");
+    files.iter()
+        .map(|file| Operation::new(&file.path, Outcome::Failed, Some(String::from("not supported on this platform"))))
+        .collect()
+}
+
+#[test]
+fn test_uid_mapping() {
+    let mappings: Vec<UidMapping> = vec!["1000:2000".parse().unwrap(), "1001:2001".parse().unwrap()];
+    assert_eq!(mappings[0], UidMapping { from: 1000, to: 2000 });
+    assert_eq!(new_owner(1000, &mappings), Some(2000));
+    assert_eq!(new_owner(1001, &mappings), Some(2001));
+    assert_eq!(new_owner(0, &mappings), None);
+
+    assert!("1000".parse::<UidMapping>().is_err());
+    assert!("alice:bob".parse::<UidMapping>().is_err());
+    assert!("-1:5".parse::<UidMapping>().is_err());
+}
+
+#[test]
+fn test_uid_mapping_edge_cases() {
+    assert_eq!(" 0 : 65534 ".parse::<UidMapping>(), Ok(UidMapping { from: 0, to: 65534 }));
+    assert!("1000:2000:3000".parse::<UidMapping>().is_err());
+    assert!("4294967296:1".parse::<UidMapping>().is_err());
+    assert!(":1".parse::<UidMapping>().is_err());
+    // the first mapping for a uid wins, and mappings aren't chained
+    let mappings: Vec<UidMapping> = vec!["1:2".parse().unwrap(), "1:3".parse().unwrap(), "2:4".parse().unwrap()];
+    assert_eq!(new_owner(1, &mappings), Some(2));
+    assert_eq!(new_owner(2, &mappings), Some(4));
+    assert_eq!(new_owner(1, &[]), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run_dry() {
+    use std::os::unix::fs::MetadataExt;
+    use std::path::PathBuf;
+
+    let dir = crate::temp::TestDir::new("chown");
+    std::fs::write(dir.join("a"), "").unwrap();
+    let uid = std::fs::metadata(dir.join("a")).unwrap().uid();
+    let file = |name: &str| MyFile { path: dir.join(name), ..Default::default() };
+    let (owned, missing) = (file("a"), file("missing"));
+    let outcomes = |mappings: &[UidMapping]| -> Vec<(PathBuf, Outcome, Option<String>)> {
+        run(&[&owned, &missing], mappings, true).into_iter().map(|o| (o.path, o.outcome, o.detail)).collect()
+    };
+
+    let changed = outcomes(&[UidMapping { from: uid, to: uid + 1 }]);
+    assert_eq!(changed[0], (owned.path.clone(), Outcome::Changed, Some(format!("{} -> {}", uid, uid + 1))));
+    assert_eq!(changed[1].1, Outcome::Failed);
+    // a mapping to the owner it already has, or for somebody else, leaves the file alone
+    assert_eq!(outcomes(&[UidMapping { from: uid, to: uid }])[0].1, Outcome::Unchanged);
+    assert_eq!(outcomes(&[UidMapping { from: uid + 1, to: uid }])[0].1, Outcome::Unchanged);
+    // a dry run really changes nothing
+    assert_eq!(std::fs::metadata(dir.join("a")).unwrap().uid(), uid);
+}
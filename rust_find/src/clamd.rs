@@ -0,0 +1,124 @@
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(not(unix), allow(dead_code))]
+pub enum Verdict {
+    Clean,
+    // the signature name clamd reported
+    Infected(String),
+}
+
+// streams the file to clamd with INSTREAM rather than sending its path, so clamd doesn't need to
+// be able to read it and the socket can belong to a daemon running as another user
+#[cfg(unix)]
+pub fn scan(socket: &Path, path: &Path) -> io::Result<Verdict> {
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut file = File::open(path)?;
+    let mut stream = UnixStream::connect(socket)?;
+    stream.write_all(b"zINSTREAM\0")?;
+    let mut chunk = vec![0; 64 * 1024];
+    loop {
+        let n = file.read(&mut chunk)?;
+        stream.write_all(&(n as u32).to_be_bytes())?;
+        if n == 0 {
+            break;
+        }
+        stream.write_all(&chunk[..n])?;
+    }
+
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply)?;
+    parse_reply(&String::from_utf8_lossy(&reply))
+}
+
+#[cfg(not(unix))]
+pub fn scan(_socket: &Path, _path: &Path) -> io::Result<Verdict> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "clamd is only reachable over a unix socket"))
+}
+
+// "stream: OK", "stream: Eicar-Signature FOUND" or "... ERROR", nul terminated in z mode
+#[cfg_attr(not(unix), allow(dead_code))]
+fn parse_reply(reply: &str) -> io::Result<Verdict> {
+    let reply = reply.trim_end_matches(['\0', '\n']);
+    let result = reply.strip_prefix("stream: ").unwrap_or(reply);
+    if result == "OK" {
+        Ok(Verdict::Clean)
+    } else if let Some(name) = result.strip_suffix(" FOUND") {
+        Ok(Verdict::Infected(name.to_string()))
+    } else {
+        Err(io::Error::other(format!("clamd answered '{}'", reply)))
+    }
+}
+
+#[test]
+fn test_parse_reply() {
+    assert_eq!(parse_reply("stream: OK\0").unwrap(), Verdict::Clean);
+    assert_eq!(parse_reply("stream: Win.Test.EICAR_HDB-1 FOUND\0").unwrap(),
+               Verdict::Infected("Win.Test.EICAR_HDB-1".to_string()));
+    assert!(parse_reply("INSTREAM size limit exceeded. ERROR\0").is_err());
+    assert!(parse_reply("").is_err());
+}
+
+#[test]
+fn test_parse_reply_edge_cases() {
+    // without z mode clamd ends the reply with a newline instead
+    assert_eq!(parse_reply("stream: OK\n").unwrap(), Verdict::Clean);
+    // only the last " FOUND" ends the signature name
+    assert_eq!(parse_reply("stream: Sig FOUND twice FOUND\0").unwrap(), Verdict::Infected(String::from("Sig FOUND twice")));
+    assert!(parse_reply("stream: OK but not really\0").is_err());
+    assert!(parse_reply("stream: FOUND\0").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_scan() {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixListener;
+
+    let dir = crate::temp::TestDir::new("clamd");
+    let socket = dir.join("clamd.sock");
+    let listener = UnixListener::bind(&socket).unwrap();
+    let file = dir.join("sample");
+    // more than one chunk, so the stream is split
+    let contents: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&file, &contents).unwrap();
+
+    // a stand-in for clamd that checks what it was sent and answers each connection in turn
+    let replies = ["stream: OK\0", "stream: Eicar-Test FOUND\0", "INSTREAM size limit exceeded. ERROR\0"];
+    let server = std::thread::spawn(move || {
+        let mut received = Vec::new();
+        for reply in replies {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut command = [0; 10];
+            stream.read_exact(&mut command).unwrap();
+            assert_eq!(&command, b"zINSTREAM\0");
+            let mut data = Vec::new();
+            loop {
+                let mut len = [0; 4];
+                stream.read_exact(&mut len).unwrap();
+                let len = u32::from_be_bytes(len) as usize;
+                if len == 0 {
+                    break;
+                }
+                let mut chunk = vec![0; len];
+                stream.read_exact(&mut chunk).unwrap();
+                data.extend_from_slice(&chunk);
+            }
+            received.push(data);
+            stream.write_all(reply.as_bytes()).unwrap();
+        }
+        received
+    });
+    assert_eq!(scan(&socket, &file).unwrap(), Verdict::Clean);
+    assert_eq!(scan(&socket, &file).unwrap(), Verdict::Infected(String::from("Eicar-Test")));
+    assert!(scan(&socket, &file).is_err());
+    assert!(server.join().unwrap().iter().all(|data| *data == contents));
+
+    // nothing is sent for a file that can't be opened, and a missing socket is an error
+    assert!(scan(&socket, &dir.join("missing")).is_err());
+    assert!(scan(&dir.join("missing.sock"), &file).is_err());
+}
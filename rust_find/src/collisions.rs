@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::MyFile;
+
+// canonical decompositions of the precomposed latin letters (U+00C0 to U+017F), the ones that
+// actually show up in file names. macOS stores names decomposed while linux keeps whatever bytes
+// it was given, so "café" typed on each can end up as two different names in one directory
+const DECOMPOSITIONS: [(char, char, char); 161] = [
+    ('À', 'A', '\u{300}'), ('Á', 'A', '\u{301}'), ('Â', 'A', '\u{302}'), ('Ã', 'A', '\u{303}'),
+    ('Ä', 'A', '\u{308}'), ('Å', 'A', '\u{30a}'), ('Ç', 'C', '\u{327}'), ('È', 'E', '\u{300}'),
+    ('É', 'E', '\u{301}'), ('Ê', 'E', '\u{302}'), ('Ë', 'E', '\u{308}'), ('Ì', 'I', '\u{300}'),
+    ('Í', 'I', '\u{301}'), ('Î', 'I', '\u{302}'), ('Ï', 'I', '\u{308}'), ('Ñ', 'N', '\u{303}'),
+    ('Ò', 'O', '\u{300}'), ('Ó', 'O', '\u{301}'), ('Ô', 'O', '\u{302}'), ('Õ', 'O', '\u{303}'),
+    ('Ö', 'O', '\u{308}'), ('Ù', 'U', '\u{300}'), ('Ú', 'U', '\u{301}'), ('Û', 'U', '\u{302}'),
+    ('Ü', 'U', '\u{308}'), ('Ý', 'Y', '\u{301}'), ('à', 'a', '\u{300}'), ('á', 'a', '\u{301}'),
+    ('â', 'a', '\u{302}'), ('ã', 'a', '\u{303}'), ('ä', 'a', '\u{308}'), ('å', 'a', '\u{30a}'),
+    ('ç', 'c', '\u{327}'), ('è', 'e', '\u{300}'), ('é', 'e', '\u{301}'), ('ê', 'e', '\u{302}'),
+    ('ë', 'e', '\u{308}'), ('ì', 'i', '\u{300}'), ('í', 'i', '\u{301}'), ('î', 'i', '\u{302}'),
+    ('ï', 'i', '\u{308}'), ('ñ', 'n', '\u{303}'), ('ò', 'o', '\u{300}'), ('ó', 'o', '\u{301}'),
+    ('ô', 'o', '\u{302}'), ('õ', 'o', '\u{303}'), ('ö', 'o', '\u{308}'), ('ù', 'u', '\u{300}'),
+    ('ú', 'u', '\u{301}'), ('û', 'u', '\u{302}'), ('ü', 'u', '\u{308}'), ('ý', 'y', '\u{301}'),
+    ('ÿ', 'y', '\u{308}'), ('Ā', 'A', '\u{304}'), ('ā', 'a', '\u{304}'), ('Ă', 'A', '\u{306}'),
+    ('ă', 'a', '\u{306}'), ('Ą', 'A', '\u{328}'), ('ą', 'a', '\u{328}'), ('Ć', 'C', '\u{301}'),
+    ('ć', 'c', '\u{301}'), ('Ĉ', 'C', '\u{302}'), ('ĉ', 'c', '\u{302}'), ('Ċ', 'C', '\u{307}'),
+    ('ċ', 'c', '\u{307}'), ('Č', 'C', '\u{30c}'), ('č', 'c', '\u{30c}'), ('Ď', 'D', '\u{30c}'),
+    ('ď', 'd', '\u{30c}'), ('Ē', 'E', '\u{304}'), ('ē', 'e', '\u{304}'), ('Ĕ', 'E', '\u{306}'),
+    ('ĕ', 'e', '\u{306}'), ('Ė', 'E', '\u{307}'), ('ė', 'e', '\u{307}'), ('Ę', 'E', '\u{328}'),
+    ('ę', 'e', '\u{328}'), ('Ě', 'E', '\u{30c}'), ('ě', 'e', '\u{30c}'), ('Ĝ', 'G', '\u{302}'),
+    ('ĝ', 'g', '\u{302}'), ('Ğ', 'G', '\u{306}'), ('ğ', 'g', '\u{306}'), ('Ġ', 'G', '\u{307}'),
+    ('ġ', 'g', '\u{307}'), ('Ģ', 'G', '\u{327}'), ('ģ', 'g', '\u{327}'), ('Ĥ', 'H', '\u{302}'),
+    ('ĥ', 'h', '\u{302}'), ('Ĩ', 'I', '\u{303}'), ('ĩ', 'i', '\u{303}'), ('Ī', 'I', '\u{304}'),
+    ('ī', 'i', '\u{304}'), ('Ĭ', 'I', '\u{306}'), ('ĭ', 'i', '\u{306}'), ('Į', 'I', '\u{328}'),
+    ('į', 'i', '\u{328}'), ('İ', 'I', '\u{307}'), ('Ĵ', 'J', '\u{302}'), ('ĵ', 'j', '\u{302}'),
+    ('Ķ', 'K', '\u{327}'), ('ķ', 'k', '\u{327}'), ('Ĺ', 'L', '\u{301}'), ('ĺ', 'l', '\u{301}'),
+    ('Ļ', 'L', '\u{327}'), ('ļ', 'l', '\u{327}'), ('Ľ', 'L', '\u{30c}'), ('ľ', 'l', '\u{30c}'),
+    ('Ń', 'N', '\u{301}'), ('ń', 'n', '\u{301}'), ('Ņ', 'N', '\u{327}'), ('ņ', 'n', '\u{327}'),
+    ('Ň', 'N', '\u{30c}'), ('ň', 'n', '\u{30c}'), ('Ō', 'O', '\u{304}'), ('ō', 'o', '\u{304}'),
+    ('Ŏ', 'O', '\u{306}'), ('ŏ', 'o', '\u{306}'), ('Ő', 'O', '\u{30b}'), ('ő', 'o', '\u{30b}'),
+    ('Ŕ', 'R', '\u{301}'), ('ŕ', 'r', '\u{301}'), ('Ŗ', 'R', '\u{327}'), ('ŗ', 'r', '\u{327}'),
+    ('Ř', 'R', '\u{30c}'), ('ř', 'r', '\u{30c}'), ('Ś', 'S', '\u{301}'), ('ś', 's', '\u{301}'),
+    ('Ŝ', 'S', '\u{302}'), ('ŝ', 's', '\u{302}'), ('Ş', 'S', '\u{327}'), ('ş', 's', '\u{327}'),
+    ('Š', 'S', '\u{30c}'), ('š', 's', '\u{30c}'), ('Ţ', 'T', '\u{327}'), ('ţ', 't', '\u{327}'),
+    ('Ť', 'T', '\u{30c}'), ('ť', 't', '\u{30c}'), ('Ũ', 'U', '\u{303}'), ('ũ', 'u', '\u{303}'),
+    ('Ū', 'U', '\u{304}'), ('ū', 'u', '\u{304}'), ('Ŭ', 'U', '\u{306}'), ('ŭ', 'u', '\u{306}'),
+    ('Ů', 'U', '\u{30a}'), ('ů', 'u', '\u{30a}'), ('Ű', 'U', '\u{30b}'), ('ű', 'u', '\u{30b}'),
+    ('Ų', 'U', '\u{328}'), ('ų', 'u', '\u{328}'), ('Ŵ', 'W', '\u{302}'), ('ŵ', 'w', '\u{302}'),
+    ('Ŷ', 'Y', '\u{302}'), ('ŷ', 'y', '\u{302}'), ('Ÿ', 'Y', '\u{308}'), ('Ź', 'Z', '\u{301}'),
+    ('ź', 'z', '\u{301}'), ('Ż', 'Z', '\u{307}'), ('ż', 'z', '\u{307}'), ('Ž', 'Z', '\u{30c}'),
+    ('ž', 'z', '\u{30c}'),
+];
+
+// what a case-insensitive, normalization-insensitive filesystem (APFS, NTFS) would compare
+pub fn fold(name: &str) -> String {
+    let mut decomposed = String::with_capacity(name.len());
+    for c in name.chars() {
+        match DECOMPOSITIONS.binary_search_by_key(&c, |(composed, _, _)| *composed) {
+            Ok(i) => {
+                decomposed.push(DECOMPOSITIONS[i].1);
+                decomposed.push(DECOMPOSITIONS[i].2);
+            },
+            Err(_) => {
+                decomposed.push(c);
+            },
+        }
+    }
+    decomposed.to_lowercase()
+}
+
+// groups of files in the same directory whose names differ but fold to the same thing, sorted by
+// directory
+pub fn find<'a>(files: &[&'a MyFile]) -> Vec<(PathBuf, Vec<&'a MyFile>)> {
+    let mut groups: BTreeMap<(&Path, String), Vec<&MyFile>> = BTreeMap::new();
+    for file in files {
+        let dir = file.path.parent().unwrap_or(Path::new(""));
+        groups.entry((dir, fold(file.name()))).or_default().push(file);
+    }
+    groups.into_iter()
+        .filter(|(_, names)| names.len() > 1)
+        .map(|((dir, _), names)| (dir.to_path_buf(), names))
+        .collect()
+}
+
+#[test]
+fn test_find_collisions() {
+    assert_eq!(fold("Caf\u{e9}.TXT"), "cafe\u{301}.txt");
+    assert_eq!(fold("cafe\u{301}.txt"), "cafe\u{301}.txt");
+    assert_ne!(fold("cafe.txt"), fold("caf\u{e9}.txt"));
+
+    let file = |path: &str| MyFile {
+        path: PathBuf::from(path),
+        ..Default::default()
+    };
+    let readme = file("docs/README.md");
+    let readme_lower = file("docs/readme.md");
+    let other_dir = file("src/readme.md");
+    let composed = file("docs/caf\u{e9}.txt");
+    let decomposed = file("docs/cafe\u{301}.txt");
+    let files = vec![&readme, &readme_lower, &other_dir, &composed, &decomposed];
+
+    let collisions = find(&files);
+    assert_eq!(collisions, vec![
+        (PathBuf::from("docs"), vec![&composed, &decomposed]),
+        (PathBuf::from("docs"), vec![&readme, &readme_lower]),
+    ]);
+}
+
+#[test]
+fn test_fold_edge_cases() {
+    // fold looks letters up by binary search, which only works on a sorted table
+    assert!(DECOMPOSITIONS.windows(2).all(|w| w[0].0 < w[1].0));
+    assert!(DECOMPOSITIONS.iter().all(|(composed, base, _)| composed.is_alphabetic() && base.is_ascii_alphabetic()));
+    // case and composition both fold away, in either order
+    assert_eq!(fold("\u{c9}T\u{c9}"), fold("e\u{301}te\u{301}"));
+    assert_eq!(fold("\u{17d}ofia"), fold("z\u{30c}ofia"));
+    // letters outside the table still fold their case
+    assert_eq!(fold("\u{3a3}\u{3b1}"), fold("\u{3c3}\u{3b1}"));
+    assert_eq!(fold(""), "");
+}
+
+#[test]
+fn test_find_edge_cases() {
+    let file = |path: &str| MyFile { path: PathBuf::from(path), ..Default::default() };
+    // any number of names can collide, and files without a directory collide with each other
+    let (a, b, c) = (file("Makefile"), file("makefile"), file("MAKEFILE"));
+    let (d, e) = (file("sub/Makefile"), file("sub/notes.txt"));
+    let collisions = find(&[&a, &d, &b, &e, &c]);
+    assert_eq!(collisions, vec![(PathBuf::from(""), vec![&a, &b, &c])]);
+    assert!(find(&[&d, &e]).is_empty());
+    assert!(find(&[]).is_empty());
+}
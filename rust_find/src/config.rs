@@ -0,0 +1,442 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::output;
+
+// where rust-find keeps its files: $XDG_CONFIG_HOME/rust-find, or ~/.config/rust-find
+pub fn dir() -> Option<PathBuf> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => {
+            PathBuf::from(dir)
+        },
+        _ => {
+            PathBuf::from(std::env::var_os("HOME")?).join(".config")
+        },
+    };
+    Some(config.join("rust-find"))
+}
+
+// walk settings for one subtree, from a section of config.toml like
+//   [paths."/mnt/nas"]
+//   threads = 2
+//   follow_symlinks = false
+// threads caps how many directories in the subtree are read at once, whatever --threads is, so a
+// slow network mount isn't flooded. follow_symlinks takes the place of --follow there
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathConfig {
+    pub path: PathBuf,
+    pub threads: Option<usize>,
+    pub follow: Option<bool>,
+}
+
+// commands saved with --save-query, from the [queries] section of config.toml:
+//   [queries]
+//   big = ["--sort", "size", "--reverse", "--limit", "50"]
+//   "old logs" = ['-p', '\.log$', '--changed-before', '30d']
+// each array is one line, and a later query with the same name replaces the earlier one
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    pub paths: Vec<PathConfig>,
+    pub queries: Vec<(String, Vec<String>)>,
+}
+
+enum Section {
+    Paths(PathBuf),
+    Queries,
+}
+
+// a basic (escapes allowed) or literal string at the start of s, and what follows it
+fn take_string(s: &str) -> Option<(String, &str)> {
+    if let Some(literal) = s.strip_prefix('\'') {
+        let (string, rest) = literal.split_once('\'')?;
+        return Some((string.to_string(), rest));
+    }
+    let basic = s.strip_prefix('"')?;
+    let mut string = String::new();
+    let mut chars = basic.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                string.push(match chars.next()?.1 {
+                    't' => '\t',
+                    'n' => '\n',
+                    'r' => '\r',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'u' => {
+                        let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                        if hex.len() != 4 {
+                            return None;
+                        }
+                        char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                    },
+                    c @ ('"' | '\\') => c,
+                    _ => {
+                        return None;
+                    },
+                });
+            },
+            '"' => {
+                return Some((string, &basic[i + 1..]));
+            },
+            c => {
+                string.push(c);
+            },
+        }
+    }
+    None
+}
+
+// s as a basic string that take_string reads back
+fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            },
+            '\t' => {
+                quoted.push_str("\\t");
+            },
+            '\n' => {
+                quoted.push_str("\\n");
+            },
+            c if c.is_control() => {
+                quoted.push_str(&format!("\\u{:04X}", c as u32));
+            },
+            c => {
+                quoted.push(c);
+            },
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn is_bare(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+// a bare or quoted key at the start of s, and what follows it
+fn take_key(s: &str) -> Option<(String, &str)> {
+    if s.starts_with(['"', '\'']) {
+        return take_string(s);
+    }
+    let end = s.find(|c| !is_bare(c)).unwrap_or(s.len());
+    (end > 0).then(|| (s[..end].to_string(), &s[end..]))
+}
+
+// `name = ["arg", 'arg']` on one line, with an optional comment after it
+fn query(line: &str) -> Option<(String, Vec<String>)> {
+    let (name, rest) = take_key(line.trim())?;
+    let mut rest = rest.trim_start().strip_prefix('=')?.trim_start().strip_prefix('[')?;
+    let mut args = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix(']') {
+            rest = after;
+            break;
+        }
+        let (arg, after) = take_string(rest)?;
+        args.push(arg);
+        rest = after.trim_start();
+        if let Some(after) = rest.strip_prefix(',') {
+            rest = after;
+        } else if !rest.starts_with(']') {
+            return None;
+        }
+    }
+    let rest = rest.trim();
+    (rest.is_empty() || rest.starts_with('#')).then_some((name, args))
+}
+
+// a [paths."..."] or [queries] header, none if line isn't a header at all
+fn section(line: &str) -> Option<Result<Section, &'static str>> {
+    let header = line.trim().strip_prefix('[')?;
+    let header = match header.rsplit_once(']') {
+        Some((header, rest)) if rest.trim().is_empty() || rest.trim().starts_with('#') => {
+            header.trim()
+        },
+        _ => {
+            return Some(Err("unclosed section header"));
+        },
+    };
+    if header == "queries" {
+        return Some(Ok(Section::Queries));
+    }
+    let path = header.strip_prefix("paths.")
+        .and_then(take_string)
+        .filter(|(_, rest)| rest.is_empty())
+        .map(|(path, _)| Section::Paths(PathBuf::from(path)));
+    Some(path.ok_or("expected a section like [paths.\"/mnt/nas\"] or [queries]"))
+}
+
+enum Current {
+    Path(usize),
+    Queries,
+}
+
+// the part of toml this needs: [paths."..."] sections holding integers and booleans, and a
+// [queries] section of string arrays, with comments. a later section for the same path adds to the
+// earlier one
+pub fn parse(text: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    let mut current: Option<Current> = None;
+    for (number, line) in text.lines().enumerate() {
+        let error = |what: &str| format!("line {}: {}", number + 1, what);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = section(line) {
+            current = Some(match section.map_err(error)? {
+                Section::Queries => Current::Queries,
+                Section::Paths(path) => {
+                    Current::Path(match config.paths.iter().position(|c| c.path == path) {
+                        Some(i) => i,
+                        None => {
+                            config.paths.push(PathConfig { path, threads: None, follow: None });
+                            config.paths.len() - 1
+                        },
+                    })
+                },
+            });
+            continue;
+        }
+        let path_config = match current {
+            Some(Current::Path(i)) => &mut config.paths[i],
+            Some(Current::Queries) => {
+                // a # inside a quoted argument isn't a comment
+                let (name, args) = query(line).ok_or_else(|| error("expected a query like name = [\"-p\", \"x\"]"))?;
+                config.queries.retain(|(n, _)| *n != name);
+                config.queries.push((name, args));
+                continue;
+            },
+            None => {
+                return Err(error("settings have to be inside a [paths.\"...\"] or [queries] section"));
+            },
+        };
+        let line = line.split('#').next().unwrap_or("").trim();
+        let (key, value) = line.split_once('=').ok_or_else(|| error("expected key = value"))?;
+        match key.trim() {
+            "threads" => {
+                let threads = value.trim().parse::<usize>().ok().filter(|&n| n > 0);
+                path_config.threads = Some(threads.ok_or_else(|| error("threads has to be a positive number"))?);
+            },
+            "follow_symlinks" => {
+                let follow = match value.trim() {
+                    "true" => true,
+                    "false" => false,
+                    _ => {
+                        return Err(error("follow_symlinks has to be true or false"));
+                    },
+                };
+                path_config.follow = Some(follow);
+            },
+            key => {
+                return Err(error(&format!("unknown setting '{}'", key)));
+            },
+        }
+    }
+    Ok(config)
+}
+
+// text with the query name set to args. the old entry is dropped and the new one goes at the end of
+// the last [queries] section, or of a new one, so the rest of the file stays as it was written
+pub fn set_query(text: &str, name: &str, args: &[String]) -> Result<String, String> {
+    if name.is_empty() {
+        return Err(String::from("a query needs a name"));
+    }
+    parse(text)?;
+    let key = if name.chars().all(is_bare) { name.to_string() } else { quote(name) };
+    let args: Vec<String> = args.iter().map(|arg| quote(arg)).collect();
+    let entry = format!("{} = [{}]", key, args.join(", "));
+    let mut lines: Vec<&str> = Vec::new();
+    let mut in_queries = false;
+    // where the entry goes: after the last query or [queries] header
+    let mut end = None;
+    for line in text.lines() {
+        match section(line) {
+            Some(section) => {
+                in_queries = matches!(section, Ok(Section::Queries));
+                lines.push(line);
+                if in_queries {
+                    end = Some(lines.len());
+                }
+            },
+            None if in_queries => {
+                match query(line) {
+                    Some((n, _)) if n == name => {},
+                    Some(_) => {
+                        lines.push(line);
+                        end = Some(lines.len());
+                    },
+                    None => {
+                        lines.push(line);
+                    },
+                }
+            },
+            None => {
+                lines.push(line);
+            },
+        }
+    }
+    match end {
+        Some(i) => {
+            lines.insert(i, &entry);
+        },
+        None => {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push("");
+            }
+            lines.push("[queries]");
+            lines.push(&entry);
+        },
+    }
+    Ok(lines.join("\n") + "\n")
+}
+
+// config.toml in the config directory
+pub fn file() -> Option<PathBuf> {
+    Some(dir()?.join("config.toml"))
+}
+
+// the settings in config.toml, empty if there is no such file
+pub fn read() -> Result<Config, String> {
+    let Some(path) = file() else {
+        return Ok(Config::default());
+    };
+    match fs::read_to_string(&path) {
+        Ok(text) => {
+            parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            Ok(Config::default())
+        },
+        Err(e) => {
+            Err(format!("{}: {}", path.display(), e))
+        },
+    }
+}
+
+// the [paths."..."] settings. a broken file is warned about and otherwise ignored, rather than
+// stopping every search
+pub fn load() -> Vec<PathConfig> {
+    match read() {
+        Ok(config) => {
+            config.paths
+        },
+        Err(e) => {
+            output::warn("could not read config", e);
+            Vec::new()
+        },
+    }
+}
+
+// which of the configs applies to dir: the one for the deepest path it is in. relative dirs are
+// taken from the current directory, without resolving links, so this costs no syscalls per dir
+pub fn for_dir(configs: &[PathConfig], dir: &Path) -> Option<usize> {
+    if configs.is_empty() {
+        return None;
+    }
+    let dir = std::path::absolute(dir).ok()?;
+    configs.iter()
+        .enumerate()
+        .filter(|(_, c)| dir.starts_with(&c.path))
+        .max_by_key(|(_, c)| c.path.components().count())
+        .map(|(i, _)| i)
+}
+
+#[test]
+fn test_parse_config() {
+    let text = r#"
+        # slow network storage
+        [paths."/mnt/nas"]
+        threads = 2
+        follow_symlinks = false   # links there point back up
+
+        [paths.'/srv/#data']
+        follow_symlinks = true
+
+        [paths."/mnt/nas"]
+        threads = 1
+    "#;
+    let configs = parse(text).unwrap().paths;
+    assert_eq!(configs, vec![
+        PathConfig { path: PathBuf::from("/mnt/nas"), threads: Some(1), follow: Some(false) },
+        PathConfig { path: PathBuf::from("/srv/#data"), threads: None, follow: Some(true) },
+    ]);
+    assert!(matches!(section(r#"[paths."/a \"b\""]"#), Some(Ok(Section::Paths(path))) if path == Path::new("/a \"b\"")));
+
+    assert!(parse("threads = 2").is_err());
+    assert!(parse("[other]\nthreads = 2").is_err());
+    assert!(parse("[paths.\"/a\"]\nthreads = 0").is_err());
+    assert!(parse("[paths.\"/a\"]\nfollow_symlinks = yes").is_err());
+    assert!(parse("[paths.\"/a\"]\ndepth = 2").is_err());
+    assert!(parse("[paths.\"/a\"").is_err());
+}
+
+#[test]
+fn test_config_for_dir() {
+    let config = |path: &str| PathConfig { path: PathBuf::from(path), threads: None, follow: None };
+    let configs = vec![config("/mnt"), config("/mnt/nas"), config("/srv")];
+    assert_eq!(for_dir(&configs, Path::new("/mnt/nas/photos/2024")), Some(1));
+    assert_eq!(for_dir(&configs, Path::new("/mnt/usb")), Some(0));
+    assert_eq!(for_dir(&configs, Path::new("/mnt/nas-old")), Some(0));
+    assert_eq!(for_dir(&configs, Path::new("/home")), None);
+    assert_eq!(for_dir(&[], Path::new("/mnt")), None);
+}
+
+#[test]
+fn test_queries() {
+    let text = r#"
+        [queries]
+        big = ["--sort", "size", "--reverse"]   # largest first
+        "old logs" = ['-p', '\.log$', "--changed-before", "30d",]
+        junk = ["-p", "^\\.?#.*#?$", "tab\there", "\u00e9"]
+
+        [queries]
+        big = []
+    "#;
+    let queries = parse(text).unwrap().queries;
+    let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+    assert_eq!(queries, vec![
+        (String::from("old logs"), args(&["-p", r"\.log$", "--changed-before", "30d"])),
+        (String::from("junk"), args(&["-p", r"^\.?#.*#?$", "tab\there", "é"])),
+        (String::from("big"), args(&[])),
+    ]);
+
+    assert!(parse("[queries]\nbig = \"--sort\"").is_err());
+    assert!(parse("[queries]\nbig = [\"a\" \"b\"]").is_err());
+    assert!(parse("[queries]\nbig = [\"a\"] x").is_err());
+    assert!(parse("[queries]\nbig = [\"\\u12\"]").is_err());
+    assert!(parse("[queries]\n= [\"a\"]").is_err());
+}
+
+#[test]
+fn test_set_query() {
+    let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+    // a file without queries gets a section at the end, and everything else is kept as written
+    let text = "# nas\n[paths.\"/mnt/nas\"]\nthreads = 2\n";
+    let text = set_query(text, "big", &args(&["--sort", "size"])).unwrap();
+    assert_eq!(text, "# nas\n[paths.\"/mnt/nas\"]\nthreads = 2\n\n[queries]\nbig = [\"--sort\", \"size\"]\n");
+    let text = set_query(&text, "it's \"odd\"", &args(&["-p", "a\"b\\c\n\u{1}"])).unwrap();
+    let text = set_query(&text, "big", &args(&["--limit", "5"])).unwrap();
+    assert_eq!(text.lines().filter(|line| line.starts_with("big")).count(), 1);
+    let config = parse(&text).unwrap();
+    assert_eq!(config.paths.len(), 1);
+    assert_eq!(config.queries, vec![
+        (String::from("it's \"odd\""), args(&["-p", "a\"b\\c\n\u{1}"])),
+        (String::from("big"), args(&["--limit", "5"])),
+    ]);
+
+    // new queries go after the last one, before whatever follows the section
+    let text = "[queries]\na = []\n\n# later\n[paths.\"/srv\"]\nthreads = 1\n";
+    assert_eq!(set_query(text, "b", &[]).unwrap(), "[queries]\na = []\nb = []\n\n# later\n[paths.\"/srv\"]\nthreads = 1\n");
+    assert_eq!(set_query("", "a", &[]).unwrap(), "[queries]\na = []\n");
+    assert!(set_query("", "", &[]).is_err());
+    // a file that doesn't parse isn't rewritten
+    assert!(set_query("threads = 2\n", "a", &[]).is_err());
+}
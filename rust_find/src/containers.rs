@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::{Component, Path};
+
+// where container runtimes keep image layers and container filesystems. everything in there is a
+// copy of something that is also reachable (and counted) through the mounted containers, or is
+// only meaningful to the runtime
+const STORAGE_DIRS: [&[&str]; 5] = [
+    &["var", "lib", "docker"],
+    &["var", "lib", "containers", "storage"],
+    &["var", "lib", "containerd"],
+    &["var", "lib", "lxd", "storage-pools"],
+    // rootless podman
+    &[".local", "share", "containers", "storage"],
+];
+
+// virtual machine disks, which are whole filesystems in one file
+const DISK_IMAGE_EXTENSIONS: [&str; 6] = ["qcow2", "qcow", "vmdk", "vdi", "vhd", "vhdx"];
+
+fn ends_with_storage_dir(path: &Path) -> bool {
+    let names: Vec<_> = path.components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect();
+    STORAGE_DIRS.iter().any(|dir| {
+        names.len() >= dir.len() && names[names.len() - dir.len()..].iter().zip(dir.iter()).all(|(a, b)| a == b)
+    })
+}
+
+// whether dir is one of the STORAGE_DIRS. the walked path can be relative, so it is resolved,
+// but only for the few directories whose name could match
+pub fn is_storage_dir(dir: &Path) -> bool {
+    let last = dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    if !STORAGE_DIRS.iter().any(|d| d.last() == Some(&last.as_str())) {
+        return false;
+    }
+    fs::canonicalize(dir).is_ok_and(|dir| ends_with_storage_dir(&dir))
+}
+
+pub fn is_disk_image(name: &str) -> bool {
+    match name.rsplit_once('.') {
+        Some((_, extension)) => {
+            DISK_IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+        },
+        None => {
+            false
+        },
+    }
+}
+
+#[test]
+fn test_container_storage() {
+    assert!(ends_with_storage_dir(Path::new("/var/lib/docker")));
+    assert!(ends_with_storage_dir(Path::new("/mnt/backup/var/lib/containers/storage")));
+    assert!(ends_with_storage_dir(Path::new("/home/ana/.local/share/containers/storage")));
+    assert!(!ends_with_storage_dir(Path::new("/var/lib/docker/overlay2")));
+    assert!(!ends_with_storage_dir(Path::new("/home/ana/docker")));
+
+    assert!(is_disk_image("ubuntu-22.04.QCOW2"));
+    assert!(is_disk_image("win11.vhdx"));
+    assert!(!is_disk_image("notes.txt"));
+    assert!(!is_disk_image("qcow2"));
+}
+
+#[test]
+fn test_container_storage_edge_cases() {
+    // trailing slashes and relative paths name the same directory
+    assert!(ends_with_storage_dir(Path::new("/var/lib/containerd/")));
+    assert!(ends_with_storage_dir(Path::new("var/lib/lxd/storage-pools")));
+    assert!(!ends_with_storage_dir(Path::new("/")));
+    assert!(!ends_with_storage_dir(Path::new("lib/docker")));
+    assert!(!ends_with_storage_dir(Path::new("/var/lib/Docker")));
+
+    // only the last extension counts, in any case
+    assert!(is_disk_image("disk.raw.Vmdk"));
+    assert!(is_disk_image(".vdi"));
+    assert!(!is_disk_image("disk.vmdk.bak"));
+    assert!(!is_disk_image("disk."));
+    assert!(!is_disk_image(""));
+}
+
+#[test]
+fn test_is_storage_dir() {
+    let dir = crate::temp::TestDir::new("containers");
+    let storage = dir.join("var/lib/docker");
+    fs::create_dir_all(&storage).unwrap();
+    fs::create_dir_all(dir.join("docker")).unwrap();
+    assert!(is_storage_dir(&storage));
+    // the path is resolved, so the way the walk got there doesn't matter
+    assert!(is_storage_dir(&storage.join("../docker")));
+    assert!(!is_storage_dir(&dir.join("docker")));
+    assert!(!is_storage_dir(&dir.join("var/lib")));
+    // a directory that can't be resolved isn't skipped
+    assert!(!is_storage_dir(&dir.join("missing/var/lib/docker")));
+}
@@ -0,0 +1,75 @@
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+
+use crate::{i18n, status};
+use crate::time;
+
+fn report(info: &PanicHookInfo, backtrace: &Backtrace) -> String {
+    let args: Vec<String> = std::env::args_os().map(|a| a.to_string_lossy().into_owned()).collect();
+    let (dirs, files) = status::counts();
+    format!("rust-find {} crash report\n\n\
+             panic: {}\n\
+             args: {:?}\n\
+             os: {} {} ({})\n\
+             scanned so far: {} dirs, {} files\n\n\
+             backtrace:\n{}\n",
+            env!("CARGO_PKG_VERSION"),
+            info,
+            args,
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+            std::env::consts::FAMILY,
+            dirs,
+            files,
+            backtrace)
+}
+
+// keeps the normal panic message, and also writes everything needed for a bug report to a file
+// in the temp dir and says where it is
+pub fn install_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        let backtrace = Backtrace::force_capture();
+        let path: PathBuf = std::env::temp_dir()
+            .join(format!("rust-find-crash-{}-{}.txt", time::now(), std::process::id()));
+        match fs::write(&path, report(info, &backtrace)) {
+            Ok(()) => {
+                eprintln!("{}",
+                          i18n::trf("rust-find crashed. please attach {} when reporting this bug", &[&path.display()]));
+            },
+            Err(e) => {
+                eprintln!("{}",
+                          i18n::trf("rust-find crashed, and writing a crash report to {} failed: {}",
+                                    &[&path.display(), &e]));
+            },
+        }
+    }));
+}
+
+#[test]
+fn test_report() {
+    use std::sync::{Arc, Mutex};
+
+    // a PanicHookInfo only exists inside a hook, so one panic is caught with a hook that keeps
+    // the report, then the old hook is put back
+    let written = Arc::new(Mutex::new(String::new()));
+    let into = Arc::clone(&written);
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        *into.lock().unwrap() = report(info, &Backtrace::disabled());
+    }));
+    let result = panic::catch_unwind(|| panic!("boom at {}", 42));
+    panic::set_hook(previous);
+    assert!(result.is_err());
+
+    let written = written.lock().unwrap();
+    let expected_start = format!("rust-find {} crash report\n\npanic: ", env!("CARGO_PKG_VERSION"));
+    assert!(written.starts_with(&expected_start), "{}", written);
+    // where it panicked and why, on one line
+    assert!(written.contains("src/crash.rs") && written.contains("boom at 42"), "{}", written);
+    assert!(written.contains(&format!("os: {} {}", std::env::consts::OS, std::env::consts::ARCH)), "{}", written);
+    assert!(written.contains(" dirs, ") && written.contains("\n\nbacktrace:\n"), "{}", written);
+}
@@ -0,0 +1,420 @@
+use std::ffi::OsString;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::output;
+use crate::script::command_line;
+use crate::MyFile;
+
+// one run of the command: the line sh runs, the paths passed to it as $1, $2, ..., and the files
+// it is run for, which fail together if it does
+#[cfg_attr(not(unix), allow(dead_code))]
+struct Job<'a> {
+    line: Vec<u8>,
+    args: Vec<&'a Path>,
+    files: Vec<&'a Path>,
+    // put in front of every line the command prints when several run at once
+    label: String,
+    // set in the command's environment on top of what rust-find was given
+    env: Vec<(&'static str, OsString)>,
+}
+
+// what --exec tells each command about its match: RF_PATH, RF_SIZE in bytes on disk and RF_MTIME
+// in seconds since the unix epoch, so scripts needn't stat the file again
+fn match_env(file: &MyFile) -> Vec<(&'static str, OsString)> {
+    vec![
+        ("RF_PATH", file.path.clone().into_os_string()),
+        ("RF_SIZE", OsString::from(file.size_bytes.to_string())),
+        ("RF_MTIME", OsString::from(file.modified.to_string())),
+    ]
+}
+
+#[cfg(unix)]
+fn command(job: &Job) -> Command {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(OsStr::from_bytes(&job.line)).arg("rust-find").args(&job.args);
+    command.envs(job.env.iter().map(|(key, value)| (key, value)));
+    command
+}
+
+#[cfg(not(unix))]
+fn command(_job: &Job) -> Command {
+    Command::new("--exec needs a unix shell")
+}
+
+// runs a job with the terminal passed through, so interactive commands like an editor work
+fn run_attached(job: &Job) -> io::Result<ExitStatus> {
+    command(job).status()
+}
+
+// copies what a job prints to out a line at a time, each line behind the job's label. a line is
+// written with one call on the locked stream, so lines of jobs running at once never mix
+fn forward(from: impl Read, label: &str, mut out: impl FnMut(&[u8])) {
+    for line in BufReader::new(from).split(b'\n') {
+        let Ok(line) = line else {
+            break;
+        };
+        let mut prefixed = format!("[{}] ", label).into_bytes();
+        prefixed.extend_from_slice(&line);
+        prefixed.push(b'\n');
+        out(&prefixed);
+    }
+}
+
+// runs a job with its output prefixed, for when other jobs run at the same time. it gets no
+// stdin, which the jobs couldn't share
+fn run_prefixed(job: &Job) -> io::Result<ExitStatus> {
+    let mut child = command(job).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    std::thread::scope(|scope| {
+        scope.spawn(|| forward(stderr, &job.label, |line| {
+            let _ = io::stderr().lock().write_all(line);
+        }));
+        forward(stdout, &job.label, |line| {
+            let _ = io::stdout().lock().write_all(line);
+        });
+    });
+    child.wait()
+}
+
+// a path whose command failed, and how
+#[derive(Debug, PartialEq, Eq)]
+pub struct Failure {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+// None if the command ran and succeeded, otherwise why not
+fn failed(result: io::Result<ExitStatus>) -> Option<String> {
+    match result {
+        Ok(status) if status.success() => {
+            None
+        },
+        Ok(status) => {
+            Some(status.to_string())
+        },
+        Err(e) => {
+            Some(e.to_string())
+        },
+    }
+}
+
+// what to do when a command fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnError {
+    // carry on with the rest
+    Continue,
+    // start no more commands, the ones already running are left to finish
+    Abort,
+    // run it again up to N more times before counting it as failed, then carry on
+    Retry(u32),
+}
+
+impl FromStr for OnError {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid --exec-on-error '{}', expected continue, abort or retry=N", s);
+        match s.trim() {
+            "continue" => Ok(OnError::Continue),
+            "abort" => Ok(OnError::Abort),
+            s => {
+                let times = s.strip_prefix("retry").ok_or_else(invalid)?.trim_start_matches([' ', '=']);
+                Ok(OnError::Retry(times.parse().map_err(|_| invalid())?))
+            },
+        }
+    }
+}
+
+// runs a job once, and again for as many retries as on_error allows while it keeps failing
+fn run_job(job: &Job, parallel: usize, on_error: OnError) -> Option<String> {
+    let tries = match on_error {
+        OnError::Retry(n) => n.saturating_add(1),
+        OnError::Continue | OnError::Abort => 1,
+    };
+    let mut reason = None;
+    for attempt in 1..=tries {
+        reason = failed(if parallel > 1 { run_prefixed(job) } else { run_attached(job) });
+        match &reason {
+            None => {
+                return None;
+            },
+            Some(r) if attempt < tries => {
+                output::warn("command failed, retrying", format!("{}: {}", job.label, r));
+            },
+            Some(_) => {},
+        }
+    }
+    reason.map(|r| if tries > 1 { format!("{} (after {} tries)", r, tries) } else { r })
+}
+
+// runs the jobs, up to parallel of them at once and in order otherwise. a failing job is warned
+// about and, unless on_error says to abort, the rest still run. the failures come back in the
+// order of the jobs, with the files of jobs an abort kept from starting among them
+fn run_jobs(jobs: &[Job], parallel: usize, on_error: OnError) -> Vec<Failure> {
+    let next = AtomicUsize::new(0);
+    let abort = AtomicBool::new(false);
+    let failures = Mutex::new(Vec::new());
+    let worker = || {
+        loop {
+            let index = next.fetch_add(1, Ordering::SeqCst);
+            let Some(job) = jobs.get(index) else {
+                break;
+            };
+            let reason = if abort.load(Ordering::SeqCst) {
+                String::from("not run, an earlier command failed")
+            } else if let Some(reason) = run_job(job, parallel, on_error) {
+                output::warn("command failed", format!("{}: {}", job.label, reason));
+                if on_error == OnError::Abort {
+                    abort.store(true, Ordering::SeqCst);
+                }
+                reason
+            } else {
+                continue;
+            };
+            failures.lock().unwrap().push((index, job.files.iter().map(|&path| Failure { path: path.to_path_buf(), reason: reason.clone() })
+                                                                  .collect::<Vec<Failure>>()));
+        }
+    };
+    std::thread::scope(|scope| {
+        for _ in 1..parallel.min(jobs.len()) {
+            scope.spawn(worker);
+        }
+        worker();
+    });
+    let mut failures = failures.into_inner().unwrap();
+    failures.sort_by_key(|(index, _)| *index);
+    failures.into_iter().flat_map(|(_, f)| f).collect()
+}
+
+// runs the --exec template for each file, with {} replaced by the quoted path, up to parallel at
+// once
+pub fn run(template: &str, files: &[&MyFile], parallel: usize, on_error: OnError) -> Vec<Failure> {
+    let jobs: Vec<Job> = files.iter()
+        .map(|file| Job {
+            line: command_line(template, &file.path),
+            args: Vec::new(),
+            files: vec![&file.path],
+            label: file.path.display().to_string(),
+            env: match_env(file),
+        })
+        .collect();
+    run_jobs(&jobs, parallel, on_error)
+}
+
+// the line sh runs for a batch. {} becomes "$@", and the paths are passed to sh as arguments of
+// their own, so they need no quoting and the line stays short however many there are
+pub fn batch_line(template: &str) -> String {
+    if template.contains("{}") {
+        template.replace("{}", "\"$@\"")
+    } else {
+        format!("{} \"$@\"", template)
+    }
+}
+
+// bytes an argument takes up in the space ARG_MAX limits: itself, its nul and its pointer
+fn arg_bytes(arg: &[u8]) -> usize {
+    arg.len() + 1 + std::mem::size_of::<usize>()
+}
+
+#[cfg(unix)]
+fn os_bytes(path: &Path) -> &[u8] {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes()
+}
+
+#[cfg(not(unix))]
+fn os_bytes(path: &Path) -> &[u8] {
+    path.as_os_str().as_encoded_bytes()
+}
+
+// room for the arguments of one command: ARG_MAX, less the environment which shares that space,
+// less what xargs also keeps back for the command to pass on
+#[cfg(unix)]
+fn arg_space() -> usize {
+    const HEADROOM: usize = 2048;
+    let arg_max = match unsafe { libc::sysconf(libc::_SC_ARG_MAX) } {
+        n if n > 0 => {
+            n as usize
+        },
+        _ => {
+            // the smallest ARG_MAX posix allows
+            4096
+        },
+    };
+    let environment: usize = std::env::vars_os()
+        .map(|(key, value)| arg_bytes(os_bytes(Path::new(&key))) + os_bytes(Path::new(&value)).len() + 1)
+        .sum();
+    arg_max.saturating_sub(environment + HEADROOM)
+}
+
+#[cfg(not(unix))]
+fn arg_space() -> usize {
+    32 * 1024
+}
+
+// splits the files, in order, into batches of at most max_files whose arguments fit in space
+// bytes. a path too long to share is sent on its own, and fails if even that is too much
+fn batches<'a>(files: &[&'a MyFile], max_files: usize, space: usize) -> Vec<Vec<&'a MyFile>> {
+    let mut batches: Vec<Vec<&MyFile>> = Vec::new();
+    let mut batch = Vec::new();
+    let mut used = 0;
+    for &file in files {
+        let bytes = arg_bytes(os_bytes(&file.path));
+        if !batch.is_empty() && (batch.len() == max_files || used + bytes > space) {
+            batches.push(std::mem::take(&mut batch));
+            used = 0;
+        }
+        batch.push(file);
+        used += bytes;
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+// the batches --exec-batch runs the template for, at most batch_size files each
+pub fn split<'a>(template: &str, files: &[&'a MyFile], batch_size: Option<usize>) -> Vec<Vec<&'a MyFile>> {
+    // sh, -c, the line and $0 go before the paths
+    let line = batch_line(template);
+    let space = arg_space().saturating_sub(["sh", "-c", &line, "rust-find"].iter().map(|a| arg_bytes(a.as_bytes())).sum());
+    batches(files, batch_size.unwrap_or(usize::MAX).max(1), space)
+}
+
+// runs the --exec-batch template once per batch, like xargs, up to parallel at once. when a batch
+// fails, all of its paths count as failed, since there is no telling which of them the command
+// choked on
+pub fn run_batches(template: &str,
+                   files: &[&MyFile],
+                   batch_size: Option<usize>,
+                   parallel: usize,
+                   on_error: OnError)
+                   -> Vec<Failure> {
+    let line = batch_line(template);
+    let jobs: Vec<Job> = split(template, files, batch_size).into_iter()
+        .enumerate()
+        .map(|(i, batch)| {
+            let paths: Vec<&Path> = batch.iter().map(|f| f.path.as_path()).collect();
+            Job {
+                line: line.clone().into_bytes(),
+                label: format!("batch {} of {}", i + 1, output::count(paths.len() as u64, "file", "files")),
+                args: paths.clone(),
+                files: paths,
+                // the matches of a batch share one command, so there is no one match to describe
+                env: Vec::new(),
+            }
+        })
+        .collect();
+    run_jobs(&jobs, parallel, on_error)
+}
+
+#[test]
+fn test_run() {
+    let file = |path: &str| MyFile { path: PathBuf::from(path), ..Default::default() };
+    let (good, bad) = (file("/"), file("/nonexistent/rust-find"));
+    let failures = run("test -e", &[&good, &bad, &good], 1, OnError::Continue);
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].path, bad.path);
+    assert!(failures[0].reason.contains("exit status: 1"), "{}", failures[0].reason);
+    assert!(run("test -e {} && test -d {}", &[&good], 1, OnError::Continue).is_empty());
+}
+
+#[test]
+fn test_batches() {
+    let files: Vec<MyFile> = (0..10).map(|i| MyFile { path: PathBuf::from(format!("f{}", i)), ..Default::default() }).collect();
+    let files: Vec<&MyFile> = files.iter().collect();
+    let sizes = |batches: Vec<Vec<&MyFile>>| batches.iter().map(|b| b.len()).collect::<Vec<usize>>();
+    let per_file = arg_bytes(b"f0");
+
+    assert_eq!(sizes(batches(&files, usize::MAX, usize::MAX)), vec![10]);
+    assert_eq!(sizes(batches(&files, 4, usize::MAX)), vec![4, 4, 2]);
+    assert_eq!(sizes(batches(&files, usize::MAX, 3 * per_file)), vec![3, 3, 3, 1]);
+    assert_eq!(sizes(batches(&files, 2, 3 * per_file)), vec![2, 2, 2, 2, 2]);
+    // a path longer than the space still goes, on its own
+    assert_eq!(sizes(batches(&files, usize::MAX, 1)), vec![1; 10]);
+    assert!(batches(&[], 4, 100).is_empty());
+    // the order is kept
+    assert_eq!(batches(&files, 4, usize::MAX)[1][0].path, PathBuf::from("f4"));
+
+    assert_eq!(batch_line("tar czf backup.tgz {}"), "tar czf backup.tgz \"$@\"");
+    assert_eq!(batch_line("rm -f"), "rm -f \"$@\"");
+}
+
+#[test]
+fn test_run_batches() {
+    let file = |path: &str| MyFile { path: PathBuf::from(path), ..Default::default() };
+    let (root, tmp, bad) = (file("/"), file("/tmp"), file("/nonexistent/rust-find"));
+    // test -d takes a single path, so it only succeeds for batches of one directory
+    assert!(run_batches("test -d", &[&root, &tmp], Some(1), 1, OnError::Continue).is_empty());
+    assert_eq!(run_batches("test -d", &[&root, &tmp], None, 1, OnError::Continue).len(), 2);
+    let failures = run_batches("test -d", &[&root, &bad], Some(1), 1, OnError::Continue);
+    assert_eq!(failures, vec![Failure { path: bad.path.clone(), reason: String::from("exit status: 1") }]);
+    assert!(run_batches("ls -d {} >/dev/null", &[&root, &tmp], None, 1, OnError::Continue).is_empty());
+}
+
+#[test]
+fn test_run_in_parallel() {
+    let files: Vec<MyFile> = (0..8).map(|i| MyFile { path: PathBuf::from(format!("/nonexistent/{}", i)), ..Default::default() })
+        .collect();
+    let files: Vec<&MyFile> = files.iter().collect();
+    // every other one fails, and the failures still come back in order
+    let failures = run("case {} in *[1357]) exit 3;; esac", &files, 4, OnError::Continue);
+    let failed: Vec<&Path> = failures.iter().map(|f| f.path.as_path()).collect();
+    assert_eq!(failed, ["/nonexistent/1", "/nonexistent/3", "/nonexistent/5", "/nonexistent/7"].map(Path::new));
+    assert_eq!(failures[0].reason, "exit status: 3");
+    assert_eq!(run_batches("test $# -eq 2 && : {}", &files, Some(2), 3, OnError::Continue), Vec::new());
+
+    let mut lines = Vec::new();
+    forward(&b"one\ntwo"[..], "a.txt", |line| lines.push(line.to_vec()));
+    assert_eq!(lines, vec![b"[a.txt] one\n".to_vec(), b"[a.txt] two\n".to_vec()]);
+}
+
+#[test]
+fn test_on_error() {
+    assert_eq!("continue".parse(), Ok(OnError::Continue));
+    assert_eq!("abort".parse(), Ok(OnError::Abort));
+    assert_eq!("retry=3".parse(), Ok(OnError::Retry(3)));
+    assert_eq!("retry 2".parse(), Ok(OnError::Retry(2)));
+    assert!("retry".parse::<OnError>().is_err());
+    assert!("retry=-1".parse::<OnError>().is_err());
+    assert!("stop".parse::<OnError>().is_err());
+
+    let file = |path: &str| MyFile { path: PathBuf::from(path), ..Default::default() };
+    let (good, bad) = (file("/"), file("/nonexistent/rust-find"));
+    // an abort leaves what comes after the failure unrun, and reports it as such
+    let failures = run("test -e", &[&good, &bad, &good, &good], 1, OnError::Abort);
+    assert_eq!(failures.len(), 3);
+    assert_eq!(failures[0], Failure { path: bad.path.clone(), reason: String::from("exit status: 1") });
+    assert!(failures[1..].iter().all(|f| f.path == good.path && f.reason.starts_with("not run")));
+    assert!(run("test -e", &[&good, &good], 1, OnError::Abort).is_empty());
+
+    // a command that fails the first time only succeeds on the retry
+    let dir = crate::temp::TestDir::new("retry");
+    let marker = dir.join("marker");
+    let flaky = format!("test -e {0} || {{ touch {0}; exit 1; }}; : {{}}", marker.display());
+    assert!(run(&flaky, &[&good], 1, OnError::Retry(1)).is_empty());
+    std::fs::remove_file(&marker).unwrap();
+    assert_eq!(run(&flaky, &[&good], 1, OnError::Continue).len(), 1);
+    let failures = run("test -e", &[&bad], 1, OnError::Retry(2));
+    assert_eq!(failures[0].reason, "exit status: 1 (after 3 tries)");
+}
+
+#[test]
+fn test_match_env() {
+    let file = MyFile { path: PathBuf::from("/"), size_bytes: 4096, modified: 1700000000, ..Default::default() };
+    assert!(run("test \"$RF_PATH\" = / && test \"$RF_SIZE\" = 4096 && test \"$RF_MTIME\" = 1700000000 && : {}",
+                &[&file],
+                1,
+                OnError::Continue).is_empty());
+    // batches have no single match to describe
+    assert!(run_batches("test -z \"$RF_PATH\" && : {}", &[&file], None, 1, OnError::Continue).is_empty());
+}
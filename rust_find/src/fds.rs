@@ -0,0 +1,95 @@
+use std::sync::{Condvar, Mutex};
+
+// left for stdio, the output file, sockets and whatever the consumer of the walk opens
+const RESERVED: usize = 32;
+// what reading one directory can hold open at once: the directory, an ignore file or an archive,
+// and gzip's pipe when the archive is compressed
+const PER_DIR: usize = 4;
+
+// how many directories may be read at the same time, so that the walk stays under the open
+// file limit however many threads there are. workers past the budget wait for a turn
+pub struct Budget {
+    free: Mutex<usize>,
+    returned: Condvar,
+}
+
+pub struct Permit<'a> {
+    budget: &'a Budget,
+}
+
+impl Budget {
+    pub fn new(permits: usize) -> Self {
+        Budget { free: Mutex::new(permits.max(1)), returned: Condvar::new() }
+    }
+
+    // blocks until a directory can be read
+    pub fn acquire(&self) -> Permit<'_> {
+        let mut free = self.free.lock().unwrap();
+        while *free == 0 {
+            free = self.returned.wait(free).unwrap();
+        }
+        *free -= 1;
+        Permit { budget: self }
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        *self.budget.free.lock().unwrap() += 1;
+        self.budget.returned.notify_one();
+    }
+}
+
+// the soft limit on open files (ulimit -n), if there is one
+#[cfg(unix)]
+fn open_file_limit() -> Option<usize> {
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 || limit.rlim_cur == libc::RLIM_INFINITY {
+        return None;
+    }
+    usize::try_from(limit.rlim_cur).ok()
+}
+
+#[cfg(not(unix))]
+fn open_file_limit() -> Option<usize> {
+    None
+}
+
+fn permits(open_files: usize) -> usize {
+    (open_files.saturating_sub(RESERVED) / PER_DIR).max(1)
+}
+
+// max_open_files stands in for the process limit when given
+pub fn budget(max_open_files: Option<usize>) -> Budget {
+    match max_open_files.or_else(open_file_limit) {
+        Some(open_files) => {
+            Budget::new(permits(open_files))
+        },
+        None => {
+            Budget::new(usize::MAX)
+        },
+    }
+}
+
+#[test]
+fn test_budget() {
+    assert_eq!(permits(1024), 248);
+    assert_eq!(permits(64), 8);
+    assert_eq!(permits(10), 1);
+
+    let budget = Budget::new(2);
+    let first = budget.acquire();
+    let second = budget.acquire();
+    assert_eq!(*budget.free.lock().unwrap(), 0);
+    drop(first);
+    assert_eq!(*budget.free.lock().unwrap(), 1);
+    std::thread::scope(|scope| {
+        let waiting = scope.spawn(|| {
+            let _third = budget.acquire();
+            let _fourth = budget.acquire();
+        });
+        drop(second);
+        waiting.join().unwrap();
+    });
+    assert_eq!(*budget.free.lock().unwrap(), 2);
+}
@@ -0,0 +1,237 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::attrs;
+use crate::MyFile;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    // newline-delimited json, one object per match
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("invalid format '{}', expected text or json", s)),
+        }
+    }
+}
+
+// json fields only ever get added in a new schema version, so consumers that pin
+// --schema-version keep getting exactly the fields they expect:
+//   1: path, name, size_bytes, modified
+//   2: adds depth, parent, extension, is_symlink
+//   3: adds link_target
+//   4: adds is_dir, entries
+pub const SCHEMA_VERSIONS: &[&str] = &["1", "2", "3", "4"];
+pub const LATEST_SCHEMA_VERSION: &str = "4";
+
+// the --porcelain layout is fixed for good: TYPE, SIZE, MODIFIED and PATH separated by tabs. the
+// path comes last so tabs in it can't shift the other fields. SIZE is size_bytes, the space the
+// file takes up on disk, as everywhere else
+pub fn porcelain_line(file: &MyFile) -> String {
+    let kind = if file.is_symlink { "l" } else if file.is_dir { "d" } else { "f" };
+    format!("{}\t{}\t{}\t{}", kind, file.size_bytes, file.modified, file.path.display())
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_optional(s: Option<&str>) -> String {
+    match s {
+        Some(s) => {
+            json_string(s)
+        },
+        None => {
+            String::from("null")
+        },
+    }
+}
+
+// how each match is rendered, in text or json
+#[derive(Debug, Clone, Copy)]
+pub struct LineFormat {
+    pub format: Format,
+    pub schema_version: u32,
+    pub show_attrs: bool,
+    // prefix text lines with [1], [2], ... so they can be picked with `rust-find last`
+    pub number: bool,
+    // follow text lines of symlinks with a tab and "-> TARGET"
+    pub show_link_targets: bool,
+    // --porcelain lines, which take precedence over everything else here
+    pub porcelain: bool,
+}
+
+impl LineFormat {
+    // index is 0-based
+    pub fn line(&self, index: usize, file: &MyFile) -> String {
+        if self.porcelain {
+            return porcelain_line(file);
+        }
+        match self.format {
+            Format::Text if self.number => {
+                format!("[{}] {}{}{}", index + 1, file.path.display(), self.attrs_suffix(file), self.link_suffix(file))
+            },
+            Format::Text => {
+                format!("{}{}{}", file.path.display(), self.attrs_suffix(file), self.link_suffix(file))
+            },
+            Format::Json => {
+                self.json_record(file)
+            },
+        }
+    }
+
+    fn attr_names(&self, file: &MyFile) -> Option<Vec<&'static str>> {
+        if !self.show_attrs {
+            return None;
+        }
+        attrs::read(&file.path).ok().map(attrs::names)
+    }
+
+    // text shown after a path, e.g. " [immutable,nodump]"
+    fn attrs_suffix(&self, file: &MyFile) -> String {
+        if !self.show_attrs {
+            return String::new();
+        }
+        match self.attr_names(file) {
+            Some(names) => {
+                format!(" [{}]", names.join(","))
+            },
+            None => {
+                String::from(" [?]")
+            },
+        }
+    }
+
+    // after a tab, which `rust-find merge` drops along with anything else following the path
+    fn link_suffix(&self, file: &MyFile) -> String {
+        match &file.link_target {
+            Some(target) if self.show_link_targets => {
+                format!("\t-> {}", target.display())
+            },
+            _ => {
+                String::new()
+            },
+        }
+    }
+
+    pub fn json_record(&self, file: &MyFile) -> String {
+        let mut json = format!("{{\"path\":{},\"name\":{},\"size_bytes\":{},\"modified\":{}",
+                               json_string(&file.path.to_string_lossy()),
+                               json_string(file.name()),
+                               file.size_bytes,
+                               file.modified);
+        if self.schema_version >= 2 {
+            let parent = file.path.parent().map(|p| p.to_string_lossy());
+            let extension = file.path.extension().map(|e| e.to_string_lossy());
+            json.push_str(&format!(",\"depth\":{},\"parent\":{},\"extension\":{},\"is_symlink\":{}",
+                                   file.depth,
+                                   json_optional(parent.as_deref()),
+                                   json_optional(extension.as_deref()),
+                                   file.is_symlink));
+        }
+        if self.schema_version >= 3 {
+            let target = file.link_target.as_ref().map(|t| t.to_string_lossy());
+            json.push_str(&format!(",\"link_target\":{}", json_optional(target.as_deref())));
+        }
+        if self.schema_version >= 4 {
+            let entries = if file.is_dir { file.entries.to_string() } else { String::from("null") };
+            json.push_str(&format!(",\"is_dir\":{},\"entries\":{}", file.is_dir, entries));
+        }
+        if self.show_attrs {
+            let names: Option<Vec<String>> = self.attr_names(file)
+                .map(|names| names.iter().map(|n| json_string(n)).collect());
+            match names {
+                Some(names) => {
+                    json.push_str(&format!(",\"attrs\":[{}]", names.join(",")));
+                },
+                None => {
+                    json.push_str(",\"attrs\":null");
+                },
+            }
+        }
+        json.push('}');
+        json
+    }
+}
+
+// a path that couldn't be read, for --include-errors. the type field tells it apart from a match,
+// which has no type field
+pub fn error_record(path: &Path, message: &str) -> String {
+    format!("{{\"type\":\"error\",\"path\":{},\"message\":{}}}",
+            json_string(&path.to_string_lossy()),
+            json_string(message))
+}
+
+#[test]
+fn test_error_record() {
+    assert_eq!(error_record(Path::new("/root/secret"), "could not read directory: Permission denied"),
+               r#"{"type":"error","path":"/root/secret","message":"could not read directory: Permission denied"}"#);
+}
+
+#[test]
+fn test_json_record() {
+    let file = MyFile {
+        path: std::path::PathBuf::from("/path/to/\"quoted\".tar.gz"),
+        size_bytes: 4096,
+        modified: 1704067200,
+        depth: 3,
+        is_symlink: false,
+        ..Default::default()
+    };
+    let v1 = LineFormat { format: Format::Json, schema_version: 1, show_attrs: false, number: false, show_link_targets: false,
+                          porcelain: false };
+    assert_eq!(v1.line(0, &file),
+               r#"{"path":"/path/to/\"quoted\".tar.gz","name":"\"quoted\".tar.gz","size_bytes":4096,"modified":1704067200}"#);
+    let v2 = LineFormat { schema_version: 2, ..v1 };
+    assert_eq!(v2.line(0, &file),
+               r#"{"path":"/path/to/\"quoted\".tar.gz","name":"\"quoted\".tar.gz","size_bytes":4096,"modified":1704067200,"depth":3,"parent":"/path/to","extension":"gz","is_symlink":false}"#);
+    let text = LineFormat { format: Format::Text, ..v1 };
+    assert_eq!(text.line(0, &file), "/path/to/\"quoted\".tar.gz");
+    let numbered = LineFormat { number: true, ..text };
+    assert_eq!(numbered.line(1, &file), "[2] /path/to/\"quoted\".tar.gz");
+
+    let link = MyFile {
+        path: std::path::PathBuf::from("/srv/current"),
+        is_symlink: true,
+        link_target: Some(std::path::PathBuf::from("releases/42")),
+        ..Default::default()
+    };
+    let v3 = LineFormat { schema_version: 3, ..v1 };
+    assert_eq!(v3.line(0, &link),
+               r#"{"path":"/srv/current","name":"current","size_bytes":0,"modified":0,"depth":0,"parent":"/srv","extension":null,"is_symlink":true,"link_target":"releases/42"}"#);
+    assert!(v3.line(0, &file).ends_with(r#""is_symlink":false,"link_target":null}"#));
+    let v4 = LineFormat { schema_version: 4, ..v1 };
+    assert!(v4.line(0, &file).ends_with(r#""link_target":null,"is_dir":false,"entries":null}"#));
+    let dir = MyFile { path: std::path::PathBuf::from("/var/spool"), is_dir: true, entries: 12000, ..Default::default() };
+    assert!(v4.line(0, &dir).ends_with(r#""is_dir":true,"entries":12000}"#));
+    let targets = LineFormat { show_link_targets: true, ..text };
+    assert_eq!(targets.line(0, &link), "/srv/current\t-> releases/42");
+    assert_eq!(targets.line(0, &file), "/path/to/\"quoted\".tar.gz");
+
+    let porcelain = LineFormat { porcelain: true, number: true, ..targets };
+    assert_eq!(porcelain.line(0, &file), "f\t4096\t1704067200\t/path/to/\"quoted\".tar.gz");
+    assert_eq!(porcelain.line(0, &link), "l\t0\t0\t/srv/current");
+    assert_eq!(porcelain.line(0, &dir), "d\t0\t0\t/var/spool");
+}
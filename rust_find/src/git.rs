@@ -0,0 +1,64 @@
+use std::path::Path;
+use std::process::Command;
+
+// commit time (seconds since the epoch) of the commit that first added the file, following
+// renames. None if the file isn't tracked, isn't inside a repository, or git isn't installed
+pub fn first_commit_time(path: &Path) -> Option<i64> {
+    let dir = path.parent()?;
+    let name = path.file_name()?;
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["log", "--follow", "--diff-filter=A", "--format=%ct", "--"])
+        .arg(name)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // log is newest first, so the original addition is the last line
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .last()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[test]
+fn test_first_commit_time() {
+    let dir = crate::temp::TestDir::new("git");
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    let git = |args: &[&str], date: &str| {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(&*dir)
+            .args(["-c", "user.name=t", "-c", "user.email=t@example.com"])
+            .args(args)
+            .env("GIT_AUTHOR_DATE", date)
+            .env("GIT_COMMITTER_DATE", date)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?}", args);
+    };
+    git(&["init", "-q"], "");
+    std::fs::write(dir.join("old.txt"), "a\n").unwrap();
+    git(&["add", "old.txt"], "");
+    git(&["commit", "-qm", "add"], "@1000000000 +0000");
+    std::fs::write(dir.join("old.txt"), "a\nb\n").unwrap();
+    git(&["commit", "-qam", "change"], "@1100000000 +0000");
+    // a rename keeps the time of the first commit
+    git(&["mv", "old.txt", "sub/new.txt"], "");
+    git(&["commit", "-qm", "move"], "@1200000000 +0000");
+    std::fs::write(dir.join("untracked"), "").unwrap();
+
+    assert_eq!(first_commit_time(&dir.join("sub/new.txt")), Some(1_000_000_000));
+    assert_eq!(first_commit_time(&dir.join("untracked")), None);
+    assert_eq!(first_commit_time(&dir.join("missing/file")), None);
+    assert_eq!(first_commit_time(Path::new("/")), None);
+    // outside a repository
+    let moved = dir.join("sub/new.txt");
+    drop(dir);
+    assert_eq!(first_commit_time(&moved), None);
+}
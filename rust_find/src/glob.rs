@@ -0,0 +1,167 @@
+use std::fmt;
+use std::str::FromStr;
+
+use regex::Regex;
+
+use crate::MyFile;
+
+// a shell glob like *.log or src/**/*.rs. globs without a slash match the name, like -p does.
+// the others match the end of the path, so src/**/*.rs finds ./src/main.rs and
+// /home/me/project/src/bin/run.rs alike
+#[derive(Debug, Clone)]
+pub struct Glob {
+    source: String,
+    regex: Regex,
+    whole_path: bool,
+}
+
+impl Glob {
+    pub fn is_match(&self, file: &MyFile) -> bool {
+        if self.whole_path {
+            self.regex.is_match(&file.path.to_string_lossy())
+        } else {
+            self.regex.is_match(file.name())
+        }
+    }
+}
+
+// the glob syntax shared by --glob, --exclude-dir and ignore files, translated into a regex that
+// the caller anchors. * and ? stay within one path component. ** as a whole component spans any
+// number of them, elsewhere it is just *. [abc], [a-z] and [!abc] are classes, and a backslash
+// makes the next character literal
+pub fn to_regex(glob: &str) -> Result<String, String> {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let component_start = i == 0 || chars[i - 1] == '/';
+        match chars[i] {
+            '*' if component_start && chars.get(i + 1) == Some(&'*') && chars.get(i + 2) == Some(&'/') => {
+                regex.push_str("(?:.*/)?");
+                i += 2;
+            },
+            '*' if component_start && chars.get(i + 1) == Some(&'*') && i + 2 == chars.len() => {
+                regex.push_str(".*");
+                i += 1;
+            },
+            '*' => {
+                regex.push_str("[^/]*");
+            },
+            '?' => {
+                regex.push_str("[^/]");
+            },
+            '[' => {
+                let mut class = String::from("[");
+                let mut j = i + 1;
+                if matches!(chars.get(j), Some('!') | Some('^')) {
+                    class.push('^');
+                    j += 1;
+                }
+                // a ] right at the start is part of the class
+                let start = j;
+                while j < chars.len() && (chars[j] != ']' || j == start) {
+                    if matches!(chars[j], '\\' | '[' | ']' | '&' | '~') {
+                        class.push('\\');
+                    }
+                    class.push(chars[j]);
+                    j += 1;
+                }
+                if j == chars.len() {
+                    return Err(format!("invalid glob '{}': unclosed [", glob));
+                }
+                class.push(']');
+                regex.push_str(&class);
+                i = j;
+            },
+            '\\' => {
+                i += 1;
+                let c = chars.get(i).ok_or_else(|| format!("invalid glob '{}': trailing \\", glob))?;
+                regex.push_str(&regex::escape(&c.to_string()));
+            },
+            c => {
+                regex.push_str(&regex::escape(&c.to_string()));
+            },
+        }
+        i += 1;
+    }
+    Ok(regex)
+}
+
+impl FromStr for Glob {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let whole_path = s.contains('/');
+        let anchor = if whole_path { "(?:^|/)" } else { "^" };
+        let regex = format!("{}{}$", anchor, to_regex(s)?);
+        let regex = Regex::new(&regex).map_err(|e| format!("invalid glob '{}': {}", s, e))?;
+        Ok(Glob { source: s.to_string(), regex, whole_path })
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[test]
+fn test_glob() {
+    use std::path::PathBuf;
+
+    let file = |path: &str| MyFile { path: PathBuf::from(path), ..Default::default() };
+    let matches = |glob: &str, path: &str| glob.parse::<Glob>().unwrap().is_match(&file(path));
+
+    assert!(matches("*.log", "/var/log/syslog.log"));
+    assert!(!matches("*.log", "/var/log/syslog.log.1"));
+    assert!(matches("file?.txt", "./file1.txt"));
+    assert!(!matches("file?.txt", "./file10.txt"));
+    assert!(matches("[ab]*.rs", "src/build.rs"));
+    assert!(!matches("[!ab]*.rs", "src/build.rs"));
+    assert!(matches("[]x].md", "./].md"));
+    assert!(matches("\\*.txt", "./*.txt"));
+    assert!(!matches("\\*.txt", "./a.txt"));
+
+    assert!(matches("src/**/*.rs", "./src/main.rs"));
+    assert!(matches("src/**/*.rs", "/home/me/project/src/bin/run.rs"));
+    assert!(!matches("src/**/*.rs", "./mysrc/main.rs"));
+    assert!(!matches("src/*.rs", "./src/bin/run.rs"));
+    assert!(matches("**/target/*", "./a/target/debug"));
+
+    // ** only spans directories as a component of its own
+    assert!(matches("src/**", "./src/a/b.rs"));
+    assert!(matches("a**.rs", "./ab.rs"));
+    assert!(!matches("src/a**.rs", "./src/a/b.rs"));
+    assert_eq!(to_regex("**/x/**").unwrap(), "(?:.*/)?x/.*");
+    assert_eq!(to_regex("[a&&b~~]").unwrap(), "[a\\&\\&b\\~\\~]");
+
+    assert!("[abc".parse::<Glob>().is_err());
+    assert!("abc\\".parse::<Glob>().is_err());
+}
+
+#[test]
+fn test_glob_edge_cases() {
+    use std::path::PathBuf;
+
+    let file = |path: &str| MyFile { path: PathBuf::from(path), ..Default::default() };
+    let matches = |glob: &str, path: &str| glob.parse::<Glob>().unwrap().is_match(&file(path));
+
+    // regex characters in a glob are literal
+    assert!(matches("a+b(1).txt", "./a+b(1).txt"));
+    assert!(!matches("a.txt", "./abtxt"));
+    assert!(matches("^$|{}", "./^$|{}"));
+    // * matches nothing too, but never a slash
+    assert!(matches("*", "./x"));
+    assert!(matches("a*", "./a"));
+    assert!(!matches("a/*", "./a/b/c"));
+    // a glob with a slash only matches whole components at its start
+    assert!(matches("/etc/*.conf", "/etc/x.conf"));
+    assert!(!matches("c/*.conf", "/etc/x.conf"));
+    assert!(matches("[a-c]?", "./b9"));
+    assert!(matches("é?", "./éé"));
+
+    assert_eq!(to_regex("").unwrap(), "");
+    assert!(to_regex("[!").is_err());
+    assert!(to_regex("[]").is_err());
+    assert!("[z-a]".parse::<Glob>().is_err());
+}
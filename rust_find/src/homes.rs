@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::time::{Date, SECS_PER_DAY};
+use crate::walk::WalkOptions;
+use crate::MyFile;
+
+// one home directory's share of the scan
+pub struct Home<'a> {
+    pub dir: PathBuf,
+    pub total: u64,
+    pub files: usize,
+    // biggest first
+    pub largest: Vec<&'a MyFile>,
+    // files of at least the huge size, least recently modified first
+    pub oldest_huge: Vec<&'a MyFile>,
+}
+
+// a home's space goes into dot-directories (.cache, .local) and gitignored build output as much
+// as anywhere, so the report walks them whatever --hidden and --no-ignore say
+pub fn walk_options(options: &WalkOptions) -> WalkOptions {
+    WalkOptions { hidden: true, ignore: false, ..options.clone() }
+}
+
+// the directory directly inside one of the roots that the file is in. files right inside a root
+// count toward the root itself
+fn home_of(path: &Path, roots: &[PathBuf]) -> PathBuf {
+    for root in roots {
+        if let Ok(rest) = path.strip_prefix(root) {
+            let mut components = rest.components();
+            let first = components.next();
+            return match (first, components.next()) {
+                (Some(first), Some(_)) => root.join(first),
+                _ => root.clone(),
+            };
+        }
+    }
+    path.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+// everything is gathered in one pass over the matches, then each home keeps its top entries.
+// homes are ordered by how much they take up, largest first
+pub fn report<'a>(files: &[&'a MyFile], roots: &[PathBuf], top: usize, huge: u64) -> Vec<Home<'a>> {
+    let mut homes: HashMap<PathBuf, Home> = HashMap::new();
+    for &file in files {
+        let dir = home_of(&file.path, roots);
+        let home = homes.entry(dir.clone()).or_insert_with(|| Home {
+            dir,
+            total: 0,
+            files: 0,
+            largest: Vec::new(),
+            oldest_huge: Vec::new(),
+        });
+        home.total += file.size_bytes;
+        home.files += 1;
+        home.largest.push(file);
+        if file.size_bytes >= huge {
+            home.oldest_huge.push(file);
+        }
+    }
+    let mut homes: Vec<Home> = homes.into_values().collect();
+    for home in &mut homes {
+        home.largest.sort_by(|a, b| b.size_bytes.cmp(&a.size_bytes).then_with(|| a.path.cmp(&b.path)));
+        home.largest.truncate(top);
+        home.oldest_huge.sort_by(|a, b| a.modified.cmp(&b.modified).then_with(|| a.path.cmp(&b.path)));
+        home.oldest_huge.truncate(top);
+    }
+    homes.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.dir.cmp(&b.dir)));
+    homes
+}
+
+// 1536 -> "1.5 KiB"
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+fn day(timestamp: i64) -> String {
+    let date = Date::from_days_since_epoch(timestamp.div_euclid(SECS_PER_DAY));
+    format!("{:04}-{:02}-{:02}", date.year, date.month, date.day)
+}
+
+pub fn lines(homes: &[Home]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for home in homes {
+        lines.push(format!("{}: {} in {}",
+                           home.dir.display(),
+                           human_size(home.total),
+                           crate::output::count(home.files as u64, "file", "files")));
+        if !home.largest.is_empty() {
+            lines.push(String::from("  largest:"));
+            for file in &home.largest {
+                lines.push(format!("    {:>10}  {}", human_size(file.size_bytes), file.path.display()));
+            }
+        }
+        if !home.oldest_huge.is_empty() {
+            lines.push(String::from("  oldest huge files:"));
+            for file in &home.oldest_huge {
+                lines.push(format!("    {}  {:>10}  {}", day(file.modified), human_size(file.size_bytes), file.path.display()));
+            }
+        }
+    }
+    lines
+}
+
+#[test]
+fn test_homes_report() {
+    let file = |path: &str, size_bytes: u64, modified: i64| MyFile {
+        path: PathBuf::from(path),
+        size_bytes,
+        modified,
+        ..Default::default()
+    };
+    let files = [
+        file("/home/ana/vm.img", 40_000, 1_600_000_000),
+        file("/home/ana/notes.txt", 10, 1_700_000_000),
+        file("/home/ana/old/backup.iso", 20_000, 1_500_000_000),
+        file("/home/bo/song.flac", 30, 1_650_000_000),
+        file("/home/stray.log", 5, 0),
+    ];
+    let files: Vec<&MyFile> = files.iter().collect();
+    let homes = report(&files, &[PathBuf::from("/home")], 2, 10_000);
+
+    let dirs: Vec<&Path> = homes.iter().map(|h| h.dir.as_path()).collect();
+    assert_eq!(dirs, vec![Path::new("/home/ana"), Path::new("/home/bo"), Path::new("/home")]);
+    assert_eq!((homes[0].total, homes[0].files), (60_010, 3));
+    assert_eq!(homes[0].largest, vec![files[0], files[2]]);
+    assert_eq!(homes[0].oldest_huge, vec![files[2], files[0]]);
+    assert!(homes[1].oldest_huge.is_empty());
+
+    assert_eq!(human_size(512), "512 B");
+    assert_eq!(human_size(1536), "1.5 KiB");
+    assert_eq!(human_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    assert_eq!(day(1_700_000_000), "2023-11-14");
+}
+
+#[test]
+fn test_homes_count_hidden() {
+    let root = crate::temp::TestDir::new("homes");
+    std::fs::create_dir_all(root.join("ana/.cache/app")).unwrap();
+    std::fs::write(root.join("ana/notes.txt"), [0; 10]).unwrap();
+    std::fs::write(root.join("ana/.cache/app/blob"), vec![1; 100_000]).unwrap();
+    let (files, _) = crate::walk::collect(crate::walk::walk(vec![root.to_path_buf()], &walk_options(&WalkOptions::default())));
+    let files: Vec<&MyFile> = files.iter().collect();
+    let homes = report(&files, &[root.to_path_buf()], 1, 500);
+    // sizes are what the files take up on disk, so only roughly known
+    assert_eq!(homes[0].files, 2);
+    assert!(homes[0].total >= 100_000, "{}", homes[0].total);
+    assert_eq!(homes[0].largest[0].path, root.join("ana/.cache/app/blob"));
+}
+
+#[test]
+fn test_homes_edge_cases() {
+    let roots = [PathBuf::from("/home"), PathBuf::from("/srv/users")];
+    assert_eq!(home_of(Path::new("/srv/users/cy/a/b"), &roots), PathBuf::from("/srv/users/cy"));
+    // a root given as a file is its own home, and files outside every root go by their directory
+    assert_eq!(home_of(Path::new("/home"), &roots), PathBuf::from("/home"));
+    assert_eq!(home_of(Path::new("/tmp/x/y"), &roots), PathBuf::from("/tmp/x"));
+    assert_eq!(home_of(Path::new("/homes/a/b"), &roots), PathBuf::from("/homes/a"));
+
+    let file = |path: &str, size_bytes: u64| MyFile { path: PathBuf::from(path), size_bytes, ..Default::default() };
+    let files = [file("/home/b/x", 5), file("/home/a/y", 5), file("/home/a/z", 0)];
+    let files: Vec<&MyFile> = files.iter().collect();
+    // equal totals go by name, and a top of 0 keeps only the totals
+    let homes = report(&files, &roots, 0, 0);
+    assert_eq!(homes.iter().map(|h| h.dir.as_path()).collect::<Vec<_>>(), vec![Path::new("/home/a"), Path::new("/home/b")]);
+    assert!(homes.iter().all(|h| h.largest.is_empty() && h.oldest_huge.is_empty()));
+    assert_eq!(lines(&homes), vec!["/home/a: 5 B in 2 files", "/home/b: 5 B in 1 file"]);
+    assert!(report(&[], &roots, 5, 0).is_empty());
+
+    assert_eq!(human_size(0), "0 B");
+    assert_eq!(human_size(1023), "1023 B");
+    assert_eq!(human_size(1024), "1.0 KiB");
+    // there is no unit past PiB
+    assert_eq!(human_size(u64::MAX), "16384.0 PiB");
+    assert_eq!(day(-1), "1969-12-31");
+}
+
+#[test]
+fn test_homes_lines() {
+    let files = [
+        MyFile { path: PathBuf::from("/home/a/big"), size_bytes: 2048, modified: 0, ..Default::default() },
+        MyFile { path: PathBuf::from("/home/a/small"), size_bytes: 1, modified: 0, ..Default::default() },
+    ];
+    let files: Vec<&MyFile> = files.iter().collect();
+    let homes = report(&files, &[PathBuf::from("/home")], 1, 1024);
+    assert_eq!(lines(&homes), vec![
+        "/home/a: 2.0 KiB in 2 files",
+        "  largest:",
+        "       2.0 KiB  /home/a/big",
+        "  oldest huge files:",
+        "    1970-01-01     2.0 KiB  /home/a/big",
+    ]);
+}
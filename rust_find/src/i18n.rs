@@ -0,0 +1,284 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    En,
+    Es,
+    De,
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    // accepts plain codes (es) as well as locale names (es_ES.UTF-8, de-AT)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.split(['_', '-', '.', '@']).next().unwrap_or("");
+        match code.to_ascii_lowercase().as_str() {
+            "en" | "c" | "posix" => Ok(Lang::En),
+            "es" => Ok(Lang::Es),
+            "de" => Ok(Lang::De),
+            _ => Err(format!("unsupported language '{}', expected en, es or de", s)),
+        }
+    }
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+// same precedence as gettext: LC_ALL, then LC_MESSAGES, then LANG. unknown languages fall back
+// to english rather than failing
+fn from_env() -> Lang {
+    ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(Lang::En)
+}
+
+pub fn init(lang: Option<Lang>) {
+    let _ = LANG.set(lang.unwrap_or_else(from_env));
+}
+
+// the catalogs are keyed by the english message, so call sites stay readable and anything
+// missing from a catalog just shows up in english
+const ES: &[(&str, &str)] = &[
+    ("warning", "aviso"),
+    ("skipping search in directory", "omitiendo la búsqueda en el directorio"),
+    ("skipping regex match", "omitiendo la expresión regular"),
+    ("no such file or directory", "no existe el archivo o directorio"),
+    ("could not access file", "no se pudo acceder al archivo"),
+    ("invalid regex", "expresión regular no válida"),
+    ("could not read attributes", "no se pudieron leer los atributos"),
+    ("could not read extended attributes", "no se pudieron leer los atributos extendidos"),
+    ("could not remove quarantine", "no se pudo quitar la cuarentena"),
+    ("could not change owner", "no se pudo cambiar el propietario"),
+    ("--chown-map requires root", "--chown-map requiere permisos de root"),
+    ("--chown-map is not supported on this platform", "--chown-map no está disponible en esta plataforma"),
+    ("refusing to run action", "no se ejecuta la acción"),
+    ("could not write audit log", "no se pudo escribir el registro de auditoría"),
+    ("could not save query", "no se pudo guardar la consulta"),
+    ("could not parse command", "no se pudo interpretar el comando"),
+    ("unknown command", "comando desconocido"),
+    ("invalid number", "número no válido"),
+    ("could not run annotation command", "no se pudo ejecutar el comando de anotación"),
+    ("could not scan file", "no se pudo analizar el archivo"),
+    ("skipping directory already visited", "omitiendo un directorio ya visitado"),
+    ("could not read directory", "no se pudo leer el directorio"),
+    ("could not read image", "no se pudo leer la imagen"),
+    ("skipping search root given more than once", "omitiendo una raíz de búsqueda indicada más de una vez"),
+    ("walking container storage, which --skip-container-storage leaves out",
+     "recorriendo el almacenamiento de contenedores, que --skip-container-storage omite"),
+    ("could not save checkpoint", "no se pudo guardar el punto de control"),
+    ("could not read archive", "no se pudo leer el archivo comprimido"),
+    ("creation time is not recorded, leaving out files like",
+     "la fecha de creación no está registrada, se omiten archivos como"),
+    ("could not write action report", "no se pudo escribir el informe de acciones"),
+    ("skipping planned file", "se omite un archivo planificado"),
+    ("could not read config", "no se pudo leer la configuración"),
+    ("could not read the file list", "no se pudo leer la lista de archivos"),
+    ("could not unset", "no se pudo quitar"),
+    ("command failed", "el comando falló"),
+    ("command failed, retrying", "el comando falló, se reintenta"),    ("error", "error"),
+    ("note", "nota"),
+    ("pattern matched no files", "el patrón no coincidió con ningún archivo"),
+    ("stopped at the first error because of --strict", "detenido en el primer error por --strict"),
+    ("could not read checkpoint", "no se pudo leer el punto de control"),
+    ("no results from a previous run", "no hay resultados de una ejecución anterior"),
+    ("could not pick results from the last run", "no se pudieron elegir resultados de la última ejecución"),
+    ("invalid --exclude-dir pattern", "patrón de --exclude-dir no válido"),
+    ("could not read plan", "no se pudo leer el plan"),
+    ("could not load the query", "no se pudo cargar la consulta"),
+    ("stdin can't be read for paths with repl, which reads its commands from it",
+     "con repl no se pueden leer rutas de stdin, de donde lee sus comandos"),
+    ("--checkpoint only works when matches are listed as they are found, without sorting, reports or actions",
+     "--checkpoint solo funciona cuando las coincidencias se listan según se encuentran, sin ordenar, informes ni acciones"),
+    ("--one-file-system is only supported on unix", "--one-file-system solo está disponible en unix"),
+    ("--plan records an action, such as --chown-map or --remove-quarantine, instead of running it",
+     "--plan registra una acción, como --chown-map o --remove-quarantine, en lugar de ejecutarla"),
+    ("scanning shard {} of {}, combine the outputs of all shards with `rust-find merge FILE...` once they are done",
+     "analizando el fragmento {} de {}, combine las salidas de todos con `rust-find merge FILE...` cuando terminen"),
+    ("could not read results", "no se pudieron leer los resultados"),
+    ("could not write plan", "no se pudo escribir el plan"),
+    ("the command failed for some matches", "el comando falló para algunas coincidencias"),
+    ("{} of {}", "{} de {}"),
+    ("match", "coincidencia"),
+    ("matches", "coincidencias"),
+    ("could not post matches", "no se pudieron enviar las coincidencias"),
+    ("could not send matches", "no se pudieron enviar las coincidencias"),
+    ("paused, press r to resume", "en pausa, pulse r para continuar"),
+    ("resumed", "reanudado"),
+    ("status: scanning {} ({} dirs, {} files, {}s elapsed)",
+     "estado: analizando {} ({} directorios, {} archivos, {}s transcurridos)"),
+    ("Status: scanning {}. {} and {} so far, {} seconds elapsed.",
+     "Estado: analizando {}. {} y {} hasta ahora, {} segundos transcurridos."),
+    ("directory", "directorio"),
+    ("directories", "directorios"),
+    ("file", "archivo"),
+    ("files", "archivos"),
+    ("kept temporary files in", "se conservaron los archivos temporales en"),
+    ("rust-find crashed. please attach {} when reporting this bug",
+     "rust-find se cerró inesperadamente. adjunte {} al informar de este error"),
+    ("rust-find crashed, and writing a crash report to {} failed: {}",
+     "rust-find se cerró inesperadamente, y no se pudo escribir el informe en {}: {}"),
+];
+
+const DE: &[(&str, &str)] = &[
+    ("warning", "Warnung"),
+    ("skipping search in directory", "Suche im Verzeichnis wird übersprungen"),
+    ("skipping regex match", "regulärer Ausdruck wird übersprungen"),
+    ("no such file or directory", "Datei oder Verzeichnis nicht gefunden"),
+    ("could not access file", "Zugriff auf Datei nicht möglich"),
+    ("invalid regex", "ungültiger regulärer Ausdruck"),
+    ("could not read attributes", "Attribute konnten nicht gelesen werden"),
+    ("could not read extended attributes", "erweiterte Attribute konnten nicht gelesen werden"),
+    ("could not remove quarantine", "Quarantäne konnte nicht entfernt werden"),
+    ("could not change owner", "Besitzer konnte nicht geändert werden"),
+    ("--chown-map requires root", "--chown-map erfordert Root-Rechte"),
+    ("--chown-map is not supported on this platform", "--chown-map wird auf dieser Plattform nicht unterstützt"),
+    ("refusing to run action", "Aktion wird nicht ausgeführt"),
+    ("could not write audit log", "Audit-Log konnte nicht geschrieben werden"),
+    ("could not save query", "Abfrage konnte nicht gespeichert werden"),
+    ("could not parse command", "Befehl konnte nicht verarbeitet werden"),
+    ("unknown command", "unbekannter Befehl"),
+    ("invalid number", "ungültige Zahl"),
+    ("could not run annotation command", "Annotationsbefehl konnte nicht ausgeführt werden"),
+    ("could not scan file", "Datei konnte nicht gescannt werden"),
+    ("skipping directory already visited", "bereits besuchtes Verzeichnis wird übersprungen"),
+    ("could not read directory", "Verzeichnis konnte nicht gelesen werden"),
+    ("could not read image", "Image konnte nicht gelesen werden"),
+    ("skipping search root given more than once", "mehrfach angegebene Suchwurzel wird übersprungen"),
+    ("walking container storage, which --skip-container-storage leaves out",
+     "Container-Speicher wird durchsucht, --skip-container-storage lässt ihn aus"),
+    ("could not save checkpoint", "Checkpoint konnte nicht gespeichert werden"),
+    ("could not read archive", "Archiv konnte nicht gelesen werden"),
+    ("creation time is not recorded, leaving out files like",
+     "Erstellungszeit wird nicht erfasst, Dateien wie diese werden ausgelassen"),
+    ("could not write action report", "Aktionsbericht konnte nicht geschrieben werden"),
+    ("skipping planned file", "geplante Datei wird übersprungen"),
+    ("could not read config", "Konfiguration konnte nicht gelesen werden"),
+    ("could not read the file list", "Dateiliste konnte nicht gelesen werden"),
+    ("could not unset", "konnte nicht zurückgesetzt werden"),
+    ("command failed", "Befehl fehlgeschlagen"),
+    ("command failed, retrying", "Befehl fehlgeschlagen, neuer Versuch"),    ("error", "Fehler"),
+    ("note", "Hinweis"),
+    ("pattern matched no files", "Muster passte auf keine Datei"),
+    ("stopped at the first error because of --strict", "wegen --strict beim ersten Fehler angehalten"),
+    ("could not read checkpoint", "Checkpoint konnte nicht gelesen werden"),
+    ("no results from a previous run", "keine Ergebnisse eines früheren Laufs"),
+    ("could not pick results from the last run", "Ergebnisse des letzten Laufs konnten nicht ausgewählt werden"),
+    ("invalid --exclude-dir pattern", "ungültiges Muster für --exclude-dir"),
+    ("could not read plan", "Plan konnte nicht gelesen werden"),
+    ("could not load the query", "Abfrage konnte nicht geladen werden"),
+    ("stdin can't be read for paths with repl, which reads its commands from it",
+     "mit repl können keine Pfade von stdin gelesen werden, da es dort seine Befehle liest"),
+    ("--checkpoint only works when matches are listed as they are found, without sorting, reports or actions",
+     "--checkpoint funktioniert nur, wenn Treffer ausgegeben werden, sobald sie gefunden sind, ohne Sortierung, Berichte oder Aktionen"),
+    ("--one-file-system is only supported on unix", "--one-file-system wird nur unter Unix unterstützt"),
+    ("--plan records an action, such as --chown-map or --remove-quarantine, instead of running it",
+     "--plan zeichnet eine Aktion wie --chown-map oder --remove-quarantine auf, statt sie auszuführen"),
+    ("scanning shard {} of {}, combine the outputs of all shards with `rust-find merge FILE...` once they are done",
+     "Teil {} von {} wird durchsucht, die Ausgaben aller Teile lassen sich danach mit `rust-find merge FILE...` zusammenführen"),
+    ("could not read results", "Ergebnisse konnten nicht gelesen werden"),
+    ("could not write plan", "Plan konnte nicht geschrieben werden"),
+    ("the command failed for some matches", "der Befehl ist für einige Treffer fehlgeschlagen"),
+    ("{} of {}", "{} von {}"),
+    ("match", "Treffer"),
+    ("matches", "Treffer"),
+    ("could not post matches", "Treffer konnten nicht gesendet werden"),
+    ("could not send matches", "Treffer konnten nicht gesendet werden"),
+    ("paused, press r to resume", "angehalten, r drücken zum Fortsetzen"),
+    ("resumed", "fortgesetzt"),
+    ("status: scanning {} ({} dirs, {} files, {}s elapsed)",
+     "Status: durchsuche {} ({} Verzeichnisse, {} Dateien, {}s vergangen)"),
+    ("Status: scanning {}. {} and {} so far, {} seconds elapsed.",
+     "Status: durchsuche {}. Bisher {} und {}, {} Sekunden vergangen."),
+    ("directory", "Verzeichnis"),
+    ("directories", "Verzeichnisse"),
+    ("file", "Datei"),
+    ("files", "Dateien"),
+    ("kept temporary files in", "temporäre Dateien wurden behalten in"),
+    ("rust-find crashed. please attach {} when reporting this bug",
+     "rust-find ist abgestürzt. Bitte {} beim Melden dieses Fehlers anhängen"),
+    ("rust-find crashed, and writing a crash report to {} failed: {}",
+     "rust-find ist abgestürzt, und der Absturzbericht konnte nicht nach {} geschrieben werden: {}"),
+];
+
+fn catalog(lang: Lang) -> &'static [(&'static str, &'static str)] {
+    match lang {
+        Lang::En => &[],
+        Lang::Es => ES,
+        Lang::De => DE,
+    }
+}
+
+fn translate(lang: Lang, msg: &'static str) -> &'static str {
+    catalog(lang).iter()
+        .find(|(en, _)| *en == msg)
+        .map(|(_, translated)| *translated)
+        .unwrap_or(msg)
+}
+
+// before init, which needs the parsed command line, the environment decides
+pub fn tr(msg: &'static str) -> &'static str {
+    translate(LANG.get().copied().unwrap_or_else(from_env), msg)
+}
+
+// tr for a message with {} placeholders, filled in order. translations keep the placeholders
+pub fn trf(msg: &'static str, args: &[&dyn Display]) -> String {
+    let mut parts = tr(msg).split("{}");
+    let mut filled = parts.next().unwrap_or("").to_string();
+    for (part, arg) in parts.zip(args) {
+        filled.push_str(&arg.to_string());
+        filled.push_str(part);
+    }
+    filled
+}
+
+#[test]
+fn test_catalogs() {
+    assert_eq!("es_ES.UTF-8".parse::<Lang>(), Ok(Lang::Es));
+    assert_eq!("de".parse::<Lang>(), Ok(Lang::De));
+    assert_eq!("C".parse::<Lang>(), Ok(Lang::En));
+    assert!("xx_YY".parse::<Lang>().is_err());
+
+    assert_eq!(translate(Lang::Es, "invalid regex"), "expresión regular no válida");
+    assert_eq!(translate(Lang::De, "not in any catalog"), "not in any catalog");
+    assert_eq!(translate(Lang::En, "invalid regex"), "invalid regex");
+
+    // every language translates the same set of messages, keeping their placeholders
+    let keys: Vec<&str> = ES.iter().map(|(en, _)| *en).collect();
+    assert_eq!(keys, DE.iter().map(|(en, _)| *en).collect::<Vec<&str>>());
+    for (en, translated) in ES.iter().chain(DE) {
+        assert_eq!(en.matches("{}").count(), translated.matches("{}").count(), "{}", en);
+    }
+    assert_eq!(translate(Lang::Es, "{} of {}").replacen("{}", "2", 1).replacen("{}", "5", 1), "2 de 5");
+    assert_eq!(trf("not in any catalog {} {}", &[&1, &"x"]), "not in any catalog 1 x");
+    assert_eq!(trf("{}", &[]), "");
+}
+
+#[test]
+fn test_lang_edge_cases() {
+    // only the language part of a locale counts, in any case
+    assert_eq!("DE_de".parse::<Lang>(), Ok(Lang::De));
+    assert_eq!("es@euro".parse::<Lang>(), Ok(Lang::Es));
+    assert_eq!("POSIX".parse::<Lang>(), Ok(Lang::En));
+    assert_eq!("C.UTF-8".parse::<Lang>(), Ok(Lang::En));
+    assert!("".parse::<Lang>().is_err());
+    assert!("_ES".parse::<Lang>().is_err());
+    assert!("esp".parse::<Lang>().is_err());
+
+    // lookups are exact
+    assert_eq!(translate(Lang::Es, "Invalid regex"), "Invalid regex");
+    assert_eq!(translate(Lang::Es, "invalid regex "), "invalid regex ");
+    assert_eq!(translate(Lang::De, ""), "");
+    // a message is only listed once, and every one is translated
+    for catalog in [ES, DE] {
+        for (i, (en, translated)) in catalog.iter().enumerate() {
+            assert!(catalog[..i].iter().all(|(other, _)| other != en), "{} is listed twice", en);
+            // "error" is spelled the same in spanish
+            let same_word = *en == "error" && catalog == ES;
+            assert!(!translated.is_empty() && (translated != en || same_word), "{} is not translated", en);
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use regex::Regex;
+
+use crate::glob;
+
+// one line of an ignore file
+#[derive(Debug)]
+struct Pattern {
+    regex: Regex,
+    // a leading ! puts back what an earlier pattern ignored
+    negated: bool,
+    // a trailing / only matches directories
+    dir_only: bool,
+}
+
+// a gitignore pattern as a regex over paths relative to the ignore file. a pattern without a
+// slash in the middle matches a name at any depth, one with a slash is anchored. patterns git
+// would reject (an unclosed [, a trailing \) match nothing
+pub fn glob_regex(glob: &str) -> Option<Regex> {
+    let anchored = glob.contains('/');
+    let glob = glob.strip_prefix('/').unwrap_or(glob);
+    let anchor = if anchored { "^" } else { "^(?:.*/)?" };
+    Regex::new(&format!("{}{}$", anchor, glob::to_regex(glob).ok()?)).ok()
+}
+
+fn parse_pattern(line: &str) -> Option<Pattern> {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    // trailing spaces are dropped unless escaped
+    let line = match line.trim_end_matches(' ') {
+        trimmed if trimmed.ends_with('\\') && trimmed.len() < line.len() => &line[..trimmed.len() + 1],
+        trimmed => trimmed,
+    };
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (negated, line) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line.strip_prefix('\\').filter(|rest| rest.starts_with(['#', '!'])).unwrap_or(line)),
+    };
+    let (dir_only, line) = match line.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    if line.is_empty() {
+        return None;
+    }
+    Some(Pattern { regex: glob_regex(line)?, negated, dir_only })
+}
+
+// the patterns of one ignore file
+#[derive(Debug)]
+struct Rules {
+    // paths are matched relative to this directory, after prefix. for ignore files inside the walk
+    // dir is where the file is and prefix is empty. for those above the walk roots dir is the root
+    // as given on the command line and prefix is the way from the ignore file down to it, since the
+    // walked paths may be relative and can't simply be stripped
+    dir: PathBuf,
+    prefix: String,
+    patterns: Vec<Pattern>,
+}
+
+impl Rules {
+    fn parse(text: &str, dir: &Path, prefix: String) -> Rules {
+        Rules {
+            dir: dir.to_path_buf(),
+            prefix,
+            patterns: text.lines().filter_map(parse_pattern).collect(),
+        }
+    }
+
+    fn read(file: &Path, dir: &Path, prefix: String) -> Option<Rules> {
+        let text = fs::read_to_string(file).ok()?;
+        Some(Rules::parse(&text, dir, prefix))
+    }
+
+    // Some(true) if ignored, Some(false) if a negated pattern put it back, None if no pattern
+    // matched. the last matching pattern in a file wins, like in git
+    fn check(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.dir).ok()?;
+        let relative = format!("{}{}", self.prefix, relative.to_string_lossy());
+        self.patterns.iter()
+            .rev()
+            .find(|p| (is_dir || !p.dir_only) && p.regex.is_match(&relative))
+            .map(|p| !p.negated)
+    }
+}
+
+// the ignore files that apply inside one directory of a git repository: its own, then those of its
+// parents up to the repository root
+#[derive(Debug)]
+pub struct Ignore {
+    // most important first: .ignore, .gitignore, and at the repository root also
+    // .git/info/exclude and the global excludes
+    rules: Vec<Rules>,
+    parent: Option<Arc<Ignore>>,
+}
+
+impl Ignore {
+    // files in deeper directories override those further up, as in git
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut level = Some(self);
+        while let Some(ignore) = level {
+            for rules in &ignore.rules {
+                if let Some(ignored) = rules.check(path, is_dir) {
+                    return ignored;
+                }
+            }
+            level = ignore.parent.as_deref();
+        }
+        false
+    }
+}
+
+// the ignore files of one directory. `has` tells whether the directory contains an entry, so
+// directories without ignore files don't cost any extra reads. outside of a repository nothing is
+// ignored, and a nested repository starts over without the rules of the outer one
+fn level(parent: Option<&Arc<Ignore>>,
+         dir: &Path,
+         has: impl Fn(&str) -> bool,
+         match_dir: &Path,
+         prefix: &str,
+         excludes: &str) -> Option<Arc<Ignore>> {
+    let repo_root = has(".git");
+    if !repo_root && parent.is_none() {
+        return None;
+    }
+    let mut rules = Vec::new();
+    for name in [".ignore", ".gitignore"] {
+        if has(name) {
+            rules.extend(Rules::read(&dir.join(name), match_dir, prefix.to_string()));
+        }
+    }
+    if repo_root {
+        rules.extend(Rules::read(&dir.join(".git/info/exclude"), match_dir, prefix.to_string()));
+        rules.push(Rules::parse(excludes, match_dir, prefix.to_string()));
+        return Some(Arc::new(Ignore { rules, parent: None }));
+    }
+    if rules.is_empty() {
+        return parent.cloned();
+    }
+    Some(Arc::new(Ignore { rules, parent: parent.cloned() }))
+}
+
+// the rules for a directory the walk is about to read, given those of the directory it is in
+pub fn enter(parent: Option<&Arc<Ignore>>, dir: &Path, has: impl Fn(&str) -> bool, excludes: &str) -> Option<Arc<Ignore>> {
+    level(parent, dir, has, dir, "", excludes)
+}
+
+// the rules coming from above a walk root, when the root is somewhere inside a repository
+pub fn above(root: &Path, excludes: &str) -> Option<Arc<Ignore>> {
+    let absolute = fs::canonicalize(root).ok()?;
+    let repo_root = absolute.ancestors().skip(1).find(|dir| dir.join(".git").exists())?;
+    let mut dirs: Vec<&Path> = absolute.ancestors().skip(1).take_while(|dir| dir.starts_with(repo_root)).collect();
+    dirs.reverse();
+    let mut ignore = None;
+    for dir in dirs {
+        let prefix = format!("{}/", absolute.strip_prefix(dir).ok()?.to_string_lossy());
+        ignore = level(ignore.as_ref(), dir, |name| dir.join(name).exists(), root, &prefix, excludes);
+    }
+    ignore
+}
+
+fn home() -> Option<PathBuf> {
+    std::env::var_os("HOME").filter(|h| !h.is_empty()).map(PathBuf::from)
+}
+
+fn config_dir() -> Option<PathBuf> {
+    match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => {
+            Some(PathBuf::from(dir))
+        },
+        _ => {
+            home().map(|h| h.join(".config"))
+        },
+    }
+}
+
+// core.excludesFile from a git config file, if set there
+fn excludes_setting(config: &str) -> Option<String> {
+    let mut in_core = false;
+    let mut setting = None;
+    for line in config.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_core = line.trim_start_matches('[').trim_end_matches(']').trim().eq_ignore_ascii_case("core");
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            if in_core && key.trim().eq_ignore_ascii_case("excludesfile") {
+                setting = Some(value.trim().trim_matches('"').to_string());
+            }
+        }
+    }
+    setting
+}
+
+// the contents of the user's global git excludes. like git, core.excludesFile from ~/.gitconfig
+// wins over the xdg config, and without it ~/.config/git/ignore is used
+pub fn global_excludes() -> String {
+    let configs = [home().map(|h| h.join(".gitconfig")), config_dir().map(|d| d.join("git/config"))];
+    let setting = configs.iter()
+        .flatten()
+        .filter_map(|config| fs::read_to_string(config).ok())
+        .find_map(|config| excludes_setting(&config));
+    let file = match setting {
+        Some(path) => {
+            match path.strip_prefix("~/") {
+                Some(rest) => home().map(|h| h.join(rest)),
+                None => Some(PathBuf::from(path)),
+            }
+        },
+        None => {
+            config_dir().map(|d| d.join("git/ignore"))
+        },
+    };
+    file.and_then(|f| fs::read_to_string(f).ok()).unwrap_or_default()
+}
+
+#[test]
+fn test_ignore_rules() {
+    let rules = Rules::parse("# build output\n\
+                              *.o\n\
+                              /target\n\
+                              logs/\n\
+                              doc/**/*.html\n\
+                              !keep.o\n\
+                              \\#notes\n\
+                              trailing   \n\
+                              [!a-c]x.tmp\n",
+                             Path::new("repo"), String::new());
+    let ignored = |path: &str, is_dir: bool| rules.check(Path::new(path), is_dir);
+    assert_eq!(ignored("repo/main.o", false), Some(true));
+    assert_eq!(ignored("repo/src/deep/lib.o", false), Some(true));
+    assert_eq!(ignored("repo/src/keep.o", false), Some(false));
+    assert_eq!(ignored("repo/target", true), Some(true));
+    assert_eq!(ignored("repo/src/target", true), None);
+    assert_eq!(ignored("repo/src/logs", true), Some(true));
+    assert_eq!(ignored("repo/src/logs", false), None);
+    assert_eq!(ignored("repo/doc/index.html", false), Some(true));
+    assert_eq!(ignored("repo/doc/a/b/page.html", false), Some(true));
+    assert_eq!(ignored("repo/src/doc/index.html", false), None);
+    assert_eq!(ignored("repo/#notes", false), Some(true));
+    assert_eq!(ignored("repo/trailing", false), Some(true));
+    assert_eq!(ignored("repo/dx.tmp", false), Some(true));
+    assert_eq!(ignored("repo/ax.tmp", false), None);
+    assert_eq!(ignored("elsewhere/main.o", false), None);
+
+    // an ignore file above the walk root, which was given as "."
+    let above = Rules::parse("/sub/generated.rs\n", Path::new("."), String::from("sub/"));
+    assert_eq!(above.check(Path::new("./generated.rs"), false), Some(true));
+    assert_eq!(above.check(Path::new("./other/generated.rs"), false), None);
+
+    // the same glob syntax as --glob, anchored the gitignore way
+    assert!(glob_regex("[abc").is_none());
+    assert!(glob_regex("build").unwrap().is_match("a/b/build"));
+    assert!(!glob_regex("a/build").unwrap().is_match("x/a/build"));
+
+    assert_eq!(excludes_setting("[user]\n\tname = x\n[core]\n\texcludesFile = \"~/.gitignore_global\"\n"),
+               Some(String::from("~/.gitignore_global")));
+    assert_eq!(excludes_setting("[user]\n\texcludesfile = nope\n"), None);
+}
+
+#[test]
+fn test_ignore_rules_edge_cases() {
+    // lines that end up empty are no patterns at all
+    for line in ["", "   ", "#", "!", "/", "!/", "\r"] {
+        assert!(parse_pattern(line).is_none(), "{:?}", line);
+    }
+    let rules = Rules::parse("a\\ \r\n\\!bang\nfoo/**\n**/bar\n*.log\n!*.log\nlate.log\n", Path::new("r"), String::new());
+    let ignored = |path: &str, is_dir: bool| rules.check(Path::new(path), is_dir);
+    // an escaped trailing space is kept, a windows line ending is not
+    assert_eq!(ignored("r/a ", false), Some(true));
+    assert_eq!(ignored("r/a", false), None);
+    assert_eq!(ignored("r/!bang", false), Some(true));
+    assert_eq!(ignored("r/foo/x/y", false), Some(true));
+    assert_eq!(ignored("r/foo", true), None);
+    assert_eq!(ignored("r/bar", false), Some(true));
+    assert_eq!(ignored("r/x/y/bar", true), Some(true));
+    // the last pattern that matches decides
+    assert_eq!(ignored("r/early.log", false), Some(false));
+    assert_eq!(ignored("r/late.log", false), Some(true));
+    assert_eq!(excludes_setting(""), None);
+    // the last setting wins, and sections and keys ignore case
+    assert_eq!(excludes_setting("[CORE]\nexcludesfile=a\n[ core ]\nExcludesFile = b\n"), Some(String::from("b")));
+}
+
+#[test]
+fn test_ignore_levels() {
+    let dir = crate::temp::TestDir::new("ignore");
+    fs::create_dir_all(dir.join("repo/.git/info")).unwrap();
+    fs::create_dir_all(dir.join("repo/sub/nested/.git")).unwrap();
+    fs::create_dir_all(dir.join("repo/plain")).unwrap();
+    fs::write(dir.join("repo/.gitignore"), "*.o\n").unwrap();
+    fs::write(dir.join("repo/.git/info/exclude"), "secret\n").unwrap();
+    fs::write(dir.join("repo/sub/.ignore"), "!keep.o\n").unwrap();
+    let has = |dir: &Path| { let dir = dir.to_path_buf(); move |name: &str| dir.join(name).exists() };
+    let enter_dir = |parent: Option<&Arc<Ignore>>, path: &Path| enter(parent, path, has(path), "global\n");
+
+    // outside a repository nothing is ignored
+    assert!(enter_dir(None, &dir).is_none());
+    let repo = enter_dir(None, &dir.join("repo")).unwrap();
+    assert!(repo.is_ignored(&dir.join("repo/a.o"), false));
+    assert!(repo.is_ignored(&dir.join("repo/secret"), false));
+    assert!(repo.is_ignored(&dir.join("repo/global"), true));
+    assert!(!repo.is_ignored(&dir.join("repo/a.c"), false));
+    // a directory without ignore files shares its parent's rules
+    let plain = enter_dir(Some(&repo), &dir.join("repo/plain")).unwrap();
+    assert!(Arc::ptr_eq(&plain, &repo));
+    // deeper ignore files override those further up
+    let sub = enter_dir(Some(&repo), &dir.join("repo/sub")).unwrap();
+    assert!(!sub.is_ignored(&dir.join("repo/sub/keep.o"), false));
+    assert!(sub.is_ignored(&dir.join("repo/sub/other.o"), false));
+    // a nested repository starts over
+    let nested = enter_dir(Some(&sub), &dir.join("repo/sub/nested")).unwrap();
+    assert!(!nested.is_ignored(&dir.join("repo/sub/nested/other.o"), false));
+
+    // a walk that starts inside the repository still sees the rules above it
+    let inside = above(&dir.join("repo/sub"), "").unwrap();
+    assert!(inside.is_ignored(&dir.join("repo/sub/x.o"), false));
+    assert!(above(&dir, "").is_none());
+}
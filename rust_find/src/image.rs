@@ -0,0 +1,434 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::temp;
+use crate::walk::WalkOptions;
+use crate::MyFile;
+
+const BLOCK: usize = 512;
+const SCHEME: &str = "docker://";
+
+// the image in a --dirs entry like docker://alpine:3.19
+pub fn reference(dir: &Path) -> Option<&str> {
+    dir.to_str()?.strip_prefix(SCHEME).filter(|r| !r.is_empty())
+}
+
+// one member of a tar archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Entry {
+    pub path: String,
+    pub size: u64,
+    pub modified: i64,
+    // the tar typeflag: b'0' regular file, b'2' symlink, b'5' directory, ...
+    pub kind: u8,
+}
+
+fn field(header: &[u8]) -> &[u8] {
+    let end = header.iter().position(|&b| b == 0).unwrap_or(header.len());
+    &header[..end]
+}
+
+fn text(header: &[u8]) -> String {
+    String::from_utf8_lossy(field(header)).into_owned()
+}
+
+// octal, or big-endian binary when the high bit is set (gnu tar, for sizes past 8GB)
+fn number(header: &[u8]) -> u64 {
+    if header.first().is_some_and(|b| b & 0x80 != 0) {
+        return header[1..].iter().fold(0, |n, &b| (n << 8) | b as u64);
+    }
+    let digits = String::from_utf8_lossy(field(header));
+    u64::from_str_radix(digits.trim(), 8).unwrap_or(0)
+}
+
+// path, size and mtime as overridden by a pax extended header ("LEN key=value\n" records)
+fn apply_pax(records: &str, entry: &mut Entry) {
+    for record in records.lines() {
+        let Some((_, pair)) = record.split_once(' ') else {
+            continue;
+        };
+        match pair.split_once('=') {
+            Some(("path", path)) => entry.path = path.to_string(),
+            Some(("size", size)) => entry.size = size.parse().unwrap_or(entry.size),
+            Some(("mtime", mtime)) => {
+                entry.modified = mtime.split('.').next().and_then(|s| s.parse().ok()).unwrap_or(entry.modified);
+            },
+            _ => {},
+        }
+    }
+}
+
+// calls each with every entry of a tar stream and a reader over its contents. ustar, gnu long
+// names and pax headers are understood, which covers what docker writes
+pub(crate) fn read_tar<R: Read>(mut input: R, mut each: impl FnMut(&Entry, &mut dyn Read) -> io::Result<()>) -> io::Result<()> {
+    let mut long_name: Option<String> = None;
+    let mut pax: Option<String> = None;
+    let mut header = [0u8; BLOCK];
+    loop {
+        match input.read_exact(&mut header) {
+            Ok(()) => {},
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Ok(());
+            },
+            Err(e) => {
+                return Err(e);
+            },
+        }
+        if header.iter().all(|&b| b == 0) {
+            return Ok(());
+        }
+        let size = number(&header[124..136]);
+        let kind = header[156];
+        let mut data = (&mut input).take(size);
+        match kind {
+            b'L' | b'x' => {
+                let mut contents = String::new();
+                data.read_to_string(&mut contents)?;
+                if kind == b'L' {
+                    long_name = Some(contents.trim_end_matches('\0').to_string());
+                } else {
+                    pax = Some(contents);
+                }
+            },
+            b'g' => {
+                io::copy(&mut data, &mut io::sink())?;
+            },
+            _ => {
+                let mut path = text(&header[0..100]);
+                if &header[257..262] == b"ustar" && header[345] != 0 {
+                    path = format!("{}/{}", text(&header[345..500]), path);
+                }
+                let mut entry = Entry {
+                    path: long_name.take().unwrap_or(path),
+                    size,
+                    modified: number(&header[136..148]) as i64,
+                    kind: if kind == 0 { b'0' } else { kind },
+                };
+                if let Some(records) = pax.take() {
+                    apply_pax(&records, &mut entry);
+                }
+                each(&entry, &mut data)?;
+                io::copy(&mut data, &mut io::sink())?;
+            },
+        }
+        let padding = (BLOCK as u64 - size % BLOCK as u64) % BLOCK as u64;
+        io::copy(&mut (&mut input).take(padding), &mut io::sink())?;
+    }
+}
+
+fn normalize(path: &str) -> String {
+    path.trim_start_matches("./").trim_start_matches('/').trim_end_matches('/').to_string()
+}
+
+fn remove_tree(files: &mut BTreeMap<String, Entry>, dir: &str) {
+    let prefix = format!("{}/", dir);
+    files.retain(|path, _| path != dir && !path.starts_with(&prefix));
+}
+
+// stacks the layers of an image, lowest first, into the filesystem a container would see.
+// .wh.NAME deletes NAME from the layers below and .wh..wh..opq empties its directory
+fn flatten(layers: Vec<Vec<Entry>>) -> BTreeMap<String, Entry> {
+    let mut files = BTreeMap::new();
+    for layer in layers {
+        let mut added = Vec::new();
+        for mut entry in layer {
+            entry.path = normalize(&entry.path);
+            let (dir, name) = match entry.path.rsplit_once('/') {
+                Some((dir, name)) => (dir.to_string(), name.to_string()),
+                None => (String::new(), entry.path.clone()),
+            };
+            let join = |name: &str| if dir.is_empty() { name.to_string() } else { format!("{}/{}", dir, name) };
+            if name == ".wh..wh..opq" {
+                let prefix = if dir.is_empty() { String::new() } else { format!("{}/", dir) };
+                files.retain(|path: &String, _| !path.starts_with(&prefix));
+            } else if let Some(deleted) = name.strip_prefix(".wh.") {
+                remove_tree(&mut files, &join(deleted));
+            } else if !entry.path.is_empty() {
+                added.push(entry);
+            }
+        }
+        for entry in added {
+            files.insert(entry.path.clone(), entry);
+        }
+    }
+    files
+}
+
+// the Layers of the first image in a `docker save` manifest.json, lowest first
+fn manifest_layers(manifest: &str) -> Vec<String> {
+    let Some(start) = manifest.find("\"Layers\"") else {
+        return Vec::new();
+    };
+    let rest = &manifest[start + "\"Layers\"".len()..];
+    let Some(open) = rest.find('[') else {
+        return Vec::new();
+    };
+    let list = &rest[open + 1..rest.find(']').unwrap_or(rest.len())];
+    list.split(',')
+        .map(|layer| layer.trim().trim_matches('"').to_string())
+        .filter(|layer| !layer.is_empty())
+        .collect()
+}
+
+// the files of a local docker or oci image, read from `docker save` without unpacking anything
+// to disk. only the layers' file listings are kept in memory, never their contents
+fn read_image(reference: &str) -> Result<BTreeMap<String, Entry>, String> {
+    let mut child = temp::for_child(&mut Command::new("docker"))
+        .arg("save")
+        .arg(reference)
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run docker: {}", e))?;
+    let stdout = child.stdout.take().ok_or("could not read docker output")?;
+
+    let mut manifest = String::new();
+    let mut layers: HashMap<String, Vec<Entry>> = HashMap::new();
+    let mut compressed = HashSet::new();
+    // layers are found by what they start with rather than by name, since older docker calls them
+    // ID/layer.tar and newer ones store them as oci blobs next to the configs
+    let read = read_tar(stdout, |entry, data| {
+        if entry.path == "manifest.json" {
+            data.read_to_string(&mut manifest)?;
+            return Ok(());
+        }
+        if entry.kind != b'0' {
+            return Ok(());
+        }
+        let mut start = Vec::with_capacity(BLOCK);
+        (&mut *data).take(BLOCK as u64).read_to_end(&mut start)?;
+        if start.len() == BLOCK && &start[257..262] == b"ustar" {
+            let mut entries = Vec::new();
+            read_tar(Cursor::new(start).chain(data), |entry, _| {
+                entries.push(entry.clone());
+                Ok(())
+            })?;
+            layers.insert(entry.path.clone(), entries);
+        } else if start.starts_with(&[0x1f, 0x8b]) || start.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            compressed.insert(entry.path.clone());
+        }
+        Ok(())
+    });
+    let status = child.wait().map_err(|e| format!("could not run docker: {}", e))?;
+    if !status.success() {
+        return Err(format!("docker save {} failed", reference));
+    }
+    read.map_err(|e| format!("could not read docker save output: {}", e))?;
+
+    let order = manifest_layers(&manifest);
+    if order.is_empty() {
+        return Err(String::from("no layers in the manifest.json written by docker save"));
+    }
+    let mut stack = Vec::new();
+    for name in order {
+        match layers.remove(&name) {
+            Some(entries) => {
+                stack.push(entries);
+            },
+            None if compressed.contains(&name) => {
+                return Err(format!("layer {} is compressed, which isn't supported", name));
+            },
+            None => {
+                return Err(format!("layer {} is missing from the docker save output", name));
+            },
+        }
+    }
+    Ok(flatten(stack))
+}
+
+// an entry of an image or archive under root as the walker would have found it in a directory,
+// with the entry's own directories counted on top of depth. min and max depth, --hidden,
+// --exclude-dir and the name prefilter apply, the options about the real filesystem don't
+pub(crate) fn member(root: &Path, depth: usize, entry: &Entry, options: &WalkOptions) -> Option<MyFile> {
+    let path = normalize(&entry.path);
+    if entry.kind == b'5' || path.is_empty() {
+        return None;
+    }
+    let parts: Vec<&str> = path.split('/').collect();
+    let name = parts[parts.len() - 1];
+    let depth = depth + parts.len();
+    if depth < options.min_depth || options.max_depth.is_some_and(|max| depth > max) {
+        return None;
+    }
+    if !options.hidden && parts.iter().any(|part| part.starts_with('.')) {
+        return None;
+    }
+    if parts[..parts.len() - 1].iter().any(|dir| options.exclude_dirs.iter().any(|regex| regex.is_match(dir))) {
+        return None;
+    }
+    if options.names.as_ref().is_some_and(|names| !names.iter().any(|regex| regex.is_match(name))) {
+        return None;
+    }
+    Some(MyFile {
+        path: root.join(&path),
+        size_bytes: if entry.kind == b'0' { entry.size } else { 0 },
+        modified: entry.modified,
+        depth,
+        is_symlink: entry.kind == b'2',
+        ..Default::default()
+    })
+}
+
+// the files of an image as the walker would have found them in a directory
+pub fn list(reference: &str, options: &WalkOptions) -> Result<Vec<MyFile>, String> {
+    let root = PathBuf::from(format!("{}{}", SCHEME, reference));
+    Ok(read_image(reference)?.values().filter_map(|entry| member(&root, 0, entry, options)).collect())
+}
+
+#[cfg(test)]
+fn tar(entries: &[(&str, u8, &[u8])]) -> Vec<u8> {
+    let mut tar = Vec::new();
+    for (path, kind, data) in entries {
+        let mut header = [0u8; BLOCK];
+        header[..path.len()].copy_from_slice(path.as_bytes());
+        header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+        header[136..147].copy_from_slice(format!("{:011o}", 1704067200).as_bytes());
+        header[156] = *kind;
+        header[257..263].copy_from_slice(b"ustar\0");
+        tar.extend_from_slice(&header);
+        tar.extend_from_slice(data);
+        tar.resize(tar.len().div_ceil(BLOCK) * BLOCK, 0);
+    }
+    tar.resize(tar.len() + 2 * BLOCK, 0);
+    tar
+}
+
+#[test]
+fn test_read_image_layers() {
+    let long = format!("usr/share/{}.txt", "x".repeat(120));
+    let pax = format!("{} path={}\n", long.len() + 11 + 1, long);
+    let lower = tar(&[("etc/", b'5', b""),
+                      ("etc/passwd", b'0', b"root:x:0:0"),
+                      ("tmp/cache/a", b'0', b"aaaa"),
+                      ("tmp/cache/b", b'0', b"bb"),
+                      ("bin/sh", b'2', b""),
+                      ("./var/log/old.log", b'0', b"log")]);
+    let upper = tar(&[("PaxHeaders/long", b'x', pax.as_bytes()),
+                      ("ignored", b'0', b"long name"),
+                      ("tmp/cache/.wh..wh..opq", b'0', b""),
+                      ("tmp/cache/c", b'0', b"c"),
+                      ("var/.wh.log", b'0', b""),
+                      ("etc/passwd", b'0', b"root:x:0:0:root")]);
+
+    let mut entries = Vec::new();
+    read_tar(Cursor::new(&upper), |entry, data| {
+        let mut contents = String::new();
+        data.read_to_string(&mut contents)?;
+        entries.push((entry.clone(), contents));
+        Ok(())
+    }).unwrap();
+    assert_eq!(entries[0].0, Entry { path: long.clone(), size: 9, modified: 1704067200, kind: b'0' });
+    assert_eq!(entries[0].1, "long name");
+    assert_eq!(entries.len(), 5);
+
+    let read = |tar: &[u8]| {
+        let mut entries = Vec::new();
+        read_tar(Cursor::new(tar), |entry, _| {
+            entries.push(entry.clone());
+            Ok(())
+        }).unwrap();
+        entries
+    };
+    let files = flatten(vec![read(&lower), read(&upper)]);
+    let paths: Vec<&str> = files.keys().map(|p| p.as_str()).collect();
+    assert_eq!(paths, vec!["bin/sh", "etc", "etc/passwd", "tmp/cache/c", long.as_str()]);
+    assert_eq!(files["etc/passwd"].size, 15);
+
+    let manifest = r#"[{"Config":"blobs/sha256/c0","RepoTags":["alpine:3.19"],"Layers":["blobs/sha256/aa","blobs/sha256/bb"]}]"#;
+    assert_eq!(manifest_layers(manifest), vec!["blobs/sha256/aa", "blobs/sha256/bb"]);
+    assert_eq!(reference(Path::new("docker://alpine:3.19")), Some("alpine:3.19"));
+    assert_eq!(reference(Path::new("/var/lib/docker")), None);
+}
+
+#[test]
+fn test_tar_edge_cases() {
+    // sizes past what 11 octal digits hold are stored as binary, and padding around octal is allowed
+    let mut binary = [0u8; 12];
+    binary[0] = 0x80;
+    binary[7..].copy_from_slice(&[2, 0, 0, 0, 1]);
+    assert_eq!(number(&binary), (2 << 32) + 1);
+    assert_eq!(number(b" 0000755 \0"), 0o755);
+    assert_eq!(number(b"\0\0\0"), 0);
+    assert_eq!(number(b"zzz"), 0);
+
+    let mut entry = Entry { path: String::from("a"), size: 1, modified: 5, kind: b'0' };
+    apply_pax("junk\n20 mtime=1700000000.25\n12 size=oops\n13 uid=1000\n", &mut entry);
+    assert_eq!(entry, Entry { path: String::from("a"), size: 1, modified: 1_700_000_000, kind: b'0' });
+
+    // gnu long names, the ustar prefix field, and an archive that just stops without end blocks
+    let long = format!("{}/file", "d".repeat(200));
+    let mut archive = tar(&[("././@LongLink", b'L', format!("{}\0", long).as_bytes()),
+                            ("short", b'0', b"x"),
+                            ("global", b'g', b"ignored=1\n"),
+                            ("name", 0, b"")]);
+    archive[6 * BLOCK + 345..6 * BLOCK + 351].copy_from_slice(b"prefix");
+    archive.truncate(7 * BLOCK);
+    let mut entries = Vec::new();
+    read_tar(Cursor::new(&archive), |entry, _| {
+        entries.push(entry.clone());
+        Ok(())
+    }).unwrap();
+    let paths: Vec<(&str, u8)> = entries.iter().map(|e| (e.path.as_str(), e.kind)).collect();
+    assert_eq!(paths, vec![(long.as_str(), b'0'), ("prefix/name", b'0')]);
+    assert!(read_tar(Cursor::new(&[]), |_, _| unreachable!()).is_ok());
+    // an error from the callback stops the read
+    assert!(read_tar(Cursor::new(&archive), |_, _| Err(io::Error::other("stop"))).is_err());
+}
+
+#[test]
+fn test_flatten_edge_cases() {
+    let entry = |path: &str| Entry { path: path.to_string(), size: 0, modified: 0, kind: b'0' };
+    let lower = vec![entry("a/x"), entry("ab/y"), entry("/root.txt"), entry("b/z")];
+    // a whiteout only removes its own tree, and a layer can delete and re-add the same path
+    let upper = vec![entry("b/z"), entry(".wh.a"), entry("./.wh.root.txt"), entry("b/.wh.z"), entry("./")];
+    let files = flatten(vec![lower.clone(), upper]);
+    assert_eq!(files.keys().collect::<Vec<_>>(), vec!["ab/y", "b/z"]);
+    // an opaque directory at the root empties everything below it
+    let files = flatten(vec![lower, vec![entry(".wh..wh..opq"), entry("new")]]);
+    assert_eq!(files.keys().collect::<Vec<_>>(), vec!["new"]);
+    assert!(flatten(Vec::new()).is_empty());
+
+    assert!(manifest_layers("").is_empty());
+    assert!(manifest_layers(r#"[{"Layers":[]}]"#).is_empty());
+    assert!(manifest_layers(r#"[{"Layers":null}]"#).is_empty());
+    // only the first image's layers are used
+    assert_eq!(manifest_layers(r#"[{"Layers":[ "a" ]},{"Layers":["b"]}]"#), vec!["a"]);
+    assert_eq!(reference(Path::new("docker://")), None);
+    assert_eq!(reference(Path::new("./docker://x")), None);
+}
+
+#[test]
+fn test_member() {
+    let entry = |path: &str, kind: u8| Entry { path: path.to_string(), size: 7, modified: 9, kind };
+    let root = Path::new("docker://x");
+    let options = WalkOptions { threads: 1, ..Default::default() };
+    let file = member(root, 1, &entry("./usr/bin/env", b'0'), &options).unwrap();
+    assert_eq!((file.path, file.size_bytes, file.modified, file.depth), (PathBuf::from("docker://x/usr/bin/env"), 7, 9, 4));
+    // links take no space of their own, and directories come from the paths below them
+    let link = member(root, 0, &entry("bin/sh", b'2'), &options).unwrap();
+    assert!(link.is_symlink && link.size_bytes == 0);
+    assert!(member(root, 0, &entry("usr/", b'5'), &options).is_none());
+    assert!(member(root, 0, &entry("/", b'0'), &options).is_none());
+
+    assert!(member(root, 0, &entry("home/.profile", b'0'), &options).is_none());
+    assert!(member(root, 0, &entry(".config/app", b'0'), &options).is_none());
+    let hidden = WalkOptions { hidden: true, ..options.clone() };
+    assert!(member(root, 0, &entry(".config/app", b'0'), &hidden).is_some());
+
+    let depth = WalkOptions { min_depth: 2, max_depth: Some(2), ..options.clone() };
+    assert!(member(root, 0, &entry("a", b'0'), &depth).is_none());
+    assert!(member(root, 0, &entry("a/b", b'0'), &depth).is_some());
+    assert!(member(root, 0, &entry("a/b/c", b'0'), &depth).is_none());
+
+    // excluded directories are matched by name, the names prefilter only by the file's own name
+    let filtered = WalkOptions {
+        exclude_dirs: vec![regex::Regex::new("^cache$").unwrap()],
+        names: Some(vec![regex::Regex::new(r"\.log$").unwrap()]),
+        ..options
+    };
+    assert!(member(root, 0, &entry("var/cache/x.log", b'0'), &filtered).is_none());
+    assert!(member(root, 0, &entry("var/log/x.log", b'0'), &filtered).is_some());
+    assert!(member(root, 0, &entry("var/cache", b'0'), &filtered).is_none());
+    assert!(member(root, 0, &entry("x.log/y", b'0'), &filtered).is_none());
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::path::Component;
+
+use crate::format::Format;
+use crate::time::SECS_PER_DAY;
+use crate::MyFile;
+
+const CACHE_DIRS: [&str; 10] = ["cache", ".cache", "caches", "tmp", "temp", ".tmp", "__pycache__",
+                                 "node_modules", ".trash", ".gradle"];
+const JUNK_EXTENSIONS: [&str; 14] = ["tmp", "temp", "bak", "old", "orig", "swp", "swo", "pyc", "pyo",
+                                      "log", "o", "class", "part", "crdownload"];
+
+// how safe a file looks to delete, 0 to 100. every signal is only a hint, so none of them alone
+// gets past 30 and the order matters more than the exact numbers
+pub fn score(file: &MyFile, now: i64, duplicated: bool) -> u32 {
+    let mut score = 0;
+
+    let age_days = (now - file.modified) / SECS_PER_DAY;
+    score += match age_days {
+        d if d > 365 => 30,
+        d if d > 90 => 20,
+        d if d > 30 => 10,
+        _ => 0,
+    };
+
+    let in_cache_dir = file.path.parent().into_iter()
+        .flat_map(|p| p.components())
+        .any(|c| match c {
+            Component::Normal(name) => {
+                CACHE_DIRS.contains(&name.to_string_lossy().to_lowercase().as_str())
+            },
+            _ => {
+                false
+            },
+        });
+    if in_cache_dir {
+        score += 30;
+    }
+
+    let extension = file.path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    if file.name().ends_with('~') || extension.is_some_and(|e| JUNK_EXTENSIONS.contains(&e.as_str())) {
+        score += 25;
+    }
+
+    if duplicated {
+        score += 15;
+    }
+    score
+}
+
+// files with the same name and size among the matches are likely copies of each other
+pub fn rank<'a>(files: &[&'a MyFile], now: i64) -> Vec<(u32, &'a MyFile)> {
+    let mut copies: HashMap<(&str, u64), usize> = HashMap::new();
+    for file in files {
+        *copies.entry((file.name(), file.size_bytes)).or_insert(0) += 1;
+    }
+    let mut ranked: Vec<(u32, &MyFile)> = files.iter()
+        .map(|f| (score(f, now, copies[&(f.name(), f.size_bytes)] > 1), *f))
+        .collect();
+    ranked.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.path.cmp(&b.1.path)));
+    ranked
+}
+
+// puts the score in front of a text line, or as the first field of a json record
+pub fn annotate(line: &str, format: Format, score: u32) -> String {
+    match format {
+        Format::Text => {
+            format!("{:>3} {}", score, line)
+        },
+        Format::Json => {
+            format!("{{\"junk_score\":{},{}", score, &line[1..])
+        },
+    }
+}
+
+#[test]
+fn test_rank() {
+    use std::path::PathBuf;
+
+    let now = 1_700_000_000;
+    let fresh = MyFile {
+        path: PathBuf::from("src/main.rs"),
+        modified: now,
+        ..Default::default()
+    };
+    let old_backup = MyFile {
+        path: PathBuf::from("notes.txt.bak"),
+        modified: now - 400 * SECS_PER_DAY,
+        ..Default::default()
+    };
+    let cached = MyFile {
+        path: PathBuf::from("home/.cache/thumbs/a.png"),
+        size_bytes: 10,
+        modified: now - 40 * SECS_PER_DAY,
+        ..Default::default()
+    };
+    let copy = MyFile {
+        path: PathBuf::from("downloads/a.png"),
+        size_bytes: 10,
+        modified: now,
+        ..Default::default()
+    };
+    let files = vec![&fresh, &old_backup, &cached, &copy];
+
+    let ranked: Vec<(u32, &PathBuf)> = rank(&files, now).into_iter().map(|(s, f)| (s, &f.path)).collect();
+    assert_eq!(ranked, vec![(55, &cached.path), (55, &old_backup.path), (15, &copy.path), (0, &fresh.path)]);
+
+    assert_eq!(annotate("a.png", Format::Text, 5), "  5 a.png");
+    assert_eq!(annotate("{\"path\":\"a.png\"}", Format::Json, 5), "{\"junk_score\":5,\"path\":\"a.png\"}");
+}
+
+#[test]
+fn test_score() {
+    use std::path::PathBuf;
+
+    let now = 1_700_000_000;
+    let file = |path: &str, age_days: i64| MyFile {
+        path: PathBuf::from(path),
+        modified: now - age_days * SECS_PER_DAY,
+        ..Default::default()
+    };
+    let score = |path: &str, age_days: i64| score(&file(path, age_days), now, false);
+
+    // age only counts once a threshold is passed, and a clock skewed into the future is fresh
+    assert_eq!(score("a.txt", 30), 0);
+    assert_eq!(score("a.txt", 31), 10);
+    assert_eq!(score("a.txt", 91), 20);
+    assert_eq!(score("a.txt", 366), 30);
+    assert_eq!(score("a.txt", -10), 0);
+
+    // any directory above the file is a location hint, whatever its case, but not the file's own
+    // name or a directory that only starts like a cache one
+    assert_eq!(score("Library/Caches/com.app/data", 0), 30);
+    assert_eq!(score("/deep/node_modules/pkg/lib/index.js", 0), 30);
+    assert_eq!(score("project/cache", 0), 0);
+    assert_eq!(score("etc/cache.d/conf", 0), 0);
+    assert_eq!(score("cache", 0), 0);
+    // a cache directory and a junk extension add up, once each
+    assert_eq!(score("tmp/.cache/tmp/x.TMP", 0), 55);
+
+    assert_eq!(score("notes.txt~", 0), 25);
+    assert_eq!(score("crash.LOG", 0), 25);
+    assert_eq!(score("main.rs.orig", 0), 25);
+    assert_eq!(score("catalog", 0), 0);
+
+    // every signal at once is as high as it goes
+    assert_eq!(self::score(&file("tmp/old.bak", 400), now, true), 100);
+}
+
+#[test]
+fn test_rank_edge_cases() {
+    use std::path::PathBuf;
+
+    let now = 1_700_000_000;
+    let file = |path: &str, size_bytes: u64| MyFile {
+        path: PathBuf::from(path),
+        size_bytes,
+        modified: now,
+        ..Default::default()
+    };
+    let (a, b, c, d) = (file("x/report.pdf", 10), file("y/report.pdf", 10), file("z/report.pdf", 11), file("w/other.pdf", 10));
+    let paths = |files: &[&MyFile]| -> Vec<(u32, String)> {
+        rank(files, now).into_iter().map(|(s, f)| (s, f.path.to_string_lossy().into_owned())).collect()
+    };
+    // copies need the same name and the same size. equal scores fall back to path order, whatever
+    // order the files came in
+    assert_eq!(paths(&[&d, &c, &b, &a]),
+               vec![(15, String::from("x/report.pdf")),
+                    (15, String::from("y/report.pdf")),
+                    (0, String::from("w/other.pdf")),
+                    (0, String::from("z/report.pdf"))]);
+    // a file isn't a copy of itself
+    assert_eq!(paths(&[&a]), vec![(0, String::from("x/report.pdf"))]);
+    assert!(rank(&[], now).is_empty());
+}
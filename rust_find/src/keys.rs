@@ -0,0 +1,147 @@
+#[cfg(unix)]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::{Arc, OnceLock};
+#[cfg(unix)]
+use std::thread::JoinHandle;
+
+#[cfg(unix)]
+use crate::status;
+
+// what a key pressed during a scan asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(unix), allow(dead_code))]
+enum Action {
+    Pause,
+    Resume,
+    Status,
+}
+
+// p pauses the scan, r resumes it and s prints where it is, like `kill -USR1` does. other keys do
+// nothing
+#[cfg_attr(not(unix), allow(dead_code))]
+fn action(key: u8) -> Option<Action> {
+    match key {
+        b'p' => Some(Action::Pause),
+        b'r' => Some(Action::Resume),
+        b's' => Some(Action::Status),
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+fn run(action: Action) {
+    match action {
+        Action::Pause => {
+            status::pause();
+        },
+        Action::Resume => {
+            status::resume();
+        },
+        Action::Status => {
+            status::show_status();
+        },
+    }
+}
+
+// the terminal settings from before listen changed them
+#[cfg(unix)]
+static SAVED: OnceLock<libc::termios> = OnceLock::new();
+
+#[cfg(unix)]
+extern "C" fn restore_terminal() {
+    if let Some(saved) = SAVED.get() {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved);
+        }
+    }
+}
+
+// keys are listened for while this is alive. dropping it stops the reader and sets the terminal
+// back, so whatever runs after the scan (an --exec'd editor, say) gets it as it was
+#[cfg(unix)]
+pub struct Listening {
+    stop: Arc<AtomicBool>,
+    reader: Option<JoinHandle<()>>,
+}
+
+#[cfg(unix)]
+impl Drop for Listening {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+        restore_terminal();
+    }
+}
+
+#[cfg(not(unix))]
+pub struct Listening;
+
+// there is no terminal to set back
+#[cfg(not(unix))]
+impl Drop for Listening {
+    fn drop(&mut self) {}
+}
+
+// with a terminal on stdin, keys take effect as they are pressed and aren't echoed. ctrl-c still
+// works. exits that skip the guard (ctrl-c, --strict) set the terminal back at exit instead
+#[cfg(unix)]
+pub fn listen() -> Option<Listening> {
+    if unsafe { libc::isatty(libc::STDIN_FILENO) } == 0 {
+        return None;
+    }
+    let mut term: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut term) } != 0 || SAVED.set(term).is_err() {
+        return None;
+    }
+    term.c_lflag &= !(libc::ICANON | libc::ECHO);
+    term.c_cc[libc::VMIN] = 1;
+    term.c_cc[libc::VTIME] = 0;
+    unsafe {
+        libc::atexit(restore_terminal);
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &term);
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&stop);
+    // stdin is polled rather than read, so the reader notices it should stop instead of taking
+    // the next key from whatever reads the terminal after the scan
+    let reader = std::thread::spawn(move || {
+        while !stopped.load(Ordering::Relaxed) {
+            let mut poll = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+            match unsafe { libc::poll(&mut poll, 1, 100) } {
+                0 => {
+                    continue;
+                },
+                n if n < 0 => {
+                    break;
+                },
+                _ => {},
+            }
+            let mut key = 0u8;
+            if unsafe { libc::read(libc::STDIN_FILENO, (&mut key as *mut u8).cast(), 1) } != 1 {
+                break;
+            }
+            if let Some(action) = action(key) {
+                run(action);
+            }
+        }
+    });
+    Some(Listening { stop, reader: Some(reader) })
+}
+
+#[cfg(not(unix))]
+pub fn listen() -> Option<Listening> {
+    None
+}
+
+#[test]
+fn test_action() {
+    assert_eq!(action(b'p'), Some(Action::Pause));
+    assert_eq!(action(b'r'), Some(Action::Resume));
+    assert_eq!(action(b's'), Some(Action::Status));
+    assert_eq!(action(b'P'), None);
+    assert_eq!(action(b'q'), None);
+    assert_eq!(action(b'\n'), None);
+}
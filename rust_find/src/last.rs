@@ -0,0 +1,155 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::temp;
+
+// the matches of the most recent run, each path ended by a NUL byte, so `rust-find last N` can act
+// on them without scanning again. paths are kept as the raw bytes the os gave, newlines and
+// invalid utf-8 included
+fn cache_file() -> Option<PathBuf> {
+    let cache = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => {
+            PathBuf::from(dir)
+        },
+        _ => {
+            PathBuf::from(std::env::var_os("HOME")?).join(".cache")
+        },
+    };
+    Some(cache.join("rust-find").join("last"))
+}
+
+// writes the cache a path at a time as matches are printed, so a streamed run doesn't have to
+// keep them. the file only replaces the previous run's once it is finished, so an interrupted
+// run leaves `last` as it was
+pub struct Writer {
+    out: BufWriter<File>,
+    partial: PathBuf,
+    path: PathBuf,
+}
+
+impl Writer {
+    pub fn create() -> io::Result<Writer> {
+        Writer::at(cache_file().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?)
+    }
+
+    fn at(path: PathBuf) -> io::Result<Writer> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let partial = temp::partial(&path);
+        Ok(Writer { out: BufWriter::new(File::create(&partial)?), partial, path })
+    }
+
+    pub fn push(&mut self, path: &Path) -> io::Result<()> {
+        self.out.write_all(path.as_os_str().as_encoded_bytes())?;
+        self.out.write_all(b"\0")
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()?;
+        fs::rename(&self.partial, &self.path)
+    }
+}
+
+pub fn save<'a>(paths: impl IntoIterator<Item = &'a Path>) -> io::Result<()> {
+    let mut writer = Writer::create()?;
+    for path in paths {
+        writer.push(path)?;
+    }
+    writer.finish()
+}
+
+pub fn load() -> io::Result<Vec<PathBuf>> {
+    read(&cache_file().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?)
+}
+
+fn read(path: &Path) -> io::Result<Vec<PathBuf>> {
+    Ok(fs::read(path)?
+        .split(|&b| b == 0)
+        .filter(|path| !path.is_empty())
+        .map(path_from_bytes)
+        .collect())
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+// picks results by their 1-based index as printed with --number. a spec is a single index (3) or
+// an inclusive range (2-5). no specs means everything
+pub fn select(paths: Vec<PathBuf>, specs: &[String]) -> Result<Vec<PathBuf>, String> {
+    if specs.is_empty() {
+        return Ok(paths);
+    }
+    let mut selected = Vec::new();
+    for spec in specs {
+        let invalid = || format!("invalid index '{}', expected N or N-M", spec);
+        let (first, last) = match spec.split_once('-') {
+            Some((a, b)) => {
+                (a.parse::<usize>().map_err(|_| invalid())?, b.parse::<usize>().map_err(|_| invalid())?)
+            },
+            None => {
+                let n = spec.parse::<usize>().map_err(|_| invalid())?;
+                (n, n)
+            },
+        };
+        if first == 0 || last < first {
+            return Err(invalid());
+        }
+        if last > paths.len() {
+            return Err(format!("index {} is out of range, the last run had {} results", last, paths.len()));
+        }
+        selected.extend_from_slice(&paths[first - 1..last]);
+    }
+    Ok(selected)
+}
+
+#[test]
+fn test_select() {
+    let paths: Vec<PathBuf> = ["a", "b", "c", "d"].iter().map(PathBuf::from).collect();
+    assert_eq!(select(paths.clone(), &[]).unwrap(), paths);
+    assert_eq!(select(paths.clone(), &["3".to_string()]).unwrap(), vec![PathBuf::from("c")]);
+    assert_eq!(select(paths.clone(), &["1".to_string(), "3-4".to_string()]).unwrap(),
+               vec![PathBuf::from("a"), PathBuf::from("c"), PathBuf::from("d")]);
+    assert!(select(paths.clone(), &["0".to_string()]).is_err());
+    assert!(select(paths.clone(), &["5".to_string()]).is_err());
+    assert!(select(paths.clone(), &["3-2".to_string()]).is_err());
+    assert!(select(paths, &["x".to_string()]).is_err());
+}
+
+#[test]
+fn test_writer() {
+    let dir = temp::TestDir::new("last");
+    let path = dir.join("last");
+    let mut writer = Writer::at(path.clone()).unwrap();
+    writer.push(Path::new("a")).unwrap();
+    writer.push(Path::new("b c\nd")).unwrap();
+    // the previous run's results stay until the new ones are complete
+    assert!(!path.exists());
+    writer.finish().unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"a\0b c\nd\0");
+    assert!(!temp::partial(&path).exists());
+    assert_eq!(read(&path).unwrap(), vec![PathBuf::from("a"), PathBuf::from("b c\nd")]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_non_utf8_paths() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = temp::TestDir::new("last-bytes");
+    let path = dir.join("last");
+    let name = Path::new(std::ffi::OsStr::from_bytes(b"/caf\xe9"));
+    let mut writer = Writer::at(path.clone()).unwrap();
+    writer.push(name).unwrap();
+    writer.finish().unwrap();
+    assert_eq!(read(&path).unwrap(), vec![name.to_path_buf()]);
+}
@@ -1,231 +1,2152 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::fs;
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use structopt::clap::AppSettings;
 use structopt::StructOpt;
 use filesize::PathExt;
 use regex::Regex;
-use colored::Colorize;
+
+mod annotate;
+mod archive;
+mod attrs;
+mod audit;
+mod capabilities;
+mod checkpoint;
+mod chown;
+mod clamd;
+mod collisions;
+mod config;
+mod containers;
+mod crash;
+mod exec;
+mod fds;
+mod format;
+mod git;
+mod glob;
+mod homes;
+mod i18n;
+mod ignore;
+mod image;
+mod junk;
+mod keys;
+mod last;
+mod merge;
+mod mounts;
+mod output;
+mod plan;
+mod queries;
+mod repl;
+mod report;
+mod safety;
+mod sample;
+mod script;
+mod size;
+mod socket;
+mod stats;
+mod status;
+mod temp;
+mod time;
+mod walk;
+#[cfg(feature = "webhook")]
+mod webhook;
+mod xattr;
+
+use attrs::Attr;
+use chown::UidMapping;
+use format::{Format, LineFormat};
+use glob::Glob;
+use report::{Operation, Outcome};
+use size::Size;
+use stats::Stats;
+use time::{Date, DateRange, TimeBound, TimeOfWeek, TimeSpan};
+use walk::{WalkError, WalkOptions};
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "rust-find 0.1.0", about = "a command line utility for searching for files")]
-struct CLI {
-    #[structopt(short, long)]
+// lets options given on the command line replace the same options from a --query or profile
+#[structopt(global_settings = &[AppSettings::AllArgsOverrideSelf])]
+struct Cli {
+    /// Directories to search. docker://IMAGE:TAG lists the files inside a local docker image
+    /// instead, as written by `docker save`
+    #[structopt(short, long, global = true)]
     dirs: Vec<PathBuf>,
-    #[structopt(short, long)]
-    patterns: Option<Vec<String>>,
+    /// Check the paths read from stdin, one per line, instead of walking --dirs. e.g.
+    /// `git ls-files | rust-find --stdin --size-min 1M`
+    #[structopt(long, global = true)]
+    stdin: bool,
+    /// Walk the directories read from stdin, separated by newlines or NUL bytes, e.g.
+    /// `find / -name .git -printf '%h\0' | rust-find --dirs-from-stdin`. `--dirs -` does the same.
+    /// Actions on the results need --force, since the search directories aren't known up front
+    #[structopt(long, global = true, conflicts_with = "stdin")]
+    dirs_from_stdin: bool,
+    /// Every few seconds, save the directories still to be walked to FILE, so an interrupted run
+    /// can be picked up again with --resume. Only for runs that list matches as they are found.
+    /// The file is removed once the walk completes
+    #[structopt(long, global = true, value_name = "FILE", conflicts_with_all = &["stdin", "dirs-from-stdin"])]
+    checkpoint: Option<PathBuf>,
+    /// Continue the walk saved in --checkpoint instead of starting at --dirs, appending to
+    /// --output. Give the same filters and walk options as the interrupted run
+    #[structopt(long, global = true, requires = "checkpoint")]
+    resume: bool,
+    /// Stop at the first directory or file that can't be read, instead of warning and going on.
+    /// Either way the exit code is 1 if anything couldn't be read
+    #[structopt(long, global = true)]
+    strict: bool,
+    /// Don't descend more than N levels below the search roots. 1 only looks at files directly
+    /// inside them, and is the least there is
+    #[structopt(long, global = true, value_name = "N", parse(try_from_str = parse_max_depth))]
+    max_depth: Option<usize>,
+    /// Ignore files less than N levels below the search roots
+    #[structopt(long, global = true, value_name = "N")]
+    min_depth: Option<usize>,
+    /// Descend into symlinked directories. Each directory is only walked once, so links that
+    /// loop back up the tree are safe
+    #[structopt(short = "L", long, global = true)]
+    follow: bool,
+    /// Number of threads reading directories. More than 1 speeds up large trees, but matches come
+    /// out in a different order each run unless --sort is given
+    #[structopt(long, global = true, default_value = "1")]
+    threads: usize,
+    /// With --threads, let at most N directories' worth of results wait to be filtered and
+    /// printed, so memory stays flat when output is slower than the walk. Unbounded by default
+    #[structopt(long, global = true, value_name = "N")]
+    buffer_size: Option<usize>,
+    /// Keep at most about N files open at once while walking. Threads beyond what that allows wait
+    /// their turn. Defaults to what the open file limit (ulimit -n) allows
+    #[structopt(long, global = true, value_name = "N")]
+    max_open_files: Option<usize>,
+    /// Also walk what .gitignore, .ignore and the global git excludes ignore. Without this, those
+    /// are skipped inside git repositories
+    #[structopt(long, global = true)]
+    no_ignore: bool,
+    /// Also walk files and directories whose name starts with a dot
+    #[structopt(long, global = true)]
+    hidden: bool,
+    /// Only walk shard I of N, e.g. 2/4, so N machines or processes can each scan a disjoint part
+    /// of the same tree. Combine their results with `rust-find merge`
+    #[structopt(long, global = true, value_name = "I/N", conflicts_with = "stdin")]
+    shard: Option<walk::Shard>,
+    /// Don't descend into directories with a matching name, e.g. `--exclude-dir node_modules
+    /// --exclude-dir 'build*'`. Takes gitignore-style globs
+    #[structopt(long, global = true, value_name = "GLOB", number_of_values = 1)]
+    exclude_dir: Vec<String>,
+    /// Leave out container storage (e.g. overlayfs layers under /var/lib/docker) and virtual
+    /// machine disk images like .qcow2, which repeat files found elsewhere
+    #[structopt(long, global = true)]
+    skip_container_storage: bool,
+    /// Stay on the filesystem of each search root, so scanning / doesn't wander into /proc,
+    /// network mounts or external drives (unix only)
+    #[structopt(short = "x", long, global = true)]
+    one_file_system: bool,
+    /// Walk filesystems of this type (as listed in /proc/mounts), or all of them. Pseudo
+    /// filesystems like /proc, /sys and /dev/pts and FUSE mounts are skipped otherwise, unless
+    /// they are a search root themselves, since they hold no real files or can hang
+    #[structopt(long, global = true, value_name = "TYPE", number_of_values = 1)]
+    include_fs: Vec<String>,
+    /// Walk directories depth-first (dfs) or level by level (bfs)
+    #[structopt(long, global = true, default_value = "dfs", value_name = "ORDER")]
+    traversal: walk::Traversal,
+    /// Read each directory in name order, so the output is the same on every run. Only
+    /// deterministic with --threads 1
+    #[structopt(long, global = true)]
+    sorted: bool,
+    /// Also list the files inside .zip, .tar and .tar.gz archives, as paths like
+    /// backup.zip!/docs/report.pdf, so name and size filters apply to them. Listing .tar.gz
+    /// needs gzip
+    #[structopt(long, global = true)]
+    search_archives: bool,
+
+    #[structopt(flatten)]
+    filters: Filters,
 
-    #[structopt(long)]
-    size_min: Option<u64>,
-    #[structopt(long)]
-    size_max: Option<u64>,
+    /// Sort matches by path, size or modified time
+    #[structopt(long, global = true)]
+    sort: Option<SortKey>,
+    /// Reverse the sort order, e.g. largest first with --sort size
+    #[structopt(long, global = true)]
+    reverse: bool,
+    /// Score matches by how likely they are safe to delete (age, cache/temp location, extension,
+    /// duplicates) and list the most deletable first. Replaces --sort. Implies --hidden
+    #[structopt(long, global = true, conflicts_with = "sort")]
+    score_junk: bool,
+    /// Only keep the first N matches (after sorting)
+    #[structopt(long, global = true)]
+    limit: Option<usize>,
+    /// Only keep the first N matches from each directory, so a cache with 500k matching files
+    /// doesn't drown out everything else. With no filters but -p, the rest of a directory's files
+    /// aren't even read
+    #[structopt(long, global = true, value_name = "N")]
+    max_per_dir: Option<usize>,
+    /// Keep a uniformly random sample of N matches, in the order they were found, e.g. to
+    /// spot-check a huge result set
+    #[structopt(long, global = true, value_name = "N")]
+    sample: Option<usize>,
+    /// Seed for --sample, so the same files are picked on every run
+    #[structopt(long, global = true, value_name = "S", requires = "sample")]
+    seed: Option<u64>,
 
-    #[structopt(short, long)]
+    #[structopt(short, long, global = true)]
     output: Option<PathBuf>,
+    /// Send matches as json lines to the unix socket at PATH instead of stdout
+    #[structopt(long, global = true, value_name = "PATH", conflicts_with = "output")]
+    output_socket: Option<PathBuf>,
+    /// POST matches as json arrays to this http:// URL instead of printing them
+    #[cfg(feature = "webhook")]
+    #[structopt(long, global = true, value_name = "URL", conflicts_with_all = &["output", "output-socket"])]
+    webhook: Option<webhook::Endpoint>,
+    /// Number of matches per --webhook request
+    #[cfg(feature = "webhook")]
+    #[structopt(long, global = true, default_value = "500")]
+    webhook_batch: usize,
+    /// Output format: text (one path per line) or json (one object per line)
+    #[structopt(long, global = true, default_value = "text")]
+    format: Format,
+    /// With --format json, also output a {"type":"error",...} record for each path that couldn't
+    /// be read, in place of the warning. Other warnings go to stderr with json output
+    #[structopt(long, global = true)]
+    include_errors: bool,
+    /// Version of the json record layout. 2 adds depth, parent, extension and is_symlink to 1, 3
+    /// adds link_target, 4 is_dir and entries. Pin it to keep the output stable across releases
+    #[structopt(long, global = true, default_value = format::LATEST_SCHEMA_VERSION, possible_values = format::SCHEMA_VERSIONS)]
+    schema_version: u32,
+    /// Show chattr attributes after each path
+    #[structopt(long, global = true)]
+    show_attrs: bool,
+    /// Run COMMAND for each match ({} is replaced by the quoted path) and add its output to the
+    /// line or json record, e.g. --annotate-cmd 'file -b {}'
+    #[structopt(long, global = true, value_name = "COMMAND")]
+    annotate_cmd: Option<String>,
+    /// Prefix each match with its index, which `rust-find last` accepts
+    #[structopt(long, global = true)]
+    number: bool,
+
+    /// Instead of listing matches, change their owner from OLD_UID to NEW_UID (requires root)
+    #[structopt(long, global = true, value_name = "OLD_UID:NEW_UID")]
+    chown_map: Option<Vec<UidMapping>>,
+    /// Instead of listing matches, remove the com.apple.quarantine xattr from them (macOS only)
+    #[structopt(long, global = true)]
+    remove_quarantine: bool,
+    /// Instead of listing matches, run COMMAND through sh for each of them, with {} replaced by the
+    /// quoted path (or the path added at the end), e.g. `rust-find last 3 --exec 'vim {}'`. Each
+    /// command also gets RF_PATH, RF_SIZE (bytes on disk) and RF_MTIME (unix seconds) in its
+    /// environment.
+    /// With --dry-run, print the commands instead. Like the actions, refuses more than --max-changes
+    /// matches or matches outside --dirs without --force. Exits with 1 if any command failed
+    #[structopt(long, global = true, value_name = "COMMAND")]
+    exec: Option<String>,
+    /// Instead of listing matches, run COMMAND through sh with as many of them at once as the
+    /// system allows, like xargs. {} is replaced by all their paths (or they are added at the end),
+    /// e.g. --exec-batch 'tar czf logs.tgz {}'. With --dry-run, print the commands instead. Held to
+    /// --max-changes and --dirs like --exec
+    #[structopt(long, global = true, value_name = "COMMAND", conflicts_with = "exec")]
+    exec_batch: Option<String>,
+    /// Pass at most N matches to each --exec-batch command
+    #[structopt(long, global = true, value_name = "N", requires = "exec-batch")]
+    batch_size: Option<usize>,
+    /// Run up to N --exec or --exec-batch commands at once. Their output is printed a line at a
+    /// time, each line behind the path (or batch) it is for, and they get no stdin
+    #[structopt(long, global = true, value_name = "N", default_value = "1")]
+    exec_jobs: usize,
+    /// What to do when an --exec or --exec-batch command fails: continue with the rest, abort
+    /// (start no more commands), or retry=N to run it up to N more times first. Failed paths are
+    /// listed at the end either way
+    #[structopt(long, global = true, value_name = "POLICY", default_value = "continue")]
+    exec_on_error: exec::OnError,
+    /// Instead of listing matches, write a shell script running TEMPLATE for each of them, with {}
+    /// replaced by the quoted path
+    #[structopt(long, global = true, value_name = "TEMPLATE")]
+    emit_script: Option<String>,
+    /// Instead of listing matches, report the ones sharing a directory whose names only differ in
+    /// case or unicode normalization, which can't coexist on macOS or windows
+    #[structopt(long, global = true)]
+    find_name_collisions: bool,
+    /// Instead of listing matches, send each of them to the clamd daemon listening on SOCKET and
+    /// print its verdict. Exits with 1 if anything was infected
+    #[structopt(long, global = true, value_name = "SOCKET")]
+    scan_clamd: Option<PathBuf>,
+    /// Instead of running an action, save what it would change to FILE, for `rust-find apply FILE`
+    /// to carry out later
+    #[structopt(long, global = true, value_name = "FILE")]
+    plan: Option<PathBuf>,
+    /// Append a JSON record of this run (arguments, match count, actions taken) to this file
+    #[structopt(long, global = true)]
+    audit_log: Option<PathBuf>,
+    /// Write a JSON report of every file an action tried to change, and how that went, to this file
+    #[structopt(long, global = true)]
+    action_report: Option<PathBuf>,
+    /// Report what an action would change without changing anything
+    #[structopt(long, global = true)]
+    dry_run: bool,
+    /// Run an action or --exec even if it affects more than --max-changes files or paths outside --dirs
+    #[structopt(long, global = true)]
+    force: bool,
+    /// Number of files an action or --exec may change without --force
+    #[structopt(long, global = true, default_value = "100")]
+    max_changes: usize,
+
+    /// Save the filters and options of this command line under NAME in config.toml, for use with
+    /// --query
+    #[structopt(long, global = true, value_name = "NAME")]
+    save_query: Option<String>,
+    /// Run with the arguments saved under NAME, followed by any given here
+    // expanded by queries::expand_args before parsing, only declared so it shows up in --help
+    #[structopt(long = "query", value_name = "NAME")]
+    _query: Option<String>,
+
+    /// Print a json description of the filters, formats and actions this build supports, then exit
+    #[structopt(long, global = true)]
+    print_capabilities: bool,
+
+    /// Language for warnings (en, es, de). Defaults to LC_ALL, LC_MESSAGES or LANG
+    #[structopt(long, global = true)]
+    lang: Option<i18n::Lang>,
+    /// Print how long the walk and each filter stage took and how many files each rejected, to
+    /// stderr
+    #[structopt(long, global = true)]
+    stats: bool,
+    /// Exit with 1 if any -p pattern matched no files, to catch patterns that went stale. With
+    /// --limit, only the listed matches count
+    #[structopt(long, global = true)]
+    require_matches: bool,
+    /// Don't delete this run's temporary files on exit, and print where they are
+    #[structopt(long, global = true)]
+    keep_temp: bool,
+    /// Disable colored output
+    #[structopt(long, global = true)]
+    no_color: bool,
+    /// Screen-reader friendly output: no colors and summaries written as full sentences
+    #[structopt(long, global = true)]
+    plain: bool,
+    /// Output for scripts that stays the same across versions: one match per line as
+    /// TYPE<tab>SIZE<tab>MODIFIED<tab>PATH, where TYPE is f, d or l, SIZE is the bytes it takes up
+    /// on disk (like du, not ls -l) and MODIFIED is in seconds since the unix epoch. No colors, and
+    /// warnings go to stderr in english
+    #[structopt(long, global = true, conflicts_with_all = &["format", "number", "show-attrs", "annotate-cmd", "score-junk", "plain"])]
+    porcelain: bool,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Path,
+    Size,
+    Modified,
+}
+
+impl std::str::FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(SortKey::Path),
+            "size" => Ok(SortKey::Size),
+            "modified" => Ok(SortKey::Modified),
+            _ => Err(format!("invalid sort key '{}', expected path, size or modified", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryType {
+    File,
+    Dir,
+    Symlink,
+    Executable,
+    Empty,
+}
+
+impl std::str::FromStr for EntryType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "f" | "file" => Ok(EntryType::File),
+            "d" | "dir" | "directory" => Ok(EntryType::Dir),
+            "l" | "symlink" => Ok(EntryType::Symlink),
+            "x" | "executable" => Ok(EntryType::Executable),
+            "e" | "empty" => Ok(EntryType::Empty),
+            _ => Err(format!("invalid type '{}', expected f, d, l, x or e", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for EntryType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let letter = match self {
+            EntryType::File => "f",
+            EntryType::Dir => "d",
+            EntryType::Symlink => "l",
+            EntryType::Executable => "x",
+            EntryType::Empty => "e",
+        };
+        write!(f, "{}", letter)
+    }
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Scan once, then refine filters interactively on the cached results
+    Repl,
+    /// Use the results of the previous run instead of scanning, e.g. `last 3 --exec 'vim {}'`.
+    /// Every run without a subcommand saves its matches for this, to
+    /// $XDG_CACHE_HOME/rust-find/last (or ~/.cache/rust-find/last)
+    Last {
+        /// Indexes as shown by --number, either N or N-M. All results if left out
+        indexes: Vec<String>,
+    },
+    /// Combine results of earlier runs, text or json, into one sorted list without duplicates
+    Merge {
+        /// Files holding the output of earlier runs
+        #[structopt(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+    /// Report how much each home directory takes up, with its largest and oldest huge files, in
+    /// one scan. Scans /home unless --dirs is given. Hidden and ignored files are always counted
+    Homes {
+        /// How many of the largest and of the oldest huge files to list per home
+        #[structopt(long, default_value = "5", value_name = "N")]
+        top: usize,
+        /// Files of at least this size count as huge, e.g. 500M or 2GiB
+        #[structopt(long, default_value = "1G", value_name = "SIZE")]
+        huge: Size,
+    },
+    /// Carry out a plan saved with --plan, skipping files that changed or went away since
+    Apply {
+        /// The file written by --plan
+        file: PathBuf,
+    },
+}
+
+// find's --max-depth 0 is the starting points alone, but the search roots are never listed here,
+// so it could only ever match nothing. refused rather than quietly walking a level anyway
+fn parse_max_depth(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(0) => {
+            Err(String::from("0 would leave only the search roots, which aren't listed themselves. 1 looks at \
+                              files directly inside them"))
+        },
+        Ok(depth) => {
+            Ok(depth)
+        },
+        Err(e) => {
+            Err(format!("invalid depth '{}': {}", s, e))
+        },
+    }
+}
+
+// an inclusive range of depths, either end can be left open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DepthRange {
+    min: usize,
+    max: usize,
+}
+
+impl std::str::FromStr for DepthRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid depth range '{}', expected MIN..MAX, MIN.. or ..MAX", s);
+        let (min, max) = s.split_once("..").ok_or_else(invalid)?;
+        let min = if min.is_empty() { 0 } else { min.parse().map_err(|_| invalid())? };
+        let max = if max.is_empty() { usize::MAX } else { max.parse().map_err(|_| invalid())? };
+        if max < min {
+            return Err(invalid());
+        }
+        Ok(DepthRange { min, max })
+    }
+}
+
+// open ends are left out, as they can be when given
+impl std::fmt::Display for DepthRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.min > 0 {
+            write!(f, "{}", self.min)?;
+        }
+        write!(f, "..")?;
+        if self.max < usize::MAX {
+            write!(f, "{}", self.max)?;
+        }
+        Ok(())
+    }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+// everything that decides whether a file matches. kept apart from the rest of the cli so the
+// same chain can be rerun on one scan (see repl)
+#[derive(Debug, Default, Clone, StructOpt)]
+#[structopt(name = "filters")]
+struct Filters {
+    #[structopt(short, long, global = true)]
+    patterns: Option<Vec<String>>,
+    /// Match files with this extension, ignoring case. Can be given more than once, and can
+    /// span dots, e.g. --ext rs --ext tar.gz
+    #[structopt(long, global = true, value_name = "EXT", number_of_values = 1)]
+    ext: Option<Vec<String>>,
+    /// Match files by shell glob, e.g. -g '*.log'. Globs with a slash match the end of the path
+    /// and ** spans directories, e.g. -g 'src/**/*.rs'. Can be given more than once
+    #[structopt(short, long, global = true, value_name = "GLOB", number_of_values = 1)]
+    glob: Option<Vec<Glob>>,
+
+    /// Match files taking up at least this much disk space, in bytes or with a unit like 500k, 10M
+    /// or 1.5GiB
+    #[structopt(long, global = true)]
+    size_min: Option<Size>,
+    /// Match files taking up at most this much disk space, in bytes or with a unit like 500k, 10M
+    /// or 1.5GiB
+    #[structopt(long, global = true)]
+    size_max: Option<Size>,
+
+    /// Match files exactly N levels below their search root, where 1 is directly inside it
+    #[structopt(long, global = true, value_name = "N", conflicts_with = "depth-range")]
+    depth_exactly: Option<usize>,
+    /// Match files whose depth below their search root is within an inclusive range, e.g. 2..4,
+    /// 3.. or ..2
+    #[structopt(long, global = true, value_name = "MIN..MAX")]
+    depth_range: Option<DepthRange>,
+    /// Match files in directories with at least N entries, to find pathologically large directories
+    #[structopt(long, global = true, value_name = "N")]
+    dir_min_entries: Option<usize>,
+    /// Match files in directories with at most N entries
+    #[structopt(long, global = true, value_name = "N")]
+    dir_max_entries: Option<usize>,
+
+    /// Match files modified within this long before now, e.g. 90m, 24h, 2d, 1w or 5bd (business
+    /// days, monday to friday)
+    #[structopt(long, global = true)]
+    changed_within: Option<TimeSpan>,
+    /// Match files last modified before a day (YYYY-MM-DD), a moment (2024-03-01T17:00, with an
+    /// optional Z or +HH:MM offset) or longer ago than a duration like 2d or 5bd
+    #[structopt(long, global = true)]
+    changed_before: Option<TimeBound>,
+    /// Match files last read within this long before now, e.g. 90m, 24h, 2d, 1w or 5bd
+    #[structopt(long, global = true)]
+    accessed_within: Option<TimeSpan>,
+    /// Match files last read before a day (YYYY-MM-DD), a moment (2024-03-01T17:00+01:00) or longer
+    /// ago than a duration like 2d or 5bd
+    #[structopt(long, global = true)]
+    accessed_before: Option<TimeBound>,
+    /// Match files created within this long before now. Files whose filesystem doesn't record
+    /// creation time never match
+    #[structopt(long, global = true)]
+    created_within: Option<TimeSpan>,
+    /// Match files created before a day (YYYY-MM-DD), a moment (2024-03-01T17:00Z) or longer ago
+    /// than a duration like 2d or 5bd. Files whose filesystem doesn't record creation time never
+    /// match
+    #[structopt(long, global = true)]
+    created_before: Option<TimeBound>,
+    /// Match files last modified within an inclusive range of days, e.g. 2024-01-01..2024-03-31
+    #[structopt(long, global = true)]
+    modified_between: Option<DateRange>,
+    /// Match files last modified on a weekend, a weekday or within an hour range like 18-8
+    #[structopt(long, global = true)]
+    modified_on: Option<Vec<TimeOfWeek>>,
+    /// Match files whose first git commit is before this date (YYYY-MM-DD). Untracked files never match
+    #[structopt(long, global = true)]
+    first_committed_before: Option<Date>,
+    /// Interpret dates, times without an offset and business days in UTC instead of local time
+    #[structopt(long, global = true)]
+    utc: bool,
+
+    /// Match files with any of these chattr attributes set: immutable, append-only, nodump (linux only)
+    #[structopt(long, global = true)]
+    attr: Option<Vec<Attr>>,
+
+    /// Match files with file capabilities set (the security.capability xattr, linux only)
+    #[structopt(long, global = true)]
+    has_capabilities: bool,
+    /// Match files downloaded from the internet (the com.apple.quarantine xattr, macOS only)
+    #[structopt(long, global = true)]
+    quarantined: bool,
+
+    /// Match symlinks whose target doesn't exist, and show where they point
+    #[structopt(long, global = true)]
+    broken_symlinks: bool,
+
+    /// Match only regular files (f), directories (d), symlinks (l), executable files (x) or empty
+    /// files and directories (e). Directories are only listed when asked for
+    #[structopt(short = "t", long = "type", global = true, value_name = "TYPE")]
+    entry_type: Option<EntryType>,
+    /// Match zero-byte files and directories with no entries, for cleanup. The same as -t e
+    #[structopt(long, global = true)]
+    empty: bool,
+    /// Match directories with at least N entries, e.g. 10000 to find bloated folders. Files never
+    /// match
+    #[structopt(long, global = true, value_name = "N")]
+    min_entries: Option<usize>,
+}
+
+impl Filters {
+    // whether directories themselves can match, which the walker then has to yield
+    fn wants_dirs(&self) -> bool {
+        matches!(self.entry_type, Some(EntryType::Dir | EntryType::Empty)) || self.empty || self.min_entries.is_some()
+    }
+
+    // the walk these filters need, from the one the rest of the command line asks for
+    fn walk_options(&self, base: &WalkOptions) -> WalkOptions {
+        WalkOptions { dirs: self.wants_dirs(), ..base.clone() }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
 struct MyFile {
     path: PathBuf,
-    name: String,
     size_bytes: u64,
+    // seconds since the unix epoch
+    modified: i64,
+    accessed: i64,
+    // None when the filesystem doesn't record it
+    created: Option<i64>,
+    // how many directories below its search root the file is, 1 for files directly inside it
+    depth: usize,
+    // entries (files and subdirectories) in the directory holding the file. only known for files
+    // found by the walker, 0 otherwise
+    dir_entries: usize,
+    is_symlink: bool,
+    // where a symlink points, as written in the link
+    link_target: Option<PathBuf>,
+    is_dir: bool,
+    // a file anyone may run. directories and symlinks never are
+    is_executable: bool,
+    // a file with no content, however much space it takes up. for directories, see entries
+    is_empty: bool,
+    // for directories, how many entries they hold. only known for directories found by the walker
+    entries: usize,
+}
+
+#[cfg(unix)]
+fn is_executable(_path: &Path, metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+}
+
+// without permission bits, it's what the extension says
+#[cfg(not(unix))]
+fn is_executable(path: &Path, metadata: &fs::Metadata) -> bool {
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    metadata.is_file() && ["exe", "com", "bat", "cmd"].contains(&extension.as_str())
 }
 
 impl MyFile {
-    fn from_path(p: &PathBuf) -> Option<Self> {
-        let path = p.clone();
-        let name = String::from(path.file_name()?.to_str()?);
-        let size = path.as_path().size_on_disk().ok()?;
-        Some(MyFile {
-            path: path,
-            name: name,
-            size_bytes: size,
+    // the last component of the path. it is read from the path rather than kept in a string of its
+    // own, so a file costs no allocation beyond its path
+    fn name(&self) -> &str {
+        self.path.file_name().and_then(|n| n.to_str()).unwrap_or("")
+    }
+
+    // takes ownership of the path so the walker doesn't have to clone it for every entry. the path
+    // is handed back on failure so the caller can still report it
+    fn from_path(path: PathBuf) -> Result<Self, PathBuf> {
+        if path.file_name().and_then(|n| n.to_str()).is_none() {
+            return Err(path);
+        }
+        let metadata = match fs::symlink_metadata(&path) {
+            Ok(m) => {
+                m
+            },
+            Err(_) => {
+                return Err(path);
+            },
+        };
+        let size_bytes = match path.size_on_disk_fast(&metadata) {
+            Ok(s) => {
+                s
+            },
+            Err(_) => {
+                return Err(path);
+            },
+        };
+        let is_symlink = metadata.file_type().is_symlink();
+        let link_target = if is_symlink { fs::read_link(&path).ok() } else { None };
+        let is_executable = is_executable(&path, &metadata);
+        Ok(MyFile {
+            path,
+            size_bytes,
+            modified: time::modified(&metadata),
+            accessed: time::accessed(&metadata),
+            created: time::created(&metadata),
+            depth: 0,
+            dir_entries: 0,
+            is_symlink,
+            link_target,
+            is_dir: metadata.is_dir(),
+            is_executable,
+            is_empty: metadata.is_file() && metadata.len() == 0,
+            entries: 0,
+        })
+    }
+}
+
+// what an action did to the matched files
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct ActionSummary {
+    changed: usize,
+    unchanged: usize,
+    failed: usize,
+}
+
+fn filter_files_regex<'a>(files: &[&'a MyFile], patterns: &[String]) -> Vec<&'a MyFile> {
+    let regexes = compile_patterns(patterns);
+    // below this many regex runs, starting threads costs more than it saves
+    const PARALLEL_MATCH_WORK: usize = 200_000;
+    let threads = if files.len() * regexes.len() >= PARALLEL_MATCH_WORK {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    } else {
+        1
+    };
+    match_names(files, &regexes, threads)
+}
+
+// the patterns compiled once up front, so an invalid one is reported once, and the filters with
+// only the valid ones left, so the walk's name prefilter and the name filter agree on what matches
+fn compiled_patterns(filters: &Filters) -> (Option<Vec<Regex>>, Filters) {
+    let regexes = filters.patterns.as_deref().map(compile_patterns);
+    let filters = Filters {
+        patterns: regexes.as_ref().map(|r| r.iter().map(|r| r.as_str().to_string()).collect()),
+        ..filters.clone()
+    };
+    (regexes, filters)
+}
+
+// invalid patterns are warned about and left out
+fn compile_patterns(patterns: &[String]) -> Vec<Regex> {
+    let mut regexes = Vec::new();
+    for pattern in patterns {
+        let regex = match Regex::new(pattern) {
+            Ok(r) => {
+                r
+            },
+            Err(e) => {
+                output::warn("invalid regex", e);
+                output::note("skipping regex match", pattern);
+                continue;
+            },
+        };
+        regexes.push(regex);
+    }
+    regexes
+}
+
+// splits the files into one contiguous chunk per thread, so the result keeps the input order
+fn match_names<'a>(files: &[&'a MyFile], regexes: &[Regex], threads: usize) -> Vec<&'a MyFile> {
+    let matches = |chunk: &[&'a MyFile]| -> Vec<&'a MyFile> {
+        chunk.iter()
+            .filter(|&&file| {
+                regexes.iter().any(|regex| regex.is_match(file.name()))
+            })
+            .cloned()
+            .collect()
+    };
+    if threads <= 1 || files.len() < 2 {
+        return matches(files);
+    }
+
+    let chunk_size = files.len().div_ceil(threads);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = files.chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || matches(chunk)))
+            .collect();
+        workers.into_iter()
+            .flat_map(|w| w.join().expect("name matching thread panicked"))
+            .collect()
+    })
+}
+
+// a leading dot is optional in the extensions. a name that is only the extension, like .gz, is
+// a hidden file without one
+fn filter_files_ext<'a>(files: &[&'a MyFile], extensions: &[String]) -> Vec<&'a MyFile> {
+    let suffixes: Vec<String> = extensions.iter()
+        .map(|ext| format!(".{}", ext.trim_start_matches('.').to_lowercase()))
+        .collect();
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            let name = file.name().to_lowercase();
+            suffixes.iter().any(|suffix| name.len() > suffix.len() && name.ends_with(suffix.as_str()))
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+fn filter_files_glob<'a>(files: &[&'a MyFile], globs: &[Glob]) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            globs.iter().any(|glob| glob.is_match(file))
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+fn filter_files_size_min<'a>(files: &[&'a MyFile], min_size: &u64) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            file.size_bytes >= *min_size
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+fn filter_files_size_max<'a>(files: &[&'a MyFile], max_size: &u64) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            file.size_bytes <= *max_size
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+// start is inclusive, end is exclusive
+fn filter_files_modified_between<'a>(files: &[&'a MyFile], start: i64, end: i64) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            file.modified >= start && file.modified < end
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+fn filter_files_depth<'a>(files: &[&'a MyFile], min: usize, max: usize) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            file.depth >= min && file.depth <= max
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+fn filter_files_dir_entries<'a>(files: &[&'a MyFile], min: usize, max: usize) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            file.dir_entries >= min && file.dir_entries <= max
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+fn filter_files_changed_within<'a>(files: &[&'a MyFile], since: i64) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            file.modified >= since
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+fn filter_files_changed_before<'a>(files: &[&'a MyFile], before: i64) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            file.modified < before
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+// start is inclusive, end is exclusive
+fn filter_files_accessed<'a>(files: &[&'a MyFile], start: i64, end: i64) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            file.accessed >= start && file.accessed < end
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+// start is inclusive, end is exclusive. files without a creation time are left out
+fn filter_files_created<'a>(files: &[&'a MyFile], start: i64, end: i64) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            file.created.is_some_and(|created| created >= start && created < end)
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+// set once the missing creation times have been warned about, so a streamed search says it once
+static WARNED_NO_CREATED: AtomicBool = AtomicBool::new(false);
+
+fn filter_files_modified_on<'a>(files: &[&'a MyFile], times: &[TimeOfWeek], utc: bool) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            times.iter().any(|time| time.contains(file.modified, utc))
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+fn filter_files_attrs<'a>(files: &[&'a MyFile], wanted: &[Attr]) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            match attrs::read(&file.path) {
+                Ok(flags) => {
+                    wanted.iter().any(|attr| attr.is_set(flags))
+                },
+                Err(e) => {
+                    output::warn("could not read attributes", format!("{}: {}", file.path.display(), e));
+                    false
+                },
+            }
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+const CAPABILITY_XATTR: &str = "security.capability";
+const QUARANTINE_XATTR: &str = "com.apple.quarantine";
+
+fn filter_files_type<'a>(files: &[&'a MyFile], entry_type: EntryType) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            match entry_type {
+                EntryType::File => {
+                    !file.is_dir && !file.is_symlink
+                },
+                EntryType::Dir => {
+                    file.is_dir
+                },
+                EntryType::Symlink => {
+                    file.is_symlink
+                },
+                EntryType::Executable => {
+                    file.is_executable
+                },
+                EntryType::Empty => {
+                    if file.is_dir { file.entries == 0 } else { file.is_empty }
+                },
+            }
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+fn filter_files_min_entries<'a>(files: &[&'a MyFile], min: usize) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            file.is_dir && file.entries >= min
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+// the target is resolved from the link's own directory, which metadata does by following it
+fn filter_files_broken_symlinks<'a>(files: &[&'a MyFile]) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            file.is_symlink && fs::metadata(&file.path).is_err()
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+fn filter_files_xattr<'a>(files: &[&'a MyFile], name: &str) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            match xattr::has(&file.path, name) {
+                Ok(has) => {
+                    has
+                },
+                Err(e) => {
+                    output::warn("could not read extended attributes", format!("{}: {}", file.path.display(), e));
+                    false
+                },
+            }
         })
+        .cloned()
+        .collect();
+    filtered
+}
+
+fn remove_quarantine(files: &[&MyFile], dry_run: bool) -> Vec<Operation> {
+    let mut operations = Vec::new();
+    for file in files {
+        if dry_run {
+            match xattr::has(&file.path, QUARANTINE_XATTR) {
+                Ok(true) => {
+                    println!("would remove quarantine from {}", file.path.display());
+                    operations.push(Operation::new(&file.path, Outcome::Changed, None));
+                },
+                Ok(false) => {
+                    operations.push(Operation::new(&file.path, Outcome::Unchanged, None));
+                },
+                Err(e) => {
+                    output::warn("could not read extended attributes", format!("{}: {}", file.path.display(), e));
+                    operations.push(Operation::new(&file.path, Outcome::Failed, Some(e.to_string())));
+                },
+            }
+            continue;
+        }
+        match xattr::remove(&file.path, QUARANTINE_XATTR) {
+            Ok(true) => {
+                println!("removed quarantine from {}", file.path.display());
+                operations.push(Operation::new(&file.path, Outcome::Changed, None));
+            },
+            Ok(false) => {
+                operations.push(Operation::new(&file.path, Outcome::Unchanged, None));
+            },
+            Err(e) => {
+                output::warn("could not remove quarantine", format!("{}: {}", file.path.display(), e));
+                operations.push(Operation::new(&file.path, Outcome::Failed, Some(e.to_string())));
+            },
+        }
+    }
+    operations
+}
+
+// runs git once per file, so it belongs at the end of the filter chain
+fn filter_files_first_committed_before<'a>(files: &[&'a MyFile], before: i64) -> Vec<&'a MyFile> {
+    let filtered: Vec<&MyFile> = files.iter()
+        .filter(|&&file| {
+            match git::first_commit_time(&file.path) {
+                Some(t) => {
+                    t < before
+                },
+                None => {
+                    false
+                },
+            }
+        })
+        .cloned()
+        .collect();
+    filtered
+}
+
+fn output_files(path: &Path, lines: impl Iterator<Item = String>) -> std::io::Result<()> {
+    File::create(path)?;
+
+    let mut output = OpenOptions::new()
+        .append(true)
+        .open(path)
+        .expect("cannot open file");
+
+    // Write to a file
+    for line in lines {
+        output.write_all(line.as_bytes()).expect("write failed");
+        output.write_all(b"\n").expect("write failed");
+    }
+    Ok(())
+}
+
+// cheapest first: name, then the fields already read by the walker (size, times), then the ones
+// that need a syscall per file (attrs, xattrs), then git which runs a process per file. every
+// stage only sees what the stages before it kept, so new filters go where their cost fits
+fn apply_filters<'a>(files: &'a [MyFile], filters: &Filters) -> Vec<&'a MyFile> {
+    apply_filters_with_stats(files, filters, &mut Stats::default())
+}
+
+fn apply_filters_with_stats<'a>(files: &'a [MyFile], filters: &Filters, stats: &mut Stats) -> Vec<&'a MyFile> {
+    let ffiles: Vec<&MyFile> = files.iter().collect();
+    let ffiles: Vec<&MyFile> = match &filters.patterns {
+        None => {
+            ffiles
+        },
+        Some(pat) => {
+            stats.stage("name", ffiles, |ffiles| filter_files_regex(ffiles, pat))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = match &filters.ext {
+        None => {
+            ffiles
+        },
+        Some(extensions) => {
+            stats.stage("name", ffiles, |ffiles| filter_files_ext(ffiles, extensions))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = match &filters.glob {
+        None => {
+            ffiles
+        },
+        Some(globs) => {
+            stats.stage("name", ffiles, |ffiles| filter_files_glob(ffiles, globs))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = match filters.depth_exactly {
+        None => {
+            ffiles
+        },
+        Some(depth) => {
+            stats.stage("depth", ffiles, |ffiles| filter_files_depth(ffiles, depth, depth))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = match filters.depth_range {
+        None => {
+            ffiles
+        },
+        Some(range) => {
+            stats.stage("depth", ffiles, |ffiles| filter_files_depth(ffiles, range.min, range.max))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = match filters.entry_type {
+        None => {
+            ffiles
+        },
+        Some(entry_type) => {
+            stats.stage("type", ffiles, |ffiles| filter_files_type(ffiles, entry_type))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = if filters.empty {
+        stats.stage("type", ffiles, |ffiles| filter_files_type(ffiles, EntryType::Empty))
+    } else {
+        ffiles
+    };
+
+    let ffiles: Vec<&MyFile> = match filters.min_entries {
+        None => {
+            ffiles
+        },
+        Some(min) => {
+            stats.stage("dir", ffiles, |ffiles| filter_files_min_entries(ffiles, min))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = match (filters.dir_min_entries, filters.dir_max_entries) {
+        (None, None) => {
+            ffiles
+        },
+        (min, max) => {
+            let (min, max) = (min.unwrap_or(0), max.unwrap_or(usize::MAX));
+            stats.stage("dir", ffiles, |ffiles| filter_files_dir_entries(ffiles, min, max))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = match filters.size_min {
+        None => {
+            ffiles
+        },
+        Some(min) => {
+            stats.stage("size", ffiles, |ffiles| filter_files_size_min(ffiles, &min.0))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = match filters.size_max {
+        None => {
+            ffiles
+        },
+        Some(max) => {
+            stats.stage("size", ffiles, |ffiles| filter_files_size_max(ffiles, &max.0))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = match filters.changed_within {
+        None => {
+            ffiles
+        },
+        Some(span) => {
+            stats.stage("time", ffiles, |ffiles| filter_files_changed_within(ffiles, span.before(time::now(), filters.utc)))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = match filters.changed_before {
+        None => {
+            ffiles
+        },
+        Some(bound) => {
+            let before = bound.timestamp(time::now(), filters.utc);
+            stats.stage("time", ffiles, |ffiles| filter_files_changed_before(ffiles, before))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = match (filters.accessed_within, filters.accessed_before) {
+        (None, None) => {
+            ffiles
+        },
+        (within, before) => {
+            let now = time::now();
+            let start = within.map_or(i64::MIN, |span| span.before(now, filters.utc));
+            let end = before.map_or(i64::MAX, |bound| bound.timestamp(now, filters.utc));
+            stats.stage("time", ffiles, |ffiles| filter_files_accessed(ffiles, start, end))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = match (filters.created_within, filters.created_before) {
+        (None, None) => {
+            ffiles
+        },
+        (within, before) => {
+            if let Some(file) = ffiles.iter().find(|file| file.created.is_none()) {
+                if !WARNED_NO_CREATED.swap(true, Ordering::Relaxed) {
+                    output::warn("creation time is not recorded, leaving out files like", file.path.display());
+                }
+            }
+            let now = time::now();
+            let start = within.map_or(i64::MIN, |span| span.before(now, filters.utc));
+            let end = before.map_or(i64::MAX, |bound| bound.timestamp(now, filters.utc));
+            stats.stage("time", ffiles, |ffiles| filter_files_created(ffiles, start, end))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = match filters.modified_between {
+        None => {
+            ffiles
+        },
+        Some(range) => {
+            let (start, end) = range.bounds(filters.utc);
+            stats.stage("time", ffiles, |ffiles| filter_files_modified_between(ffiles, start, end))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = match &filters.modified_on {
+        None => {
+            ffiles
+        },
+        Some(times) => {
+            stats.stage("time", ffiles, |ffiles| filter_files_modified_on(ffiles, times, filters.utc))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = match &filters.attr {
+        None => {
+            ffiles
+        },
+        Some(wanted) => {
+            stats.stage("attrs", ffiles, |ffiles| filter_files_attrs(ffiles, wanted))
+        }
+    };
+
+    let ffiles: Vec<&MyFile> = if filters.has_capabilities {
+        stats.stage("xattr", ffiles, |ffiles| filter_files_xattr(ffiles, CAPABILITY_XATTR))
+    } else {
+        ffiles
+    };
+
+    let ffiles: Vec<&MyFile> = if filters.quarantined {
+        stats.stage("xattr", ffiles, |ffiles| filter_files_xattr(ffiles, QUARANTINE_XATTR))
+    } else {
+        ffiles
+    };
+
+    let ffiles: Vec<&MyFile> = if filters.broken_symlinks {
+        stats.stage("link", ffiles, filter_files_broken_symlinks)
+    } else {
+        ffiles
+    };
+
+    let ffiles: Vec<&MyFile> = match filters.first_committed_before {
+        None => {
+            ffiles
+        },
+        Some(date) => {
+            stats.stage("git", ffiles, |ffiles| filter_files_first_committed_before(ffiles, date.midnight(filters.utc)))
+        }
+    };
+
+    ffiles
+}
+
+// keeps the first max matches in each directory. counts carries over between calls, since
+// streaming filters the matches a batch at a time
+fn limit_per_dir<'a>(files: Vec<&'a MyFile>, max: usize, counts: &mut HashMap<PathBuf, usize>) -> Vec<&'a MyFile> {
+    files.into_iter()
+        .filter(|file| {
+            let dir = file.path.parent().map(Path::to_path_buf).unwrap_or_default();
+            let count = counts.entry(dir).or_insert(0);
+            *count += 1;
+            *count <= max
+        })
+        .collect()
+}
+
+// the walker stops taking files from a directory at --max-per-dir when every file it yields is a
+// match, which is when nothing but -p decides and the walker checks -p itself. otherwise the limit
+// has to wait for the filters, or a directory could end up with fewer than its first N matches.
+// limit_per_dir runs afterwards either way, for files that didn't come from the walker
+fn walk_limit(cli: &Cli, filters: &Filters, names: &Option<Vec<Regex>>) -> Option<usize> {
+    let only_patterns = repl::flags(filters).iter().all(|flag| flag.starts_with("--patterns="));
+    let names_checked = filters.patterns.is_none() || names.is_some();
+    cli.max_per_dir.filter(|_| cli.command.is_none() && only_patterns && names_checked)
+}
+
+// stable, so files that compare equal keep the order they were found in, reversed or not
+fn sort_files(files: &mut [&MyFile], key: SortKey, reverse: bool) {
+    let order = |a: &&MyFile, b: &&MyFile| match key {
+        SortKey::Path => a.path.cmp(&b.path),
+        SortKey::Size => a.size_bytes.cmp(&b.size_bytes),
+        SortKey::Modified => a.modified.cmp(&b.modified),
+    };
+    if reverse {
+        files.sort_by(|a, b| order(b, a));
+    } else {
+        files.sort_by(order);
+    }
+}
+
+fn summary_line(summary: &ActionSummary, dry_run: bool, plain: bool) -> String {
+    if !plain {
+        return format!("{} {}, {} unchanged, {} failed",
+                       if dry_run { "would change" } else { "changed" },
+                       summary.changed,
+                       summary.unchanged,
+                       summary.failed);
+    }
+    format!("{} {}. {} left unchanged. {} could not be changed.",
+            output::count(summary.changed as u64, "file", "files"),
+            if dry_run { "would be changed" } else { "changed" },
+            output::count(summary.unchanged as u64, "file", "files"),
+            output::count(summary.failed as u64, "file", "files"))
+}
+
+fn line_format(cli: &Cli) -> LineFormat {
+    LineFormat {
+        format: cli.format,
+        schema_version: cli.schema_version,
+        show_attrs: cli.show_attrs,
+        number: cli.number,
+        show_link_targets: cli.filters.broken_symlinks,
+        porcelain: cli.porcelain,
+    }
+}
+
+// one match as printed, with its --score-junk score and --annotate-cmd output if asked for
+fn render(cli: &Cli, line_format: LineFormat, index: usize, file: &MyFile, junk_score: Option<u32>) -> String {
+    let line = line_format.line(index, file);
+    let line = match junk_score {
+        Some(score) => {
+            junk::annotate(&line, cli.format, score)
+        },
+        None => {
+            line
+        },
+    };
+    match &cli.annotate_cmd {
+        Some(template) => {
+            let annotation = annotate::run(template, &file.path).unwrap_or_else(|e| {
+                output::warn("could not run annotation command", &e);
+                String::new()
+            });
+            annotate::append(&line, cli.format, &annotation)
+        },
+        None => {
+            line
+        },
+    }
+}
+
+// the files to filter: read from stdin with --stdin, otherwise walked
+fn found_files(cli: &Cli, walk_options: &WalkOptions) -> Box<dyn Iterator<Item = Result<MyFile, WalkError>>> {
+    if cli.stdin {
+        Box::new(walk::from_lines(std::io::stdin().lock()))
+    } else if reads_dirs_from_stdin(cli) {
+        // each directory is walked as soon as it is read, so results start before the other tool
+        // is done listing
+        let dirs: Vec<PathBuf> = cli.dirs.iter().filter(|d| d.as_os_str() != "-").cloned().collect();
+        let options = walk_options.clone();
+        let piped = walk::roots_from(std::io::stdin().lock()).flat_map(move |dir| walk::walk(vec![dir], &options));
+        Box::new(walk::walk(dirs, walk_options).chain(piped))
+    } else {
+        Box::new(walk::walk(cli.dirs.clone(), walk_options))
+    }
+}
+
+// set once anything could not be walked, which makes the run exit with 1 in the end
+static WALK_FAILED: AtomicBool = AtomicBool::new(false);
+
+// notes walk errors for the exit code. with --strict the walk ends after the first one, which is
+// still passed on so it can be reported
+fn watch_errors(strict: bool, items: Box<dyn Iterator<Item = Result<MyFile, WalkError>>>)
+    -> Box<dyn Iterator<Item = Result<MyFile, WalkError>>> {
+    let mut stopped = false;
+    Box::new(items.map_while(move |item| {
+        if stopped {
+            return None;
+        }
+        if item.is_err() {
+            WALK_FAILED.store(true, Ordering::Relaxed);
+            stopped = strict;
+        }
+        Some(item)
+    }))
+}
+
+fn require_matches(counts: &[(String, usize)]) {
+    let unmatched: Vec<&str> = counts.iter().filter(|(_, n)| *n == 0).map(|(p, _)| p.as_str()).collect();
+    if !unmatched.is_empty() {
+        for pattern in &unmatched {
+            output::error("pattern matched no files", format!("'{}'", pattern));
+        }
+        std::process::exit(1);
+    }
+}
+
+fn stopped_by_strict() -> ! {
+    output::error("stopped at the first error because of --strict", "");
+    std::process::exit(1);
+}
+
+// the walk for --checkpoint, started over or picked up from the file with --resume
+fn checkpointed_walk(cli: &Cli, path: &Path, walk_options: &WalkOptions) -> walk::Files {
+    if !cli.resume {
+        return walk::walk(cli.dirs.clone(), walk_options);
+    }
+    match checkpoint::load(path) {
+        Ok(entries) => {
+            walk::resume(entries, walk_options)
+        },
+        Err(e) => {
+            output::error("could not read checkpoint", format!("{}: {}", path.display(), e));
+            std::process::exit(1);
+        },
+    }
+}
+
+// --dirs-from-stdin, or - among the --dirs
+fn reads_dirs_from_stdin(cli: &Cli) -> bool {
+    cli.dirs_from_stdin || cli.dirs.iter().any(|d| d.as_os_str() == "-")
+}
+
+// whether matches can be printed as soon as they are found, which is the case unless something
+// needs to see all of them first (sorting, actions and their safety checks, reports over all
+// matches) or the scan is reused (repl, last)
+fn can_stream(cli: &Cli) -> bool {
+    #[cfg(feature = "webhook")]
+    if cli.webhook.is_some() {
+        return false;
+    }
+    cli.command.is_none()
+        && cli.sort.is_none()
+        && !cli.reverse
+        && !cli.score_junk
+        && cli.sample.is_none()
+        && !cli.stats
+        && cli.chown_map.is_none()
+        && !cli.remove_quarantine
+        && cli.exec.is_none()
+        && cli.exec_batch.is_none()
+        && cli.emit_script.is_none()
+        && !cli.find_name_collisions
+        && cli.scan_clamd.is_none()
+        && cli.output_socket.is_none()
+}
+
+// lists matches while the walk is still going, so memory stays flat however big the tree is and
+// --limit stops the walk early. files go through the filters in batches, which keeps the per-call
+// setup of the filters (compiling regexes, spawning threads) from being paid per file
+fn stream(cli: &Cli, walk_options: WalkOptions) -> audit::Record {
+    const BATCH_SIZE: usize = 256;
+    const CHECKPOINT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+    // compiled once here so invalid patterns are reported once, not once per batch
+    let (regexes, filters) = compiled_patterns(&cli.filters);
+    let max_per_dir = walk_limit(cli, &filters, &regexes);
+    let walk_options = WalkOptions { names: regexes, max_per_dir, ..walk_options };
+    let line_format = line_format(cli);
+    let mut out: Box<dyn Write> = match &cli.output {
+        // not locked for the whole walk, walker threads print their warnings to stdout too
+        None => {
+            Box::new(std::io::stdout())
+        },
+        // the interrupted run already wrote the matches before its checkpoint
+        Some(path) if cli.resume => {
+            let file = OpenOptions::new().append(true).create(true).open(path).expect("cannot open file");
+            Box::new(std::io::BufWriter::new(file))
+        },
+        Some(path) => {
+            Box::new(std::io::BufWriter::new(File::create(path).expect("cannot open file")))
+        },
+    };
+
+    let limit = cli.limit.unwrap_or(usize::MAX);
+    let mut matched = 0;
+    // nothing about a match is kept once it's printed: its path goes to the `last` cache and its
+    // name to the pattern counts --require-matches checks at the end. the cache is a convenience,
+    // so failing to write it isn't worth a warning
+    let mut last = last::Writer::create().ok();
+    let mut pattern_counts = filters.patterns.as_deref().filter(|_| cli.require_matches).map(stats::PatternCounts::new);
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let (files, frontier) = match &cli.checkpoint {
+        Some(path) => {
+            let files = checkpointed_walk(cli, path, &walk_options);
+            let frontier = files.frontier();
+            let files: Box<dyn Iterator<Item = Result<MyFile, WalkError>>> = Box::new(files);
+            (files, Some((path, frontier)))
+        },
+        None => {
+            (found_files(cli, &walk_options), None)
+        },
+    };
+    let mut files = watch_errors(cli.strict, files);
+    let mut saved_at = std::time::Instant::now();
+    // matches so far in each directory, for --max-per-dir
+    let mut per_dir = HashMap::new();
+    while matched < limit {
+        let item = files.next();
+        let done = item.is_none();
+        match item {
+            Some(Ok(file)) => {
+                batch.push(file);
+            },
+            Some(Err(e)) if cli.include_errors && cli.format == Format::Json => {
+                writeln!(out, "{}", format::error_record(&e.path, &e.message)).expect("write failed");
+            },
+            _ => {},
+        }
+        // the frontier only lines up with what has been written between directories, and the
+        // batch has to be written out before it is saved
+        let checkpoint = match &frontier {
+            Some((path, frontier)) if !done && saved_at.elapsed() >= CHECKPOINT_INTERVAL => {
+                frontier.entries().map(|entries| (path, entries))
+            },
+            _ => {
+                None
+            },
+        };
+        if batch.len() == BATCH_SIZE || done || checkpoint.is_some() {
+            let kept = apply_filters(&batch, &filters);
+            let kept = match cli.max_per_dir {
+                Some(max) => {
+                    limit_per_dir(kept, max, &mut per_dir)
+                },
+                None => {
+                    kept
+                },
+            };
+            for file in kept.into_iter().take(limit - matched) {
+                writeln!(out, "{}", render(cli, line_format, matched, file, None)).expect("write failed");
+                matched += 1;
+                if let Some(writer) = &mut last {
+                    if writer.push(&file.path).is_err() {
+                        last = None;
+                    }
+                }
+                if let Some(counts) = &mut pattern_counts {
+                    counts.add(file.name());
+                }
+            }
+            batch.clear();
+        }
+        if let Some((path, entries)) = checkpoint {
+            out.flush().expect("write failed");
+            if let Err(e) = checkpoint::save(path, &entries) {
+                output::warn("could not save checkpoint", format!("{}: {}", path.display(), e));
+            }
+            saved_at = std::time::Instant::now();
+        }
+        if done {
+            break;
+        }
+    }
+    drop(files);
+    out.flush().expect("write failed");
+    if cli.strict && WALK_FAILED.load(Ordering::Relaxed) {
+        stopped_by_strict();
+    }
+    // a walk cut short by --limit isn't finished, so its checkpoint stays
+    if let Some((path, _)) = &frontier {
+        if matched < limit {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    if let Some(counts) = pattern_counts {
+        require_matches(&counts.counts());
+    }
+
+    if let Some(writer) = last {
+        let _ = writer.finish();
+    }
+    audit::Record {
+        matches: matched,
+        dry_run: cli.dry_run,
+        ..Default::default()
+    }
+}
+
+fn last_files(indexes: &[String]) -> (Vec<MyFile>, Vec<WalkError>) {
+    let paths = match last::load() {
+        Ok(p) => {
+            p
+        },
+        Err(e) => {
+            output::error("no results from a previous run", e);
+            std::process::exit(1);
+        },
+    };
+    let paths = match last::select(paths, indexes) {
+        Ok(p) => {
+            p
+        },
+        Err(e) => {
+            output::error("could not pick results from the last run", e);
+            std::process::exit(1);
+        },
+    };
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    for path in paths {
+        match MyFile::from_path(path) {
+            Ok(f) => {
+                files.push(f);
+            },
+            Err(path) => {
+                output::warn_error("could not access file", path.display());
+                errors.push(WalkError { path, message: "could not access file".to_string() });
+            },
+        }
+    }
+    (files, errors)
+}
+
+fn exclude_dirs(globs: &[String]) -> Vec<Regex> {
+    globs.iter()
+        .map(|glob| {
+            match ignore::glob_regex(glob.trim_end_matches('/')) {
+                Some(regex) => {
+                    regex
+                },
+                None => {
+                    output::error("invalid --exclude-dir pattern", format!("'{}'", glob));
+                    std::process::exit(1);
+                },
+            }
+        })
+        .collect()
+}
+
+// the plan's files were checked against --max-changes and --dirs when it was made, so only files
+// that changed since are held back here
+fn apply_plan(cli: &Cli, path: &Path) {
+    let plan = match plan::load(path) {
+        Ok(plan) => {
+            plan
+        },
+        Err(e) => {
+            output::error("could not read plan", format!("{}: {}", path.display(), e));
+            std::process::exit(1);
+        },
+    };
+    let mut operations = Vec::new();
+    let mut files = Vec::new();
+    for planned in &plan.files {
+        let file = MyFile::from_path(planned.path.clone()).ok();
+        match plan::drift(planned, file.as_ref()) {
+            Some(reason) => {
+                output::warn("skipping planned file", format!("{}: {}", planned.path.display(), reason));
+                operations.push(Operation::new(&planned.path, Outcome::Failed, Some(reason.to_string())));
+            },
+            None => {
+                files.extend(file);
+            },
+        }
+    }
+    let files: Vec<&MyFile> = files.iter().collect();
+    operations.extend(match &plan.action {
+        plan::Action::ChownMap(mappings) => {
+            chown::run(&files, mappings, cli.dry_run)
+        },
+        plan::Action::RemoveQuarantine => {
+            remove_quarantine(&files, cli.dry_run)
+        },
+    });
+
+    let summary = report::summarize(&operations);
+    let record = audit::Record {
+        matches: plan.files.len(),
+        action: Some(plan.action.name()),
+        dry_run: cli.dry_run,
+        summary: Some(summary),
+        ..Default::default()
+    };
+    write_action_report(&cli.action_report, &record, &operations, None);
+    println!("{}", summary_line(&summary, cli.dry_run, output::is_plain()));
+    write_audit(&cli.audit_log, &record);
+    if summary.failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+// refuses an action on more than --max-changes files or on files outside --dirs, unless --force
+// was given. the refusal is recorded wherever the action would have been
+fn refuse_if_unsafe(cli: &Cli, files: &[&MyFile], record: &mut audit::Record) {
+    if let Err(e) = safety::check(files, &cli.dirs, cli.max_changes, cli.force) {
+        output::warn("refusing to run action", &e);
+        write_action_report(&cli.action_report, record, &[], Some(&e));
+        record.refused = Some(e);
+        write_audit(&cli.audit_log, record);
+        std::process::exit(1);
+    }
+}
+
+fn write_action_report(path: &Option<PathBuf>, record: &audit::Record, operations: &[Operation], refused: Option<&str>) {
+    if let (Some(path), Some(action)) = (path, record.action) {
+        if let Err(e) = report::write(path, action, record.dry_run, operations, refused) {
+            output::warn("could not write action report", format!("{}: {}", path.display(), e));
+        }
     }
 }
 
-// gets all files
-fn get_files(dirs: Vec<PathBuf>) -> Vec<MyFile> {
-    // would be more efficient to skip files based on the regex, but i think this method is more
-    // robust for future features
-    fn rec_get_files(dir: PathBuf) -> Vec<MyFile> {
-        let mut vec = Vec::new();
-        for entry in fs::read_dir(dir).unwrap() {
-            let path = entry.unwrap().path();
-            if !path.is_dir() {
-                let file = match MyFile::from_path(&path) {
-                    Some(f) => {
-                        f
-                    },
-                    None => {
-                        println!("{}{}{}", 
-                                 "warning".bold().yellow(), 
-                                 ": could not access file: ".bold(),
-                                 path.display());
-                        println!("skipping search in directory: {}", path.display());
-                        continue;
-                    },
-                };
-                vec.push(file);
-                continue;
-            }
-            vec.append(&mut rec_get_files(path));
+fn write_audit(path: &Option<PathBuf>, record: &audit::Record) {
+    if let Some(path) = path {
+        // a broken audit log shouldn't hide the results of the run, so only warn
+        if let Err(e) = audit::append(path, record) {
+            output::warn("could not write audit log", format!("{}: {}", path.display(), e));
         }
-        vec
     }
+}
 
-    let mut vec = Vec::new();
-    for dir in dirs {
-        if !dir.exists() {
-            println!("{}{}{}", 
-                     "warning".bold().yellow(), 
-                     ": no such file or directory: ".bold(),
-                     dir.clone().into_os_string().into_string().unwrap());
-            println!("skipping search in directory: {}", dir.clone().into_os_string().into_string().unwrap());
-
-            continue;
-        }
-        vec.append(&mut rec_get_files(dir));
+fn main() {
+    run();
+    if WALK_FAILED.load(Ordering::Relaxed) {
+        std::process::exit(1);
     }
-    vec
 }
 
-fn filter_files_regex<'a>(files: &'a Vec<&'a MyFile>, patterns: &Vec<String>) -> Vec<&'a MyFile> {
-    let mut regexes = Vec::new();
-    for pattern in patterns {
-        let regex = match Regex::new(&pattern) {
-            Ok(r) => {
-                r
+fn run() {
+    crash::install_hook();
+    let args = match queries::expand_args(std::env::args_os().collect()) {
+        Ok(a) => {
+            a
+        },
+        Err(e) => {
+            output::error("could not load the query", e);
+            std::process::exit(1);
+        },
+    };
+    let mut cli = Cli::from_iter(args.clone());
+    if matches!(cli.command, Some(Command::Homes { .. })) && cli.dirs.is_empty() {
+        cli.dirs.push(PathBuf::from("/home"));
+    }
+    output::init(cli.no_color,
+                 cli.plain,
+                 cli.porcelain,
+                 cli.format == Format::Json || cli.include_errors,
+                 cli.include_errors && cli.format == Format::Json);
+    // porcelain output stays the same across versions and locales
+    i18n::init(if cli.porcelain { Some(i18n::Lang::En) } else { cli.lang });
+    let _temp = temp::init(cli.keep_temp);
+
+    if cli.print_capabilities {
+        println!("{}", capabilities::json());
+        return;
+    }
+
+    if let Some(name) = &cli.save_query {
+        let mut args = args;
+        queries::take_option(&mut args, "--save-query");
+        let args: Vec<String> = args.iter().skip(1).map(|a| a.to_string_lossy().into_owned()).collect();
+        match queries::save(name, &args) {
+            Ok(path) => {
+                println!("saved query {} to {}", name, path.display());
             },
             Err(e) => {
-                println!("{}{}{}",
-                         "warning".bold().yellow(),
-                         ": invalid regex: ".bold(),
-                         e);
-                println!("skipping regex match: {}", pattern);
-                continue;
+                output::warn("could not save query", e);
             },
-        };
-        regexes.push(regex);
+        }
     }
+    status::install_handler();
 
-    let filtered: Vec<&MyFile> = files.iter()
-        .filter(|&&file| {
-            regexes.iter().any(|regex| regex.is_match(&file.name))
-        })
-        .cloned()
-        .collect();
-    filtered
-}
+    let walk_options = WalkOptions {
+        min_depth: cli.min_depth.unwrap_or(0),
+        max_depth: cli.max_depth,
+        follow: cli.follow,
+        threads: cli.threads,
+        ignore: !cli.no_ignore,
+        // much of what junk scoring looks for (.DS_Store, .cache, .Trash) is hidden
+        hidden: cli.hidden || cli.score_junk,
+        shard: cli.shard,
+        exclude_dirs: exclude_dirs(&cli.exclude_dir),
+        skip_container_storage: cli.skip_container_storage,
+        one_file_system: cli.one_file_system,
+        traversal: cli.traversal,
+        sorted: cli.sorted,
+        search_archives: cli.search_archives,
+        buffer_size: cli.buffer_size,
+        max_open_files: cli.max_open_files,
+        paths: config::load(),
+        skip_mounts: mounts::skipped(&cli.include_fs),
+        ..Default::default()
+    };
+    let walk_options = cli.filters.walk_options(&walk_options);
+    if (cli.stdin || reads_dirs_from_stdin(&cli)) && matches!(cli.command, Some(Command::Repl)) {
+        output::error("stdin can't be read for paths with repl, which reads its commands from it", "");
+        std::process::exit(1);
+    }
+    if cli.checkpoint.is_some() && !can_stream(&cli) {
+        output::error("--checkpoint only works when matches are listed as they are found, without sorting, reports or actions",
+                      "");
+        std::process::exit(1);
+    }
+    // there is nothing to tell filesystems apart by elsewhere, and silently crossing them would be
+    // worse than refusing
+    if cfg!(not(unix)) && cli.one_file_system {
+        output::error("--one-file-system is only supported on unix", "");
+        std::process::exit(1);
+    }
+    if cli.plan.is_some() && cli.chown_map.is_none() && !cli.remove_quarantine {
+        output::error("--plan records an action, such as --chown-map or --remove-quarantine, instead of running it", "");
+        std::process::exit(1);
+    }
+    if let Some(Command::Apply { file }) = &cli.command {
+        apply_plan(&cli, file);
+        return;
+    }
+    if let Some(shard) = cli.shard {
+        if cli.command.is_none() {
+            eprintln!("{}: {}",
+                      i18n::tr("note"),
+                      i18n::trf("scanning shard {} of {}, combine the outputs of all shards with `rust-find merge FILE...` \
+                                 once they are done",
+                                &[&shard.index, &shard.count]));
+        }
+    }
 
-fn filter_files_size_min<'a>(files: &'a Vec<&'a MyFile>, min_size: &u64) -> Vec<&'a MyFile> {
-    let filtered: Vec<&MyFile> = files.iter()
-        .filter(|&&file| {
-            file.size_bytes >= *min_size
-        })
-        .cloned()
-        .collect();
-    filtered
-}
+    // keys work while the scan runs. stdin is left alone when paths or commands come from it
+    let keys = if !cli.stdin && !reads_dirs_from_stdin(&cli) && !matches!(cli.command, Some(Command::Repl)) {
+        keys::listen()
+    } else {
+        None
+    };
+    if can_stream(&cli) {
+        let record = stream(&cli, walk_options);
+        drop(keys);
+        write_audit(&cli.audit_log, &record);
+        return;
+    }
 
-fn filter_files_size_max<'a>(files: &'a Vec<&'a MyFile>, max_size: &u64) -> Vec<&'a MyFile> {
-    let filtered: Vec<&MyFile> = files.iter()
-        .filter(|&&file| {
-            file.size_bytes <= *max_size
-        })
-        .cloned()
-        .collect();
-    filtered
-}
+    let mut stats = Stats::default();
+    let (regexes, filters) = compiled_patterns(&cli.filters);
+    let walk_start = std::time::Instant::now();
+    let (files, walk_errors) = match &cli.command {
+        Some(Command::Last { indexes }) => {
+            last_files(indexes)
+        },
+        Some(Command::Merge { inputs }) => {
+            match merge::load(inputs) {
+                Ok(files) => {
+                    (files, Vec::new())
+                },
+                Err((path, e)) => {
+                    output::error("could not read results", format!("{}: {}", path.display(), e));
+                    std::process::exit(1);
+                },
+            }
+        },
+        Some(Command::Repl) => {
+            // the patterns can change later in the session, so everything has to be kept
+            walk::collect(watch_errors(cli.strict, found_files(&cli, &walk_options)))
+        },
+        Some(Command::Apply { .. }) => {
+            unreachable!("plans are applied without scanning");
+        },
+        Some(Command::Homes { .. }) | None => {
+            // --stats counts the files the name filter rejects, which the walk would never report
+            let names = regexes.filter(|_| !cli.stats);
+            let max_per_dir = walk_limit(&cli, &filters, &names);
+            let walk_options = match cli.command {
+                Some(Command::Homes { .. }) => {
+                    homes::walk_options(&walk_options)
+                },
+                _ => {
+                    walk_options.clone()
+                },
+            };
+            walk::collect(watch_errors(cli.strict, found_files(&cli, &WalkOptions { names, max_per_dir, ..walk_options })))
+        },
+    };
+    drop(keys);
+    // nothing has been printed or changed yet
+    if cli.strict && !walk_errors.is_empty() {
+        stopped_by_strict();
+    }
 
-fn output_files(path: &PathBuf, files: &Vec<&MyFile>) -> std::io::Result<()> {
-    File::create(path)?;
+    stats.walk(walk_start.elapsed(), files.len());
 
-    let mut output = OpenOptions::new()
-        .append(true)
-        .open(path)
-        .expect("cannot open file");
+    if let Some(Command::Repl) = cli.command {
+        let rescan = |options: &WalkOptions| {
+            let (files, errors) = walk::collect(watch_errors(cli.strict, found_files(&cli, options)));
+            if cli.strict && !errors.is_empty() {
+                stopped_by_strict();
+            }
+            files
+        };
+        repl::run(files, filters, walk_options, rescan);
+        return;
+    }
 
-    // Write to a file
-    for file in files {
-        output.write(file.path.to_str()
-                              .ok_or("conversion to string failed")
-                              .unwrap()
-                              .as_bytes())
-              .expect("write failed");
-        output.write(b"\n").expect("write failed");
+    let mut ffiles = apply_filters_with_stats(&files, &filters, &mut stats);
+    if let Some(patterns) = filters.patterns.as_ref().filter(|_| cli.stats || cli.require_matches) {
+        let counts = stats::pattern_counts(ffiles.iter().map(|f| f.name()), patterns);
+        // nothing has been printed or changed yet
+        if cli.require_matches {
+            require_matches(&counts);
+        }
+        stats.patterns(counts);
+    }
+    if cli.stats {
+        for line in stats.lines() {
+            eprintln!("{}", line);
+        }
+    }
+    if let Some(max) = cli.max_per_dir {
+        ffiles = limit_per_dir(ffiles, max, &mut HashMap::new());
+    }
+    if let Some(n) = cli.sample {
+        let mut rng = cli.seed.map(sample::Rng::new).unwrap_or_else(sample::Rng::from_entropy);
+        ffiles = sample::reservoir(ffiles, n, &mut rng);
+    }
+    // merged results come from several runs, so they get a stable order unless asked otherwise
+    let merging = matches!(cli.command, Some(Command::Merge { .. }));
+    let mut junk_scores = Vec::new();
+    if cli.score_junk {
+        let ranked = junk::rank(&ffiles, time::now());
+        junk_scores = ranked.iter().map(|(score, _)| *score).collect();
+        ffiles = ranked.into_iter().map(|(_, file)| file).collect();
+    } else if let Some(key) = cli.sort.or(merging.then_some(SortKey::Path)) {
+        sort_files(&mut ffiles, key, cli.reverse);
+    } else if cli.reverse {
+        ffiles.reverse();
+    }
+    if let Some(limit) = cli.limit {
+        ffiles.truncate(limit);
+        junk_scores.truncate(limit);
+    }
+    // only a plain scan is saved for `last`, so `last 2` followed by `last 3` both refer to it and
+    // merge or homes don't replace it. the cache is a convenience, so failing to write it isn't
+    // worth a warning
+    if cli.command.is_none() {
+        let _ = last::save(ffiles.iter().map(|f| f.path.as_path()));
     }
-    Ok(())
-}
 
-fn main() {
-    let cli = CLI::from_args();
+    let mut record = audit::Record {
+        matches: ffiles.len(),
+        dry_run: cli.dry_run,
+        ..Default::default()
+    };
+    if cli.chown_map.is_some() {
+        record.action = Some("chown-map");
+    } else if cli.remove_quarantine {
+        record.action = Some("remove-quarantine");
+    }
 
-    let files: Vec<MyFile> = get_files(cli.dirs);
+    if record.action.is_some() && !cli.dry_run {
+        refuse_if_unsafe(&cli, &ffiles, &mut record);
+    }
 
-    let ffiles = files.iter().collect();
-    let ffiles: Vec<&MyFile> = match cli.patterns {
-        None => {
-            files.iter().collect()
-        },
-        Some(pat) => {
-            filter_files_regex(&ffiles, &pat)
+    if let Some(path) = &cli.plan {
+        let action = match &cli.chown_map {
+            Some(mappings) => {
+                plan::Action::ChownMap(mappings.clone())
+            },
+            None => {
+                plan::Action::RemoveQuarantine
+            },
+        };
+        if let Err(e) = plan::save(path, &plan::Plan::new(action, &ffiles)) {
+            output::error("could not write plan", format!("{}: {}", path.display(), e));
+            std::process::exit(1);
         }
+        println!("planned {} for {}, run `rust-find apply {}` to carry it out",
+                 record.action.unwrap_or_default(),
+                 output::count(ffiles.len() as u64, "file", "files"),
+                 path.display());
+        write_audit(&cli.audit_log, &record);
+        return;
+    }
+
+    let operations = if let Some(mappings) = &cli.chown_map {
+        chown::run(&ffiles, mappings, cli.dry_run)
+    } else if cli.remove_quarantine {
+        remove_quarantine(&ffiles, cli.dry_run)
+    } else {
+        Vec::new()
     };
+    if record.action.is_some() {
+        record.summary = Some(report::summarize(&operations));
+        write_action_report(&cli.action_report, &record, &operations, None);
+    }
 
-    let ffiles: Vec<&MyFile> = match cli.size_min {
-        None => {
-            ffiles
-        },
-        Some(min) => {
-            filter_files_size_min(&ffiles, &min)
+    if let Some(summary) = record.summary {
+        println!("{}", summary_line(&summary, cli.dry_run, output::is_plain()));
+        write_audit(&cli.audit_log, &record);
+        if summary.failed > 0 {
+            std::process::exit(1);
         }
-    };
+        return;
+    }
 
-    let ffiles: Vec<&MyFile> = match cli.size_max {
-        None => {
-            ffiles
-        },
-        Some(max) => {
-            filter_files_size_max(&ffiles, &max)
+    if let Some(Command::Homes { top, huge }) = cli.command {
+        for line in homes::lines(&homes::report(&ffiles, &cli.dirs, top, huge.0)) {
+            println!("{}", line);
         }
-    };
+        write_audit(&cli.audit_log, &record);
+        return;
+    }
 
-    match cli.output {
-        None => {
-            for file in ffiles {
-                println!("{}", file.path.display());
+    if let Some(socket) = &cli.scan_clamd {
+        let mut infected = 0;
+        let mut failed = 0;
+        for file in &ffiles {
+            match clamd::scan(socket, &file.path) {
+                Ok(clamd::Verdict::Clean) => {
+                    println!("{}: OK", file.path.display());
+                },
+                Ok(clamd::Verdict::Infected(name)) => {
+                    println!("{}: {} FOUND", file.path.display(), name);
+                    infected += 1;
+                },
+                Err(e) => {
+                    output::warn("could not scan file", format!("{}: {}", file.path.display(), e));
+                    failed += 1;
+                },
             }
-        }, 
-        Some(path) => {
-            output_files(&path, &ffiles).expect("output failed");
-        },
-    };
+        }
+        println!("scanned {}, {} infected, {} failed", ffiles.len() - failed, infected, failed);
+        write_audit(&cli.audit_log, &record);
+        if infected > 0 {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if cli.find_name_collisions {
+        for (dir, files) in collisions::find(&ffiles) {
+            let names: Vec<&str> = files.iter().map(|f| f.name()).collect();
+            println!("{}: {}", dir.display(), names.join(", "));
+        }
+        write_audit(&cli.audit_log, &record);
+        return;
+    }
+
+    if cli.exec.is_some() || cli.exec_batch.is_some() {
+        record.action = Some("exec");
+        if cli.dry_run {
+            let batches = match &cli.exec_batch {
+                Some(template) => {
+                    exec::split(template, &ffiles, cli.batch_size)
+                },
+                None => {
+                    ffiles.iter().map(|&f| vec![f]).collect()
+                },
+            };
+            let template = cli.exec.as_ref().or(cli.exec_batch.as_ref()).unwrap();
+            let mut out = std::io::stdout().lock();
+            for batch in batches {
+                let paths: Vec<&Path> = batch.iter().map(|f| f.path.as_path()).collect();
+                out.write_all(&script::command_line_all(template, &paths)).expect("write failed");
+                out.write_all(b"\n").expect("write failed");
+            }
+            write_audit(&cli.audit_log, &record);
+            return;
+        }
+        // a command can delete as easily as any built-in action, so it is held to the same limits
+        refuse_if_unsafe(&cli, &ffiles, &mut record);
+        let failures = match (&cli.exec, &cli.exec_batch) {
+            (Some(template), _) => {
+                exec::run(template, &ffiles, cli.exec_jobs.max(1), cli.exec_on_error)
+            },
+            (None, Some(template)) => {
+                exec::run_batches(template, &ffiles, cli.batch_size, cli.exec_jobs.max(1), cli.exec_on_error)
+            },
+            (None, None) => {
+                unreachable!()
+            },
+        };
+        write_audit(&cli.audit_log, &record);
+        if !failures.is_empty() {
+            let matches = output::count(ffiles.len() as u64, i18n::tr("match"), i18n::tr("matches"));
+            output::error("the command failed for some matches", i18n::trf("{} of {}", &[&failures.len(), &matches]));
+            for failure in &failures {
+                eprintln!("  {}: {}", failure.path.display(), failure.reason);
+            }
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(template) = cli.emit_script {
+        let result = match cli.output {
+            None => {
+                script::write_script(&mut std::io::stdout().lock(), &template, &ffiles)
+            },
+            Some(path) => {
+                File::create(path).and_then(|mut f| script::write_script(&mut f, &template, &ffiles))
+            },
+        };
+        result.expect("writing script failed");
+        write_audit(&cli.audit_log, &record);
+        return;
+    }
+
+    let line_format = line_format(&cli);
+    #[cfg(feature = "webhook")]
+    if let Some(endpoint) = &cli.webhook {
+        if let Err(e) = webhook::post(endpoint, &ffiles, line_format, cli.webhook_batch) {
+            output::error("could not post matches", e);
+            std::process::exit(1);
+        }
+        write_audit(&cli.audit_log, &record);
+        return;
+    }
+    if let Some(path) = &cli.output_socket {
+        if let Err(e) = socket::send(path, &ffiles, line_format) {
+            output::error("could not send matches", format!("{}: {}", path.display(), e));
+            std::process::exit(1);
+        }
+    } else {
+        let lines = ffiles.iter().enumerate()
+            .map(|(i, file)| render(&cli, line_format, i, file, junk_scores.get(i).copied()));
+        // the walk is over by the time anything is printed, so the errors all come first
+        let errors = walk_errors.iter()
+            .filter(|_| cli.include_errors && cli.format == Format::Json)
+            .map(|e| format::error_record(&e.path, &e.message));
+        let lines = errors.chain(lines);
+        match &cli.output {
+            None => {
+                for line in lines {
+                    println!("{}", line);
+                }
+            }, 
+            Some(path) => {
+                output_files(path, lines).expect("output failed");
+            },
+        };
+    }
+    write_audit(&cli.audit_log, &record);
 }
 
 #[test]
 fn test_filter_files_regex() {
     let file1 = MyFile { 
         path: PathBuf::from("/path/to/file1.txt"), 
-        name: "file1.txt".to_string(), 
-        size_bytes: 1024 
+        size_bytes: 1024,
+        ..Default::default()
     };
     let file2 = MyFile { 
         path: PathBuf::from("/path/to/file2.jpg"), 
-        name: "file2.jpg".to_string(), 
-        size_bytes: 2048 
+        size_bytes: 2048,
+        ..Default::default()
     };
     let file3 = MyFile { 
         path: PathBuf::from("/path/to/file3.txt"), 
-        name: "file3.txt".to_string(), 
-        size_bytes: 4096 
+        size_bytes: 4096,
+        ..Default::default()
     };
     let file4 = MyFile { 
         path: PathBuf::from("/path/to/file4.png"), 
-        name: "file4.png".to_string(), 
-        size_bytes: 1024
+        size_bytes: 1024,
+        ..Default::default()
     };
     let files = vec![&file1, &file2, &file3, &file4];
 
@@ -243,27 +2164,63 @@ fn test_filter_files_regex() {
     assert!(!result.contains(&&file4));
 }
 
+#[test]
+fn test_compiled_patterns() {
+    let filters = Filters { patterns: Some(vec![String::from("("), String::from(r"\.log$")]), ..Default::default() };
+    // the invalid pattern is left out of both, so the walk and the name filter match the same files
+    let (regexes, filters) = compiled_patterns(&filters);
+    assert_eq!(regexes.unwrap().iter().map(|r| r.as_str()).collect::<Vec<&str>>(), vec![r"\.log$"]);
+    assert_eq!(filters.patterns, Some(vec![String::from(r"\.log$")]));
+    let (regexes, filters) = compiled_patterns(&Filters::default());
+    assert!(regexes.is_none() && filters.patterns.is_none());
+}
+
+#[test]
+fn filter_files_ext_test() {
+    let file = |name: &str| MyFile { path: PathBuf::from(name), ..Default::default() };
+    let (code, manifest, backup, hidden, plain) = (file("main.RS"), file("Cargo.toml"), file("site.tar.gz"), file(".gz"), file("gz"));
+    let files = vec![&code, &manifest, &backup, &hidden, &plain];
+
+    assert_eq!(filter_files_ext(&files, &["rs".to_string(), ".TOML".to_string()]), vec![&code, &manifest]);
+    assert_eq!(filter_files_ext(&files, &["tar.gz".to_string()]), vec![&backup]);
+    assert_eq!(filter_files_ext(&files, &["gz".to_string()]), vec![&backup]);
+}
+
+#[test]
+fn test_match_names_in_parallel() {
+    let files: Vec<MyFile> = (0..100)
+        .map(|i| MyFile { path: PathBuf::from(format!("file{}.{}", i, if i % 3 == 0 { "rs" } else { "txt" })), ..Default::default() })
+        .collect();
+    let files: Vec<&MyFile> = files.iter().collect();
+    let regexes = vec![Regex::new(r"\.rs$").unwrap(), Regex::new(r"^file1").unwrap()];
+
+    let serial = match_names(&files, &regexes, 1);
+    assert_eq!(serial.len(), 42);
+    assert_eq!(match_names(&files, &regexes, 4), serial);
+    assert_eq!(match_names(&files, &regexes, 7), serial);
+}
+
 #[test]
 fn filter_files_size_min_test() {
     let file1 = MyFile { 
         path: PathBuf::from("/path/to/file1.txt"), 
-        name: "file1.txt".to_string(), 
-        size_bytes: 1024 
+        size_bytes: 1024,
+        ..Default::default()
     };
     let file2 = MyFile { 
         path: PathBuf::from("/path/to/file2.jpg"), 
-        name: "file2.jpg".to_string(), 
-        size_bytes: 2048 
+        size_bytes: 2048,
+        ..Default::default()
     };
     let file3 = MyFile { 
         path: PathBuf::from("/path/to/file3.txt"), 
-        name: "file3.txt".to_string(), 
-        size_bytes: 4096 
+        size_bytes: 4096,
+        ..Default::default()
     };
     let file4 = MyFile { 
         path: PathBuf::from("/path/to/file4.png"), 
-        name: "file4.png".to_string(), 
-        size_bytes: 1024
+        size_bytes: 1024,
+        ..Default::default()
     };
     let files = vec![&file1, &file2, &file3, &file4];
 
@@ -282,23 +2239,23 @@ fn filter_files_size_min_test() {
 fn filter_files_size_max_test() {
     let file1 = MyFile { 
         path: PathBuf::from("/path/to/file1.txt"), 
-        name: "file1.txt".to_string(), 
-        size_bytes: 1024 
+        size_bytes: 1024,
+        ..Default::default()
     };
     let file2 = MyFile { 
         path: PathBuf::from("/path/to/file2.jpg"), 
-        name: "file2.jpg".to_string(), 
-        size_bytes: 2048 
+        size_bytes: 2048,
+        ..Default::default()
     };
     let file3 = MyFile { 
         path: PathBuf::from("/path/to/file3.txt"), 
-        name: "file3.txt".to_string(), 
-        size_bytes: 4096 
+        size_bytes: 4096,
+        ..Default::default()
     };
     let file4 = MyFile { 
         path: PathBuf::from("/path/to/file4.png"), 
-        name: "file4.png".to_string(), 
-        size_bytes: 1024
+        size_bytes: 1024,
+        ..Default::default()
     };
     let files = vec![&file1, &file2, &file3, &file4];
 
@@ -312,3 +2269,239 @@ fn filter_files_size_max_test() {
     assert!(!result.contains(&&file3));
     assert!(result.contains(&&file4));
 }
+
+#[test]
+fn filter_files_modified_between_test() {
+    let file1 = MyFile { 
+        path: PathBuf::from("/path/to/file1.txt"), 
+        modified: 1704067199,
+        ..Default::default()
+    };
+    let file2 = MyFile { 
+        path: PathBuf::from("/path/to/file2.jpg"), 
+        modified: 1704067200,
+        ..Default::default()
+    };
+    let file3 = MyFile { 
+        path: PathBuf::from("/path/to/file3.txt"), 
+        modified: 1711929599,
+        ..Default::default()
+    };
+    let file4 = MyFile { 
+        path: PathBuf::from("/path/to/file4.png"), 
+        modified: 1711929600,
+        ..Default::default()
+    };
+    let files = vec![&file1, &file2, &file3, &file4];
+
+    let (start, end) = "2024-01-01..2024-03-31".parse::<DateRange>().unwrap().bounds(true);
+
+    let result = filter_files_modified_between(&files, start, end);
+
+    assert_eq!(result.len(), 2);
+    assert!(!result.contains(&&file1));
+    assert!(result.contains(&&file2));
+    assert!(result.contains(&&file3));
+    assert!(!result.contains(&&file4));
+}
+
+#[test]
+fn filter_files_modified_on_test() {
+    // saturday 2024-01-06 12:00 and 20:00, monday 2024-01-08 10:00 and 07:00 (UTC)
+    let file1 = MyFile { 
+        path: PathBuf::from("/path/to/file1.txt"), 
+        modified: 1704542400,
+        ..Default::default()
+    };
+    let file2 = MyFile { 
+        path: PathBuf::from("/path/to/file2.jpg"), 
+        modified: 1704571200,
+        ..Default::default()
+    };
+    let file3 = MyFile { 
+        path: PathBuf::from("/path/to/file3.txt"), 
+        modified: 1704708000,
+        ..Default::default()
+    };
+    let file4 = MyFile { 
+        path: PathBuf::from("/path/to/file4.png"), 
+        modified: 1704697200,
+        ..Default::default()
+    };
+    let files = vec![&file1, &file2, &file3, &file4];
+
+    let result = filter_files_modified_on(&files, &[TimeOfWeek::Weekday], true);
+    assert_eq!(result.len(), 2);
+    assert!(result.contains(&&file3));
+    assert!(result.contains(&&file4));
+
+    let outside_business_hours = [TimeOfWeek::Weekend, TimeOfWeek::Hours(18, 9)];
+    let result = filter_files_modified_on(&files, &outside_business_hours, true);
+    assert_eq!(result.len(), 3);
+    assert!(result.contains(&&file1));
+    assert!(result.contains(&&file2));
+    assert!(!result.contains(&&file3));
+    assert!(result.contains(&&file4));
+}
+
+#[test]
+fn filter_files_depth_test() {
+    let top = MyFile { path: PathBuf::from("a"), depth: 1, ..Default::default() };
+    let nested = MyFile { path: PathBuf::from("b/c/d"), depth: 3, ..Default::default() };
+    let deep = MyFile { path: PathBuf::from("b/c/d/e/f"), depth: 5, ..Default::default() };
+    let files = vec![&top, &nested, &deep];
+
+    assert_eq!(filter_files_depth(&files, 3, 3), vec![&nested]);
+    let range: DepthRange = "2..".parse().unwrap();
+    assert_eq!(filter_files_depth(&files, range.min, range.max), vec![&nested, &deep]);
+    let range: DepthRange = "..3".parse().unwrap();
+    assert_eq!(filter_files_depth(&files, range.min, range.max), vec![&top, &nested]);
+
+    assert_eq!("2..4".parse::<DepthRange>(), Ok(DepthRange { min: 2, max: 4 }));
+    assert!("4..2".parse::<DepthRange>().is_err());
+    assert!("3".parse::<DepthRange>().is_err());
+}
+
+#[test]
+fn filter_files_dir_entries_test() {
+    let lonely = MyFile { path: PathBuf::from("a/only"), dir_entries: 1, ..Default::default() };
+    let crowded = MyFile { path: PathBuf::from("b/one"), dir_entries: 20_000, ..Default::default() };
+    let files = vec![&lonely, &crowded];
+
+    assert_eq!(filter_files_dir_entries(&files, 10_000, usize::MAX), vec![&crowded]);
+    assert_eq!(filter_files_dir_entries(&files, 0, 1), vec![&lonely]);
+    assert_eq!(filter_files_dir_entries(&files, 2, 19_999), Vec::<&MyFile>::new());
+}
+
+#[test]
+fn filter_files_type_test() {
+    let file = MyFile { path: PathBuf::from("a/file"), ..Default::default() };
+    let small = MyFile { path: PathBuf::from("a/small"), is_dir: true, entries: 3, ..Default::default() };
+    let bloated = MyFile { path: PathBuf::from("a/bloated"), is_dir: true, entries: 50_000, ..Default::default() };
+    let files = vec![&file, &small, &bloated];
+
+    assert_eq!(filter_files_type(&files, EntryType::File), vec![&file]);
+    assert_eq!(filter_files_type(&files, EntryType::Dir), vec![&small, &bloated]);
+
+    let link = MyFile { path: PathBuf::from("a/link"), is_symlink: true, ..Default::default() };
+    let script = MyFile { path: PathBuf::from("a/run.sh"), is_executable: true, ..Default::default() };
+    let blank = MyFile { path: PathBuf::from("a/blank"), is_empty: true, ..Default::default() };
+    let hollow = MyFile { path: PathBuf::from("a/hollow"), is_dir: true, ..Default::default() };
+    let kinds = vec![&file, &link, &script, &blank, &hollow, &small];
+    assert_eq!(filter_files_type(&kinds, EntryType::File), vec![&file, &script, &blank]);
+    assert_eq!(filter_files_type(&kinds, EntryType::Symlink), vec![&link]);
+    assert_eq!(filter_files_type(&kinds, EntryType::Executable), vec![&script]);
+    assert_eq!(filter_files_type(&kinds, EntryType::Empty), vec![&blank, &hollow]);
+    assert_eq!("l".parse::<EntryType>(), Ok(EntryType::Symlink));
+
+    assert_eq!(filter_files_min_entries(&files, 10_000), vec![&bloated]);
+    assert_eq!(filter_files_min_entries(&files, 0), vec![&small, &bloated]);
+    assert_eq!("d".parse::<EntryType>(), Ok(EntryType::Dir));
+    assert!("z".parse::<EntryType>().is_err());
+}
+
+#[test]
+fn limit_per_dir_test() {
+    let files: Vec<MyFile> = ["cache/1", "cache/2", "src/a", "cache/3", "src/b"].iter()
+        .map(|p| MyFile { path: PathBuf::from(p), ..Default::default() })
+        .collect();
+    let mut counts = HashMap::new();
+    let first = limit_per_dir(files[..3].iter().collect(), 2, &mut counts);
+    assert_eq!(first, vec![&files[0], &files[1], &files[2]]);
+    let rest = limit_per_dir(files[3..].iter().collect(), 2, &mut counts);
+    assert_eq!(rest, vec![&files[4]]);
+}
+
+#[test]
+fn filter_files_changed_within_test() {
+    let file1 = MyFile { 
+        path: PathBuf::from("/path/to/file1.txt"), 
+        modified: 1000,
+        ..Default::default()
+    };
+    let file2 = MyFile { 
+        path: PathBuf::from("/path/to/file2.jpg"), 
+        modified: 2000,
+        ..Default::default()
+    };
+    let files = vec![&file1, &file2];
+
+    let result = filter_files_changed_within(&files, 1500);
+
+    assert_eq!(result.len(), 1);
+    assert!(!result.contains(&&file1));
+    assert!(result.contains(&&file2));
+
+    let result = filter_files_changed_before(&files, 1500);
+    assert_eq!(result, vec![&file1]);
+}
+
+#[test]
+fn filter_files_accessed_and_created_test() {
+    let read = MyFile { path: PathBuf::from("read"), accessed: 2000, created: Some(1000), ..Default::default() };
+    let unread = MyFile { path: PathBuf::from("unread"), accessed: 500, created: Some(500), ..Default::default() };
+    let unknown = MyFile { path: PathBuf::from("unknown"), accessed: 2000, created: None, ..Default::default() };
+    let files = vec![&read, &unread, &unknown];
+
+    assert_eq!(filter_files_accessed(&files, 1500, i64::MAX), vec![&read, &unknown]);
+    assert_eq!(filter_files_accessed(&files, i64::MIN, 1500), vec![&unread]);
+    assert_eq!(filter_files_created(&files, i64::MIN, 800), vec![&unread]);
+    assert_eq!(filter_files_created(&files, 800, i64::MAX), vec![&read]);
+}
+
+#[test]
+fn sort_files_test() {
+    let file1 = MyFile { 
+        path: PathBuf::from("/path/to/b.txt"), 
+        size_bytes: 2048,
+        modified: 3,
+        ..Default::default()
+    };
+    let file2 = MyFile { 
+        path: PathBuf::from("/path/to/a.txt"), 
+        size_bytes: 4096,
+        modified: 1,
+        ..Default::default()
+    };
+    let file3 = MyFile { 
+        path: PathBuf::from("/path/to/c.txt"), 
+        size_bytes: 1024,
+        modified: 2,
+        ..Default::default()
+    };
+    let mut files = vec![&file1, &file2, &file3];
+
+    sort_files(&mut files, SortKey::Path, false);
+    assert_eq!(files, vec![&file2, &file1, &file3]);
+    sort_files(&mut files, SortKey::Size, true);
+    assert_eq!(files, vec![&file2, &file1, &file3]);
+    sort_files(&mut files, SortKey::Modified, false);
+    assert_eq!(files, vec![&file2, &file3, &file1]);
+
+    // ties keep the order they came in, reversed or not
+    let file4 = MyFile { path: PathBuf::from("/path/to/d.txt"), size_bytes: 2048, ..Default::default() };
+    let mut files = vec![&file1, &file4, &file2];
+    sort_files(&mut files, SortKey::Size, true);
+    assert_eq!(files, vec![&file2, &file1, &file4]);
+    sort_files(&mut files, SortKey::Size, false);
+    assert_eq!(files, vec![&file1, &file4, &file2]);
+}
+
+#[test]
+fn summary_line_test() {
+    let summary = ActionSummary { changed: 1, unchanged: 2, failed: 0 };
+    assert_eq!(summary_line(&summary, false, false), "changed 1, 2 unchanged, 0 failed");
+    assert_eq!(summary_line(&summary, true, true),
+               "1 file would be changed. 2 files left unchanged. 0 files could not be changed.");
+}
+
+#[test]
+fn test_max_depth() {
+    let parse = |depth: &str| Cli::from_iter_safe(["rust_find", "--max-depth", depth]).map(|cli| cli.max_depth);
+    assert_eq!(parse("1").unwrap(), Some(1));
+    assert_eq!(parse("12").unwrap(), Some(12));
+    let error = parse("0").unwrap_err();
+    assert!(error.message.contains("search roots"), "{}", error.message);
+    assert!(parse("-1").is_err());
+    assert!(parse("x").is_err());
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::MyFile;
+
+// a json value as far as merging needs it. arrays and objects (e.g. attrs) are skipped
+#[derive(Debug, PartialEq)]
+enum Value {
+    Str(String),
+    Num(i64),
+    Bool(bool),
+    Other,
+}
+
+// parses one flat json object, as written by --format json. not a general json parser: nested
+// values are skipped over and numbers are read as integers
+fn parse_object(line: &str) -> Option<Vec<(String, Value)>> {
+    let mut chars = line.trim().chars().peekable();
+    let mut fields = Vec::new();
+    if chars.next()? != '{' {
+        return None;
+    }
+    loop {
+        skip_whitespace(&mut chars);
+        match chars.next()? {
+            '}' => {
+                return Some(fields);
+            },
+            ',' => {
+                continue;
+            },
+            '"' => {},
+            _ => {
+                return None;
+            },
+        }
+        let key = parse_string(&mut chars)?;
+        skip_whitespace(&mut chars);
+        if chars.next()? != ':' {
+            return None;
+        }
+        skip_whitespace(&mut chars);
+        let value = match chars.peek()? {
+            '"' => {
+                chars.next();
+                Value::Str(parse_string(&mut chars)?)
+            },
+            '[' | '{' => {
+                skip_nested(&mut chars)?;
+                Value::Other
+            },
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == ',' || c == '}' || c.is_whitespace() {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                match word.as_str() {
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    w => w.parse().map(Value::Num).unwrap_or(Value::Other),
+                }
+            },
+        };
+        fields.push((key, value));
+    }
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_whitespace(chars: &mut Chars) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+// reads up to and including the closing quote, the opening one is already consumed
+fn parse_string(chars: &mut Chars) -> Option<String> {
+    let mut s = String::new();
+    loop {
+        match chars.next()? {
+            '"' => {
+                return Some(s);
+            },
+            '\\' => {
+                match chars.next()? {
+                    'n' => s.push('\n'),
+                    'r' => s.push('\r'),
+                    't' => s.push('\t'),
+                    'u' => {
+                        let hex: String = (0..4).filter_map(|_| chars.next()).collect();
+                        s.push(u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)?);
+                    },
+                    c => s.push(c),
+                }
+            },
+            c => {
+                s.push(c);
+            },
+        }
+    }
+}
+
+fn skip_nested(chars: &mut Chars) -> Option<()> {
+    let mut depth = 0;
+    loop {
+        match chars.next()? {
+            '[' | '{' => {
+                depth += 1;
+            },
+            ']' | '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(());
+                }
+            },
+            '"' => {
+                parse_string(chars)?;
+            },
+            _ => {},
+        }
+    }
+}
+
+// "./a//b/" and "a/b" are the same result
+fn normalize(path: &str) -> PathBuf {
+    Path::new(path).components()
+        .filter(|c| !matches!(c, std::path::Component::CurDir))
+        .collect()
+}
+
+// one line of earlier output: a json record, or a text line with an optional [N] prefix from
+// --number and anything after a tab (--annotate-cmd) dropped. None for error records and blanks
+fn parse_line(line: &str) -> Option<MyFile> {
+    if line.trim_start().starts_with('{') {
+        let fields = parse_object(line)?;
+        let mut file = MyFile::default();
+        for (key, value) in fields {
+            match (key.as_str(), value) {
+                ("type", _) => {
+                    return None;
+                },
+                ("path", Value::Str(s)) => file.path = normalize(&s),
+                ("size_bytes", Value::Num(n)) => file.size_bytes = n.max(0) as u64,
+                ("modified", Value::Num(n)) => file.modified = n,
+                ("depth", Value::Num(n)) => file.depth = n.max(0) as usize,
+                ("is_symlink", Value::Bool(b)) => file.is_symlink = b,
+                ("link_target", Value::Str(s)) => file.link_target = Some(PathBuf::from(s)),
+                ("is_dir", Value::Bool(b)) => file.is_dir = b,
+                ("entries", Value::Num(n)) => file.entries = n.max(0) as usize,
+                _ => {},
+            }
+        }
+        return (!file.path.as_os_str().is_empty()).then_some(file);
+    }
+
+    let line = line.split('\t').next().unwrap_or("");
+    let line = match line.strip_prefix('[').and_then(|l| l.split_once("] ")) {
+        Some((n, rest)) if n.chars().all(|c| c.is_ascii_digit()) => rest,
+        _ => line,
+    };
+    if line.trim().is_empty() {
+        return None;
+    }
+    let path = normalize(line);
+    // text output only has the path, so the rest comes from the file itself when it is here
+    Some(match MyFile::from_path(path) {
+        Ok(file) => {
+            file
+        },
+        Err(path) => {
+            MyFile { path, ..Default::default() }
+        },
+    })
+}
+
+// every result in the given files, each path once. json records win over text lines for the same
+// path since they carry more. a missing input fails the whole merge rather than quietly leaving out
+// a shard
+pub fn load(inputs: &[PathBuf]) -> Result<Vec<MyFile>, (PathBuf, io::Error)> {
+    let mut files: Vec<MyFile> = Vec::new();
+    let mut index = HashMap::new();
+    for input in inputs {
+        let contents = fs::read_to_string(input).map_err(|e| (input.clone(), e))?;
+        for line in contents.lines() {
+            let from_json = line.trim_start().starts_with('{');
+            let Some(file) = parse_line(line) else {
+                continue;
+            };
+            match index.get(&file.path) {
+                Some(&i) => {
+                    if from_json {
+                        files[i] = file;
+                    }
+                },
+                None => {
+                    index.insert(file.path.clone(), files.len());
+                    files.push(file);
+                },
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[test]
+fn test_parse_line() {
+    let json = r#"{"path":"./a/\"b\".txt","name":"\"b\".txt","size_bytes":12,"modified":-5,"depth":2,"parent":"a","extension":"txt","is_symlink":true,"attrs":["immutable"]}"#;
+    let file = parse_line(json).unwrap();
+    assert_eq!(file.path, PathBuf::from("a/\"b\".txt"));
+    assert_eq!(file.name(), "\"b\".txt");
+    assert_eq!((file.size_bytes, file.modified, file.depth, file.is_symlink), (12, -5, 2, true));
+
+    assert!(parse_line(r#"{"type":"error","path":"/x","message":"could not access file"}"#).is_none());
+    assert!(parse_line("{\"path\":").is_none());
+    assert!(parse_line("").is_none());
+
+    let text = parse_line("[3] /nonexistent//dir/./f.log\tASCII text").unwrap();
+    assert_eq!(text.path, PathBuf::from("/nonexistent/dir/f.log"));
+    assert_eq!(text.name(), "f.log");
+}
+
+#[test]
+fn test_parse_object_edge_cases() {
+    let fields = parse_object(" { \"a\" : \"x\\u00e9\\n\" , \"b\":{\"c\":\"}]\",\"d\":[1,[2]]},\"n\":-3,\"f\":1.5,\"z\":null,\"t\":true } ").unwrap();
+    assert_eq!(fields, vec![(String::from("a"), Value::Str(String::from("x\u{e9}\n"))),
+                            (String::from("b"), Value::Other),
+                            (String::from("n"), Value::Num(-3)),
+                            (String::from("f"), Value::Other),
+                            (String::from("z"), Value::Other),
+                            (String::from("t"), Value::Bool(true))]);
+    assert_eq!(parse_object("{}"), Some(Vec::new()));
+    assert_eq!(parse_object("[]"), None);
+    assert_eq!(parse_object("{\"a\" 1}"), None);
+    assert_eq!(parse_object("{\"a\":\"\\uzzzz\"}"), None);
+    assert_eq!(parse_object("{\"a\":{\"b\":1}"), None);
+}
+
+#[test]
+fn test_parse_line_edge_cases() {
+    // sizes and depths can't go below zero, and records without a path are dropped
+    let file = parse_line(r#"{"path":"x","size_bytes":-1,"depth":-2,"is_dir":true,"entries":4,"link_target":"y"}"#).unwrap();
+    assert_eq!((file.size_bytes, file.depth, file.is_dir, file.entries), (0, 0, true, 4));
+    assert_eq!(file.link_target, Some(PathBuf::from("y")));
+    assert!(parse_line(r#"{"size_bytes":1}"#).is_none());
+    assert!(parse_line(r#"{"path":""}"#).is_none());
+    assert!(parse_line(r#"{"path":7}"#).is_none());
+
+    // only a number in brackets is a --number prefix
+    assert_eq!(parse_line("[a] /x/y").unwrap().path, PathBuf::from("[a] /x/y"));
+    assert_eq!(parse_line("[12]/x").unwrap().path, PathBuf::from("[12]/x"));
+    assert_eq!(parse_line("[] /x").unwrap().path, PathBuf::from("/x"));
+    assert!(parse_line("   ").is_none());
+    assert!(parse_line("\tannotation only").is_none());
+    assert_eq!(normalize("./"), PathBuf::new());
+}
+
+#[test]
+fn test_load() {
+    let dir = crate::temp::TestDir::new("merge");
+    let (text, json) = (dir.join("shard0.txt"), dir.join("shard1.jsonl"));
+    fs::write(&text, "/nonexistent/a\n./nonexistent/b\n\n/nonexistent/a\n").unwrap();
+    fs::write(&json, "{\"path\":\"nonexistent/b\",\"size_bytes\":9}\n{\"path\":\"/nonexistent/c\"}\n").unwrap();
+    // results keep the order they were first seen in, and json replaces text for the same path
+    let files = load(&[text.clone(), json.clone(), text.clone()]).unwrap();
+    let paths: Vec<&Path> = files.iter().map(|f| f.path.as_path()).collect();
+    assert_eq!(paths, vec![Path::new("/nonexistent/a"), Path::new("nonexistent/b"), Path::new("/nonexistent/c")]);
+    assert_eq!(files[1].size_bytes, 9);
+    assert!(load(&[]).unwrap().is_empty());
+
+    let missing = dir.join("missing");
+    let (path, error) = load(&[text, missing.clone()]).unwrap_err();
+    assert_eq!((path, error.kind()), (missing, io::ErrorKind::NotFound));
+}
@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+// kernel interfaces rather than stored files. reading them is slow at best (/proc has an entry
+// per process, /sys loops back on itself) and some files block or have side effects
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+const VIRTUAL_FS: [&str; 22] = [
+    "proc", "sysfs", "devtmpfs", "devpts", "cgroup", "cgroup2", "securityfs", "debugfs", "tracefs",
+    "pstore", "bpf", "configfs", "fusectl", "mqueue", "hugetlbfs", "autofs", "binfmt_misc",
+    "efivarfs", "rpc_pipefs", "nsfs", "selinuxfs", "ramfs",
+];
+
+// fuse filesystems are often network mounts (sshfs, rclone, s3fs) that hang when the other end
+// is gone. fuseblk is ntfs-3g and friends on local disks, which are walked
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn is_virtual(fs_type: &str) -> bool {
+    VIRTUAL_FS.contains(&fs_type) || fs_type == "fuse" || fs_type.starts_with("fuse.")
+}
+
+// /proc/mounts escapes space, tab, newline and backslash in paths as octal
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn unescape(field: &str) -> String {
+    let mut path = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(i) = rest.find('\\') {
+        path.push_str(&rest[..i]);
+        let code = rest.get(i + 1..i + 4).and_then(|octal| u8::from_str_radix(octal, 8).ok());
+        match code {
+            Some(byte) => {
+                path.push(byte as char);
+                rest = &rest[i + 4..];
+            },
+            None => {
+                path.push('\\');
+                rest = &rest[i + 1..];
+            },
+        }
+    }
+    path.push_str(rest);
+    path
+}
+
+// the mount points of virtual filesystems in a mount table, except for the types in include.
+// "all" in include keeps every one of them
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn virtual_mounts(table: &str, include: &[String]) -> Vec<PathBuf> {
+    if include.iter().any(|t| t == "all") {
+        return Vec::new();
+    }
+    table.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = fields.nth(1)?;
+            let fs_type = fields.next()?;
+            let skip = is_virtual(fs_type) && !include.iter().any(|t| t == fs_type);
+            skip.then(|| PathBuf::from(unescape(mount_point)))
+        })
+        .collect()
+}
+
+// where the walker shouldn't go. only linux has a mount table to read this from
+#[cfg(target_os = "linux")]
+pub fn skipped(include: &[String]) -> Vec<PathBuf> {
+    match std::fs::read_to_string("/proc/self/mounts") {
+        Ok(table) => {
+            virtual_mounts(&table, include)
+        },
+        Err(_) => {
+            Vec::new()
+        },
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn skipped(_include: &[String]) -> Vec<PathBuf> {
+    Vec::new()
+}
+
+#[test]
+fn test_virtual_mounts() {
+    let table = "proc /proc proc rw,relatime 0 0\n\
+                 /dev/vda / ext4 rw,relatime 0 0\n\
+                 devpts /dev/pts devpts rw 0 0\n\
+                 tmpfs /dev/shm tmpfs rw 0 0\n\
+                 me@nas:/ /mnt/my\\040nas fuse.sshfs rw 0 0\n\
+                 /dev/sdb1 /media/usb fuseblk rw 0 0\n";
+    let paths = |include: &[&str]| {
+        let include: Vec<String> = include.iter().map(|t| t.to_string()).collect();
+        virtual_mounts(table, &include)
+    };
+    assert_eq!(paths(&[]), vec![PathBuf::from("/proc"), PathBuf::from("/dev/pts"), PathBuf::from("/mnt/my nas")]);
+    assert_eq!(paths(&["fuse.sshfs", "proc"]), vec![PathBuf::from("/dev/pts")]);
+    assert_eq!(paths(&["all"]), Vec::<PathBuf>::new());
+    assert_eq!(unescape(r"a\134b\tc"), r"a\b\tc");
+}
+
+#[test]
+fn test_virtual_mounts_edge_cases() {
+    // plain fuse is skipped like its subtypes, other types that only start with fuse are not
+    assert!(is_virtual("fuse"));
+    assert!(is_virtual("fuse.rclone"));
+    assert!(!is_virtual("fuseblk"));
+    assert!(!is_virtual("fusefs"));
+    assert!(!is_virtual("ext4"));
+
+    // lines too short to have a type are left out
+    let table = "\n\
+                 proc\n\
+                 proc /proc\n\
+                 sysfs /sys sysfs rw 0 0\n";
+    assert_eq!(virtual_mounts(table, &[]), vec![PathBuf::from("/sys")]);
+    assert_eq!(virtual_mounts(table, &[String::from("sysfs"), String::from("all")]), Vec::<PathBuf>::new());
+    assert!(virtual_mounts("", &[]).is_empty());
+
+    // escapes that aren't three octal digits are kept as they are
+    assert_eq!(unescape(r"\011\012\040"), "\t\n ");
+    assert_eq!(unescape(r"a\"), r"a\");
+    assert_eq!(unescape(r"a\04"), r"a\04");
+    assert_eq!(unescape(r"\999x"), r"\999x");
+    assert_eq!(unescape(""), "");
+}
@@ -0,0 +1,114 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::i18n;
+
+#[cfg(feature = "color")]
+use colored::Colorize;
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+static PORCELAIN: AtomicBool = AtomicBool::new(false);
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+static ERROR_RECORDS: AtomicBool = AtomicBool::new(false);
+
+// all user-facing warnings go through here so coloring is decided in one place. colored already
+// looks at NO_COLOR/CLICOLOR on its own; --no-color forces it off on top of that. --plain implies
+// --no-color and asks for full sentences instead of terse counters, for screen readers.
+// --porcelain implies --no-color too and keeps stdout for matches alone, with warnings on stderr
+// in english, whatever --lang says. other machine_readable output (json, --include-errors) keeps
+// stdout for the records the same way. with error_records, a path that can't be read is a record
+// of its own, so the warning that would go with it isn't printed at all
+pub fn init(no_color: bool, plain: bool, porcelain: bool, machine_readable: bool, error_records: bool) {
+    PLAIN.store(plain, Ordering::Relaxed);
+    PORCELAIN.store(porcelain, Ordering::Relaxed);
+    TO_STDERR.store(porcelain || machine_readable, Ordering::Relaxed);
+    ERROR_RECORDS.store(error_records, Ordering::Relaxed);
+    #[cfg(feature = "color")]
+    if no_color || plain || porcelain {
+        colored::control::set_override(false);
+    }
+    #[cfg(not(feature = "color"))]
+    let _ = no_color;
+}
+
+pub fn is_plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+// "1 file", "2 files"
+pub fn count(n: impl Into<u64>, singular: &str, plural: &str) -> String {
+    let n = n.into();
+    format!("{} {}", n, if n == 1 { singular } else { plural })
+}
+
+fn is_porcelain() -> bool {
+    PORCELAIN.load(Ordering::Relaxed)
+}
+
+// whether a path that can't be read is reported as an error record instead of a warning
+pub fn errors_as_records() -> bool {
+    ERROR_RECORDS.load(Ordering::Relaxed)
+}
+
+fn print(line: impl Display) {
+    if TO_STDERR.load(Ordering::Relaxed) {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+// msg is looked up in the message catalog, detail is printed as is
+pub fn warn(msg: &'static str, detail: impl Display) {
+    if is_porcelain() {
+        eprintln!("warning: {}: {}", msg, detail);
+        return;
+    }
+    let msg = i18n::tr(msg);
+    #[cfg(feature = "color")]
+    print(format!("{}{}{}",
+                  i18n::tr("warning").bold().yellow(),
+                  format!(": {}: ", msg).bold(),
+                  detail));
+    #[cfg(not(feature = "color"))]
+    print(format!("{}: {}: {}", i18n::tr("warning"), msg, detail));
+}
+
+// a line following up on a warning, e.g. what is skipped because of it. it goes where the warning
+// went
+pub fn note(msg: &'static str, detail: impl Display) {
+    if is_porcelain() {
+        eprintln!("{}: {}", msg, detail);
+    } else {
+        print(format!("{}: {}", i18n::tr(msg), detail));
+    }
+}
+
+// an error the run stops for. unlike warnings these always go to stderr, since they aren't about
+// any one path. an empty detail is left out
+pub fn error(msg: &'static str, detail: impl Display) {
+    let detail = detail.to_string();
+    if detail.is_empty() {
+        eprintln!("{}: {}", i18n::tr("error"), i18n::tr(msg));
+    } else {
+        eprintln!("{}: {}: {}", i18n::tr("error"), i18n::tr(msg), detail);
+    }
+}
+
+// a warning about a path that is also reported as an error record with --include-errors, where the
+// record stands in for it
+pub fn warn_error(msg: &'static str, detail: impl Display) {
+    if !errors_as_records() {
+        warn(msg, detail);
+    }
+}
+
+#[test]
+fn test_count() {
+    assert_eq!(count(0u64, "file", "files"), "0 files");
+    assert_eq!(count(1u8, "file", "files"), "1 file");
+    assert_eq!(count(2u32, "directory", "directories"), "2 directories");
+    assert_eq!(count(u64::MAX, "byte", "bytes"), format!("{} bytes", u64::MAX));
+    // nothing is plain or porcelain until init asks for it
+    assert!(!is_plain() && !is_porcelain());
+}
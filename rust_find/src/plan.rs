@@ -0,0 +1,161 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::chown::UidMapping;
+use crate::MyFile;
+
+const HEADER: &[u8] = b"rust-find plan 1\n";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    ChownMap(Vec<UidMapping>),
+    RemoveQuarantine,
+}
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::ChownMap(_) => {
+                "chown-map"
+            },
+            Action::RemoveQuarantine => {
+                "remove-quarantine"
+            },
+        }
+    }
+}
+
+// a file as it was when the plan was made, so apply can tell whether it changed since
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Planned {
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    pub modified: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    pub action: Action,
+    pub files: Vec<Planned>,
+}
+
+impl Plan {
+    pub fn new(action: Action, files: &[&MyFile]) -> Self {
+        let files = files.iter()
+            .map(|f| Planned { path: f.path.clone(), size_bytes: f.size_bytes, modified: f.modified })
+            .collect();
+        Plan { action, files }
+    }
+}
+
+// why a planned file shouldn't be touched anymore, if it changed since the plan was made. file is
+// what is at its path now
+pub fn drift(planned: &Planned, file: Option<&MyFile>) -> Option<&'static str> {
+    match file {
+        None => {
+            Some("no longer exists")
+        },
+        Some(f) if f.size_bytes != planned.size_bytes || f.modified != planned.modified => {
+            Some("changed since the plan was made")
+        },
+        Some(_) => {
+            None
+        },
+    }
+}
+
+// the action on one line ("chown-map 1000:2000,1001:2001"), then one record per file,
+// "SIZE\tMODIFIED\tPATH" ended by a NUL byte, since paths can hold anything but NUL
+fn encode(plan: &Plan) -> Vec<u8> {
+    let mut bytes = HEADER.to_vec();
+    let action = match &plan.action {
+        Action::ChownMap(mappings) => {
+            let mappings: Vec<String> = mappings.iter().map(|m| format!("{}:{}", m.from, m.to)).collect();
+            format!("chown-map {}\n", mappings.join(","))
+        },
+        Action::RemoveQuarantine => {
+            String::from("remove-quarantine\n")
+        },
+    };
+    bytes.extend_from_slice(action.as_bytes());
+    for file in &plan.files {
+        bytes.extend_from_slice(format!("{}\t{}\t", file.size_bytes, file.modified).as_bytes());
+        bytes.extend_from_slice(file.path.as_os_str().as_encoded_bytes());
+        bytes.push(0);
+    }
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> Option<Plan> {
+    let rest = bytes.strip_prefix(HEADER)?;
+    let end = rest.iter().position(|&b| b == b'\n')?;
+    let action = std::str::from_utf8(&rest[..end]).ok()?;
+    let action = match action.split_once(' ') {
+        Some(("chown-map", mappings)) => {
+            Action::ChownMap(mappings.split(',').map(|m| m.parse().ok()).collect::<Option<_>>()?)
+        },
+        None if action == "remove-quarantine" => {
+            Action::RemoveQuarantine
+        },
+        _ => {
+            return None;
+        },
+    };
+    let mut files = Vec::new();
+    for record in rest[end + 1..].split(|&b| b == 0).filter(|r| !r.is_empty()) {
+        let mut fields = record.splitn(3, |&b| b == b'\t');
+        let mut text = || fields.next().and_then(|f| std::str::from_utf8(f).ok().map(String::from));
+        let size_bytes = text()?.parse().ok()?;
+        let modified = text()?.parse().ok()?;
+        let path = fields.next()?;
+        files.push(Planned { path: path_from_bytes(path), size_bytes, modified });
+    }
+    Some(Plan { action, files })
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+pub fn save(path: &Path, plan: &Plan) -> io::Result<()> {
+    fs::write(path, encode(plan))
+}
+
+pub fn load(path: &Path) -> io::Result<Plan> {
+    decode(&fs::read(path)?).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a rust-find plan"))
+}
+
+#[test]
+fn test_encode_plan() {
+    let files = vec![
+        Planned { path: PathBuf::from("/srv/a\tb"), size_bytes: 4096, modified: 1704067200 },
+        Planned { path: PathBuf::from("relative/file"), size_bytes: 0, modified: -5 },
+    ];
+    let mappings = vec![UidMapping { from: 1000, to: 2000 }, UidMapping { from: 1001, to: 2001 }];
+    let plan = Plan { action: Action::ChownMap(mappings), files: files.clone() };
+    assert_eq!(decode(&encode(&plan)), Some(plan));
+    let plan = Plan { action: Action::RemoveQuarantine, files: Vec::new() };
+    assert_eq!(decode(&encode(&plan)), Some(plan));
+
+    assert_eq!(decode(b"something else"), None);
+    assert_eq!(decode(b"rust-find plan 1\ndelete\n"), None);
+    assert_eq!(decode(b"rust-find plan 1\nchown-map 1000\n"), None);
+}
+
+#[test]
+fn test_drift() {
+    let planned = Planned { path: PathBuf::from("a"), size_bytes: 10, modified: 100 };
+    let file = |size_bytes, modified| MyFile { path: PathBuf::from("a"), size_bytes, modified, ..Default::default() };
+    assert_eq!(drift(&planned, Some(&file(10, 100))), None);
+    assert!(drift(&planned, Some(&file(12, 100))).is_some());
+    assert!(drift(&planned, Some(&file(10, 101))).is_some());
+    assert!(drift(&planned, None).is_some());
+}
@@ -0,0 +1,136 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::config;
+
+// profiles that work without any setup. a saved query with the same name takes precedence. junk
+// walks hidden files, or it could never find .DS_Store or emacs's .#locks
+const BUILTIN: [(&str, &[&str]); 3] = [
+    ("big", &["--sort", "size", "--reverse", "--limit", "50"]),
+    ("recent", &["--changed-within", "24h"]),
+    ("junk", &["--hidden",
+               "-p", r"\.(tmp|temp|bak|old|swp|swo|pyc|pyo|log)$",
+               "-p", r"~$",
+               "-p", r"^(\.DS_Store|Thumbs\.db|desktop\.ini|core)$",
+               "-p", r"^\.?#.*#?$"]),
+];
+
+pub fn builtin_names() -> Vec<&'static str> {
+    BUILTIN.iter().map(|(n, _)| *n).collect()
+}
+
+fn builtin(name: &str) -> Option<Vec<String>> {
+    BUILTIN.iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, args)| args.iter().map(|a| a.to_string()).collect())
+}
+
+// saved queries live in the [queries] section of config.toml
+pub fn find(name: &str) -> Result<Option<Vec<String>>, String> {
+    match config::read()?.queries.into_iter().find(|(n, _)| n == name) {
+        Some((_, args)) => {
+            Ok(Some(args))
+        },
+        None => {
+            Ok(builtin(name))
+        },
+    }
+}
+
+// stores args under name, replacing an earlier query with the same name
+pub fn save(name: &str, args: &[String]) -> Result<PathBuf, String> {
+    let path = config::file().ok_or("could not find a config directory, set HOME or XDG_CONFIG_HOME")?;
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => {
+            text
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            String::new()
+        },
+        Err(e) => {
+            return Err(format!("{}: {}", path.display(), e));
+        },
+    };
+    let text = config::set_query(&text, name, args).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    fs::write(&path, text).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+// removes `option VALUE` and `option=VALUE` from args, returning the value
+pub fn take_option(args: &mut Vec<OsString>, option: &str) -> Option<OsString> {
+    let prefix = format!("{}=", option);
+    let i = args.iter().position(|a| a == option || a.to_string_lossy().starts_with(&prefix))?;
+    let arg = args.remove(i);
+    if arg == option {
+        if i < args.len() {
+            return Some(args.remove(i));
+        }
+        return None;
+    }
+    Some(OsString::from(&arg.to_string_lossy()[prefix.len()..]))
+}
+
+// takes a leading `rust-find NAME ...` if NAME isn't an option or a subcommand
+fn take_profile(args: &mut Vec<OsString>) -> Option<OsString> {
+    let first = args.get(1)?.to_string_lossy();
+    if first.starts_with('-') || ["repl", "last", "merge", "homes", "apply", "help"].contains(&first.as_ref()) {
+        return None;
+    }
+    Some(args.remove(1))
+}
+
+// replaces `--query NAME` (or a leading `NAME`, for aliases like `rust-find big`) with the
+// arguments saved under NAME, before the command line is parsed
+pub fn expand_args(mut args: Vec<OsString>) -> Result<Vec<OsString>, String> {
+    let name = match take_option(&mut args, "--query").or_else(|| take_profile(&mut args)) {
+        Some(n) => {
+            n.to_string_lossy().into_owned()
+        },
+        None => {
+            return Ok(args);
+        },
+    };
+    let saved = find(&name)
+        .map_err(|e| format!("could not read saved queries from {}", e))?
+        .ok_or_else(|| format!("no saved query named '{}'", name))?;
+    let program = if args.is_empty() { OsString::from("rust_find") } else { args.remove(0) };
+    let mut expanded = vec![program];
+    expanded.extend(saved.into_iter().map(OsString::from));
+    expanded.extend(args);
+    Ok(expanded)
+}
+
+#[test]
+fn test_take_option() {
+    let mut args: Vec<OsString> = vec!["rust_find".into(), "--save-query".into(), "big".into(), "-p".into(), "x".into()];
+    assert_eq!(take_option(&mut args, "--save-query"), Some(OsString::from("big")));
+    assert_eq!(args, vec![OsString::from("rust_find"), "-p".into(), "x".into()]);
+
+    let mut args: Vec<OsString> = vec!["rust_find".into(), "--query=big".into()];
+    assert_eq!(take_option(&mut args, "--query"), Some(OsString::from("big")));
+    assert_eq!(args, vec![OsString::from("rust_find")]);
+    assert_eq!(take_option(&mut args, "--query"), None);
+}
+
+#[test]
+fn test_builtin_profiles() {
+    assert_eq!(builtin("recent"), Some(vec!["--changed-within".to_string(), "24h".to_string()]));
+    assert_eq!(builtin("nope"), None);
+    // junk's dotfile patterns only match if hidden files are walked
+    assert!(builtin("junk").unwrap().contains(&String::from("--hidden")));
+    for (_, args) in BUILTIN.iter() {
+        for pattern in args.windows(2).filter(|w| w[0] == "-p").map(|w| w[1]) {
+            assert!(regex::Regex::new(pattern).is_ok(), "bad builtin pattern {}", pattern);
+        }
+    }
+
+    let mut args: Vec<OsString> = vec!["rust_find".into(), "big".into(), "-d".into(), ".".into()];
+    assert_eq!(take_profile(&mut args), Some(OsString::from("big")));
+    let mut args: Vec<OsString> = vec!["rust_find".into(), "repl".into()];
+    assert_eq!(take_profile(&mut args), None);
+}
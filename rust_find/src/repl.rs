@@ -0,0 +1,522 @@
+use std::io::{self, BufRead, Write};
+
+use structopt::StructOpt;
+
+use crate::output;
+use crate::walk::WalkOptions;
+use crate::{apply_filters, Filters, MyFile};
+
+const HELP: &str = "\
+commands:
+    -p PATTERN, --size-min N, ...  add or replace filters, using the same flags as the command line
+    count                          print the number of matches
+    list [N]                       print the first N matches (all if N is left out)
+    unset FLAG...                  turn filters off again, e.g. unset --empty --size-min
+    show                           print the active filters as flags that can be typed back in
+    undo                           go back to the previous set of filters
+    reset                          remove all filters
+    help                           print this message
+    quit                           leave the repl";
+
+// splits a line into words on whitespace, keeping anything inside single or double quotes
+// together so patterns can contain spaces
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => {
+                quote = None;
+            },
+            Some(_) => {
+                word.push(c);
+            },
+            None if c == '\'' || c == '"' => {
+                quote = Some(c);
+                in_word = true;
+            },
+            None if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            },
+            None => {
+                word.push(c);
+                in_word = true;
+            },
+        }
+    }
+    if quote.is_some() {
+        return Err(String::from("unclosed quote"));
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+// filters given in `new` replace the ones in `base`, everything else is kept. flags can only be
+// turned on this way, see unset. `new` is taken apart and the result built field by field, so a
+// filter added to Filters doesn't compile until it is handled here
+fn merge(base: Filters, new: Filters) -> Filters {
+    let Filters {
+        patterns, ext, glob, size_min, size_max, depth_exactly, depth_range, dir_min_entries,
+        dir_max_entries, changed_within, changed_before, accessed_within, accessed_before,
+        created_within, created_before, modified_between, modified_on, first_committed_before, utc,
+        attr, has_capabilities, quarantined, broken_symlinks, entry_type, empty, min_entries
+    } = new;
+    // the two depth filters conflict, so giving either replaces both
+    let (depth_exactly, depth_range) = if depth_exactly.is_some() || depth_range.is_some() {
+        (depth_exactly, depth_range)
+    } else {
+        (base.depth_exactly, base.depth_range)
+    };
+    Filters {
+        patterns: patterns.or(base.patterns),
+        ext: ext.or(base.ext),
+        glob: glob.or(base.glob),
+        size_min: size_min.or(base.size_min),
+        size_max: size_max.or(base.size_max),
+        depth_exactly,
+        depth_range,
+        dir_min_entries: dir_min_entries.or(base.dir_min_entries),
+        dir_max_entries: dir_max_entries.or(base.dir_max_entries),
+        changed_within: changed_within.or(base.changed_within),
+        changed_before: changed_before.or(base.changed_before),
+        accessed_within: accessed_within.or(base.accessed_within),
+        accessed_before: accessed_before.or(base.accessed_before),
+        created_within: created_within.or(base.created_within),
+        created_before: created_before.or(base.created_before),
+        modified_between: modified_between.or(base.modified_between),
+        modified_on: modified_on.or(base.modified_on),
+        first_committed_before: first_committed_before.or(base.first_committed_before),
+        utc: utc || base.utc,
+        attr: attr.or(base.attr),
+        has_capabilities: has_capabilities || base.has_capabilities,
+        quarantined: quarantined || base.quarantined,
+        broken_symlinks: broken_symlinks || base.broken_symlinks,
+        entry_type: entry_type.or(base.entry_type),
+        empty: empty || base.empty,
+        min_entries: min_entries.or(base.min_entries),
+    }
+}
+
+// turns the filters named by flags (as given on the command line, e.g. --empty or -p) off again
+fn unset(filters: Filters, flags: &[String]) -> Result<Filters, String> {
+    let Filters {
+        mut patterns, mut ext, mut glob, mut size_min, mut size_max, mut depth_exactly,
+        mut depth_range, mut dir_min_entries, mut dir_max_entries, mut changed_within,
+        mut changed_before, mut accessed_within, mut accessed_before, mut created_within,
+        mut created_before, mut modified_between, mut modified_on, mut first_committed_before,
+        mut utc, mut attr, mut has_capabilities, mut quarantined, mut broken_symlinks,
+        mut entry_type, mut empty, mut min_entries
+    } = filters;
+    for flag in flags {
+        match flag.as_str() {
+            "-p" | "--patterns" => {
+                patterns = None;
+            },
+            "--ext" => {
+                ext = None;
+            },
+            "-g" | "--glob" => {
+                glob = None;
+            },
+            "--size-min" => {
+                size_min = None;
+            },
+            "--size-max" => {
+                size_max = None;
+            },
+            "--depth-exactly" | "--depth-range" => {
+                depth_exactly = None;
+                depth_range = None;
+            },
+            "--dir-min-entries" => {
+                dir_min_entries = None;
+            },
+            "--dir-max-entries" => {
+                dir_max_entries = None;
+            },
+            "--changed-within" => {
+                changed_within = None;
+            },
+            "--changed-before" => {
+                changed_before = None;
+            },
+            "--accessed-within" => {
+                accessed_within = None;
+            },
+            "--accessed-before" => {
+                accessed_before = None;
+            },
+            "--created-within" => {
+                created_within = None;
+            },
+            "--created-before" => {
+                created_before = None;
+            },
+            "--modified-between" => {
+                modified_between = None;
+            },
+            "--modified-on" => {
+                modified_on = None;
+            },
+            "--first-committed-before" => {
+                first_committed_before = None;
+            },
+            "--utc" => {
+                utc = false;
+            },
+            "--attr" => {
+                attr = None;
+            },
+            "--has-capabilities" => {
+                has_capabilities = false;
+            },
+            "--quarantined" => {
+                quarantined = false;
+            },
+            "--broken-symlinks" => {
+                broken_symlinks = false;
+            },
+            "-t" | "--type" => {
+                entry_type = None;
+            },
+            "--empty" => {
+                empty = false;
+            },
+            "--min-entries" => {
+                min_entries = None;
+            },
+            flag => {
+                return Err(format!("unknown filter '{}'", flag));
+            },
+        }
+    }
+    Ok(Filters {
+        patterns, ext, glob, size_min, size_max, depth_exactly, depth_range, dir_min_entries,
+        dir_max_entries, changed_within, changed_before, accessed_within, accessed_before,
+        created_within, created_before, modified_between, modified_on, first_committed_before, utc,
+        attr, has_capabilities, quarantined, broken_symlinks, entry_type, empty, min_entries
+    })
+}
+
+// the filters as command line flags, which typed back into the repl (or given to rust-find)
+// set the same filters again. built field by field like merge, so a new filter has to be added
+// here before it compiles
+pub fn flags(filters: &Filters) -> Vec<String> {
+    let Filters {
+        patterns, ext, glob, size_min, size_max, depth_exactly, depth_range, dir_min_entries,
+        dir_max_entries, changed_within, changed_before, accessed_within, accessed_before,
+        created_within, created_before, modified_between, modified_on, first_committed_before, utc,
+        attr, has_capabilities, quarantined, broken_symlinks, entry_type, empty, min_entries
+    } = filters;
+    let mut flags = Vec::new();
+    // values go after an = so one starting with a dash isn't taken for a flag
+    let mut values = |flag: &str, values: Vec<String>| {
+        flags.extend(values.into_iter().map(|value| format!("--{}={}", flag, value)));
+    };
+    fn one<T: ToString>(value: &Option<T>) -> Vec<String> {
+        value.iter().map(ToString::to_string).collect()
+    }
+    fn all<T: ToString>(values: &Option<Vec<T>>) -> Vec<String> {
+        values.iter().flatten().map(ToString::to_string).collect()
+    }
+    values("patterns", all(patterns));
+    values("ext", all(ext));
+    values("glob", all(glob));
+    values("size-min", one(size_min));
+    values("size-max", one(size_max));
+    values("depth-exactly", one(depth_exactly));
+    values("depth-range", one(depth_range));
+    values("dir-min-entries", one(dir_min_entries));
+    values("dir-max-entries", one(dir_max_entries));
+    values("changed-within", one(changed_within));
+    values("changed-before", one(changed_before));
+    values("accessed-within", one(accessed_within));
+    values("accessed-before", one(accessed_before));
+    values("created-within", one(created_within));
+    values("created-before", one(created_before));
+    values("modified-between", one(modified_between));
+    values("modified-on", all(modified_on));
+    values("first-committed-before", one(first_committed_before));
+    values("attr", all(attr));
+    values("type", one(entry_type));
+    values("min-entries", one(min_entries));
+    for (flag, set) in [("utc", utc), ("has-capabilities", has_capabilities), ("quarantined", quarantined),
+                        ("broken-symlinks", broken_symlinks), ("empty", empty)] {
+        if *set {
+            flags.push(format!("--{}", flag));
+        }
+    }
+    flags
+}
+
+// quotes a word so split_words gives it back unchanged. quoted parts join onto each other, so a
+// single quote goes in double quotes between two single quoted parts
+fn quote(word: &str) -> String {
+    if !word.is_empty() && !word.contains(|c: char| c.is_whitespace() || c == '\'' || c == '"') {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', "'\"'\"'"))
+}
+
+// the scan the filters run on. the walk options are rebuilt for every set of filters, and the
+// scan is redone when they change, which is when directories start or stop being able to match
+struct Scan<W: FnMut(&WalkOptions) -> Vec<MyFile>> {
+    base: WalkOptions,
+    dirs: bool,
+    files: Vec<MyFile>,
+    walk: W,
+}
+
+impl<W: FnMut(&WalkOptions) -> Vec<MyFile>> Scan<W> {
+    fn matches(&mut self, filters: &Filters) -> Vec<&MyFile> {
+        let options = filters.walk_options(&self.base);
+        if options.dirs != self.dirs {
+            self.files = (self.walk)(&options);
+            self.dirs = options.dirs;
+        }
+        apply_filters(&self.files, filters)
+    }
+}
+
+// files is the scan made for filters, which walk redoes when a later set of filters needs a
+// different walk
+pub fn run(files: Vec<MyFile>, filters: Filters, base: WalkOptions, walk: impl FnMut(&WalkOptions) -> Vec<MyFile>) {
+    let dirs = filters.walk_options(&base).dirs;
+    let mut scan = Scan { base, dirs, files, walk };
+    // every change pushes a new set of filters so undo is just a pop
+    let mut history = vec![filters];
+    let matches = scan.matches(&history[0]).len();
+    println!("scanned {} files, {} match. type help for commands", scan.files.len(), matches);
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        io::stdout().flush().expect("flush failed");
+        let line = match lines.next() {
+            Some(Ok(l)) => {
+                l
+            },
+            _ => {
+                println!();
+                break;
+            },
+        };
+        let words = match split_words(&line) {
+            Ok(w) => {
+                w
+            },
+            Err(e) => {
+                output::warn("could not parse command", e);
+                continue;
+            },
+        };
+        let current = history.last().expect("history is never empty");
+        match words.first().map(|w| w.as_str()) {
+            None => {},
+            Some("quit") | Some("exit") => {
+                break;
+            },
+            Some("help") => {
+                println!("{}", HELP);
+            },
+            Some("show") => {
+                let flags: Vec<String> = flags(current).iter().map(|f| quote(f)).collect();
+                if flags.is_empty() {
+                    println!("no filters");
+                } else {
+                    println!("{}", flags.join(" "));
+                }
+            },
+            Some("count") => {
+                println!("{} matches", scan.matches(current).len());
+            },
+            Some("list") => {
+                let limit = match words.get(1).map(|n| n.parse::<usize>()) {
+                    None => {
+                        usize::MAX
+                    },
+                    Some(Ok(n)) => {
+                        n
+                    },
+                    Some(Err(_)) => {
+                        output::warn("invalid number", &words[1]);
+                        continue;
+                    },
+                };
+                for file in scan.matches(current).iter().take(limit) {
+                    println!("{}", file.path.display());
+                }
+            },
+            Some("undo") => {
+                if history.len() > 1 {
+                    history.pop();
+                }
+                println!("{} matches", scan.matches(history.last().unwrap()).len());
+            },
+            Some("reset") => {
+                history.push(Filters::default());
+                println!("{} matches", scan.matches(history.last().unwrap()).len());
+            },
+            Some("unset") => {
+                match unset(current.clone(), &words[1..]) {
+                    Ok(filters) => {
+                        println!("{} matches", scan.matches(&filters).len());
+                        history.push(filters);
+                    },
+                    Err(e) => {
+                        output::warn("could not unset", e);
+                    },
+                }
+            },
+            Some(flag) if flag.starts_with('-') => {
+                match Filters::from_iter_safe(std::iter::once("filters").chain(words.iter().map(|w| w.as_str()))) {
+                    Ok(new) => {
+                        let merged = merge(current.clone(), new);
+                        println!("{} matches", scan.matches(&merged).len());
+                        history.push(merged);
+                    },
+                    Err(e) => {
+                        println!("{}", e.message);
+                    },
+                }
+            },
+            Some(command) => {
+                output::warn("unknown command", format!("{} (type help for commands)", command));
+            },
+        }
+    }
+}
+
+#[test]
+fn test_split_words() {
+    assert_eq!(split_words("  -p  foo --size-min 10 ").unwrap(), vec!["-p", "foo", "--size-min", "10"]);
+    assert_eq!(split_words("-p 'a b' \"c'd\"").unwrap(), vec!["-p", "a b", "c'd"]);
+    assert_eq!(split_words("-p ''").unwrap(), vec!["-p", ""]);
+    assert!(split_words("-p 'oops").is_err());
+}
+
+#[test]
+fn test_merge() {
+    let parse = |args: &[&str]| Filters::from_iter_safe(std::iter::once("filters").chain(args.iter().copied())).unwrap();
+    let base = parse(&["-p", "a", "--empty", "--depth-exactly", "2"]);
+    let merged = merge(base, parse(&["-p", "b", "--size-min", "1k", "--depth-range", "1..3"]));
+    assert_eq!(merged.patterns, Some(vec![String::from("b")]));
+    assert_eq!(merged.size_min, Some(crate::size::Size(1000)));
+    assert!(merged.empty);
+    assert_eq!(merged.depth_exactly, None);
+    assert!(merged.depth_range.is_some());
+}
+
+#[test]
+fn test_unset() {
+    let parse = |args: &[&str]| Filters::from_iter_safe(std::iter::once("filters").chain(args.iter().copied())).unwrap();
+    let filters = parse(&["-p", "a", "--empty", "--quarantined", "--size-min", "1k", "--depth-exactly", "2"]);
+    let flags = |flags: &[&str]| flags.iter().map(|f| f.to_string()).collect::<Vec<String>>();
+    let unset_some = unset(filters.clone(), &flags(&["--empty", "--depth-range"])).unwrap();
+    assert!(!unset_some.empty && unset_some.quarantined);
+    assert_eq!(unset_some.depth_exactly, None);
+    assert_eq!(unset_some.patterns, Some(vec![String::from("a")]));
+    let unset_all = unset(filters.clone(), &flags(&["-p", "--empty", "--quarantined", "--size-min", "--depth-exactly"])).unwrap();
+    assert_eq!(format!("{:?}", unset_all), format!("{:?}", Filters::default()));
+    assert!(unset(filters, &flags(&["--hidden"])).is_err());
+
+    // every filter this build lists can be turned off again
+    for name in crate::capabilities::filters() {
+        assert!(unset(Filters::default(), &[format!("--{}", name)]).is_ok(), "can't unset --{}", name);
+    }
+}
+
+#[test]
+fn test_scan_follows_filters() {
+    let mut walks = Vec::new();
+    let dir = MyFile { path: std::path::PathBuf::from("d"), is_dir: true, ..Default::default() };
+    let file = MyFile { path: std::path::PathBuf::from("d/f"), ..Default::default() };
+    let mut scan = Scan {
+        base: WalkOptions::default(),
+        dirs: false,
+        files: vec![MyFile { path: file.path.clone(), ..Default::default() }],
+        walk: |options: &WalkOptions| {
+            walks.push(options.dirs);
+            let file = MyFile { path: file.path.clone(), ..Default::default() };
+            if options.dirs {
+                vec![MyFile { path: dir.path.clone(), is_dir: true, ..Default::default() }, file]
+            } else {
+                vec![file]
+            }
+        },
+    };
+    let dirs = Filters { entry_type: Some(crate::EntryType::Dir), ..Default::default() };
+    assert_eq!(scan.matches(&Filters::default()).len(), 1);
+    assert_eq!(scan.matches(&dirs).len(), 1);
+    assert_eq!(scan.matches(&dirs)[0].path, dir.path);
+    assert_eq!(scan.matches(&Filters::default()).len(), 1);
+    drop(scan);
+    assert_eq!(walks, vec![true, false]);
+}
+
+#[test]
+fn test_split_words_edge_cases() {
+    // quotes join onto the word around them rather than starting a new one
+    assert_eq!(split_words("a'b c'd \"e\"f").unwrap(), vec!["ab cd", "ef"]);
+    assert_eq!(split_words("'a''b'").unwrap(), vec!["ab"]);
+    assert_eq!(split_words("\t-p\tx\n").unwrap(), vec!["-p", "x"]);
+    assert_eq!(split_words("'\"' \"'\"").unwrap(), vec!["\"", "'"]);
+    assert_eq!(split_words("-p é\u{a0}x").unwrap(), vec!["-p", "é", "x"]);
+    assert_eq!(split_words("").unwrap(), Vec::<String>::new());
+    assert_eq!(split_words("   ").unwrap(), Vec::<String>::new());
+    assert!(split_words("\"").is_err());
+    assert!(split_words("'a' \"b").is_err());
+}
+
+#[test]
+fn test_merge_and_unset_edge_cases() {
+    let parse = |args: &[&str]| Filters::from_iter_safe(std::iter::once("filters").chain(args.iter().copied())).unwrap();
+    let flags = |flags: &[&str]| flags.iter().map(|f| f.to_string()).collect::<Vec<String>>();
+    let base = parse(&["-p", "a", "--depth-range", "1..3", "--utc", "-t", "d"]);
+
+    // merging nothing changes nothing, and a depth filter replaces the other kind
+    assert_eq!(format!("{:?}", merge(base.clone(), Filters::default())), format!("{:?}", base));
+    let merged = merge(base.clone(), parse(&["--depth-exactly", "1"]));
+    assert_eq!((merged.depth_exactly, merged.depth_range.is_none()), (Some(1), true));
+    // flags stay on once set
+    assert!(merge(base.clone(), parse(&["-p", "b"])).utc);
+
+    // long and short names both work, and nothing is unset when one of the flags is unknown
+    let unset_short = unset(base.clone(), &flags(&["-t", "--patterns", "--utc"])).unwrap();
+    assert_eq!((unset_short.entry_type, unset_short.patterns, unset_short.utc), (None, None, false));
+    assert_eq!(format!("{:?}", unset(base.clone(), &[]).unwrap()), format!("{:?}", base));
+    assert_eq!(unset(base.clone(), &flags(&["--utc", "-x"])).unwrap_err(), "unknown filter '-x'");
+    // flags are named without a value
+    assert!(unset(base, &flags(&["--depth-range=1..3"])).is_err());
+}
+
+#[test]
+fn test_show_flags() {
+    let parse = |args: &[&str]| Filters::from_iter_safe(std::iter::once("filters").chain(args.iter().copied())).unwrap();
+    let filters = parse(&["--patterns=-a b", "-p", "it's \"x\"", "--ext", "tar.gz", "-g", "src/**/*.rs", "--size-min", "1.5k",
+                          "--depth-range", "..3", "--changed-within", "5bd", "--changed-before", "2024-03-01T17:00+01:00",
+                          "--accessed-before", "2d", "--created-before", "2024-01-01", "--modified-between",
+                          "2024-01-01..2024-03-31", "--modified-on", "weekend", "--modified-on", "18-8",
+                          "--first-committed-before", "2020-02-29", "--attr", "a", "-t", "d", "--utc", "--empty"]);
+    let line: Vec<String> = flags(&filters).iter().map(|f| quote(f)).collect();
+    let line = line.join(" ");
+    assert!(line.starts_with("'--patterns=-a b' '--patterns=it'\"'\"'s \"x\"' --ext=tar.gz --glob=src/**/*.rs --size-min=1500 "),
+            "{}",
+            line);
+    assert!(line.contains(" --depth-range=..3 --changed-within=5bd --changed-before=2024-03-01T17:00:00+01:00 --accessed-before=2d "), "{}", line);
+    assert!(line.ends_with(" --attr=append-only --type=d --utc --empty"), "{}", line);
+    // what show prints sets the same filters again
+    let words = split_words(&line).unwrap();
+    let again = Filters::from_iter_safe(std::iter::once("filters").chain(words.iter().map(|w| w.as_str()))).unwrap();
+    assert_eq!(format!("{:?}", again), format!("{:?}", filters));
+    assert!(flags(&Filters::default()).is_empty());
+    assert_eq!(quote(""), "''");
+}
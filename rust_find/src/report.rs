@@ -0,0 +1,133 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::format::json_string;
+use crate::ActionSummary;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Changed,
+    Unchanged,
+    Failed,
+}
+
+impl Outcome {
+    fn name(self) -> &'static str {
+        match self {
+            Outcome::Changed => {
+                "changed"
+            },
+            Outcome::Unchanged => {
+                "unchanged"
+            },
+            Outcome::Failed => {
+                "failed"
+            },
+        }
+    }
+}
+
+// what an action did (or, with --dry-run, would do) to one file. detail is what changed, or why
+// it failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    pub path: PathBuf,
+    pub outcome: Outcome,
+    pub detail: Option<String>,
+}
+
+impl Operation {
+    pub fn new(path: &Path, outcome: Outcome, detail: Option<String>) -> Self {
+        Operation { path: path.to_path_buf(), outcome, detail }
+    }
+}
+
+pub fn summarize(operations: &[Operation]) -> ActionSummary {
+    let mut summary = ActionSummary::default();
+    for op in operations {
+        match op.outcome {
+            Outcome::Changed => {
+                summary.changed += 1;
+            },
+            Outcome::Unchanged => {
+                summary.unchanged += 1;
+            },
+            Outcome::Failed => {
+                summary.failed += 1;
+            },
+        }
+    }
+    summary
+}
+
+// the whole --action-report file: one JSON object with every operation that was attempted. a
+// refused action attempted none, and says why
+pub fn to_json(action: &str, dry_run: bool, time: i64, operations: &[Operation], refused: Option<&str>) -> String {
+    let summary = summarize(operations);
+    let mut json = format!("{{\"time\":{},\"action\":{},\"dry_run\":{}", time, json_string(action), dry_run);
+    if let Some(reason) = refused {
+        json.push_str(&format!(",\"refused\":{}", json_string(reason)));
+    }
+    json.push_str(&format!(",\"changed\":{},\"unchanged\":{},\"failed\":{},\"operations\":[",
+                           summary.changed,
+                           summary.unchanged,
+                           summary.failed));
+    let ops: Vec<String> = operations.iter()
+        .map(|op| {
+            let detail = op.detail.as_deref().map_or(String::from("null"), json_string);
+            format!("{{\"path\":{},\"outcome\":\"{}\",\"detail\":{}}}",
+                    json_string(&op.path.to_string_lossy()),
+                    op.outcome.name(),
+                    detail)
+        })
+        .collect();
+    json.push_str(&ops.join(","));
+    json.push_str("]}");
+    json
+}
+
+// replaces whatever was at path, since the report is about this run only
+pub fn write(path: &Path, action: &str, dry_run: bool, operations: &[Operation], refused: Option<&str>) -> io::Result<()> {
+    fs::write(path, to_json(action, dry_run, crate::time::now(), operations, refused) + "\n")
+}
+
+#[test]
+fn test_report_to_json() {
+    let ops = vec![
+        Operation::new(Path::new("/srv/a"), Outcome::Changed, Some(String::from("1000 -> 2000"))),
+        Operation::new(Path::new("/srv/b"), Outcome::Unchanged, None),
+        Operation::new(Path::new("/srv/\"c\""), Outcome::Failed, Some(String::from("Permission denied"))),
+    ];
+    assert_eq!(summarize(&ops), ActionSummary { changed: 1, unchanged: 1, failed: 1 });
+    assert_eq!(to_json("chown-map", false, 10, &ops[..2], None),
+               r#"{"time":10,"action":"chown-map","dry_run":false,"changed":1,"unchanged":1,"failed":0,"operations":[{"path":"/srv/a","outcome":"changed","detail":"1000 -> 2000"},{"path":"/srv/b","outcome":"unchanged","detail":null}]}"#);
+    assert!(to_json("chown-map", false, 10, &ops, None).contains(r#"{"path":"/srv/\"c\"","outcome":"failed""#));
+    assert_eq!(to_json("remove-quarantine", true, 10, &[], Some("too many")),
+               r#"{"time":10,"action":"remove-quarantine","dry_run":true,"refused":"too many","changed":0,"unchanged":0,"failed":0,"operations":[]}"#);
+}
+
+#[test]
+fn test_report_edge_cases() {
+    assert_eq!(summarize(&[]), ActionSummary::default());
+    let failed = Operation::new(Path::new("a\nb"), Outcome::Failed, Some(String::from("tab\there")));
+    let ops = vec![failed.clone(), failed];
+    assert_eq!(summarize(&ops), ActionSummary { changed: 0, unchanged: 0, failed: 2 });
+    // a refused run can still list operations, and control characters are escaped
+    assert_eq!(to_json("a\"b", true, -1, &ops[..1], Some("")),
+               r#"{"time":-1,"action":"a\"b","dry_run":true,"refused":"","changed":0,"unchanged":0,"failed":1,"operations":[{"path":"a\nb","outcome":"failed","detail":"tab\there"}]}"#);
+}
+
+#[test]
+fn test_write() {
+    let dir = crate::temp::TestDir::new("report");
+    let path = dir.join("report.json");
+    let ops = [Operation::new(Path::new("/x"), Outcome::Unchanged, None)];
+    fs::write(&path, "an earlier report that is much longer than the next one\n").unwrap();
+    write(&path, "chown-map", false, &ops, None).unwrap();
+    // the file holds this run's report alone, on one line
+    let report = fs::read_to_string(&path).unwrap();
+    assert!(report.starts_with("{\"time\":") && report.ends_with("\"detail\":null}]}\n"), "{}", report);
+    assert_eq!(report.lines().count(), 1);
+    assert!(write(&path.join("not a dir"), "chown-map", false, &ops, None).is_err());
+}
@@ -0,0 +1,91 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::MyFile;
+
+// where a file really lives, without resolving the file itself (actions work on the link, not
+// its target)
+fn real_location(path: &Path) -> Option<PathBuf> {
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => {
+            p
+        },
+        _ => {
+            Path::new(".")
+        },
+    };
+    Some(fs::canonicalize(parent).ok()?.join(path.file_name()?))
+}
+
+// paths that don't sit under any of the (already canonical) roots
+pub fn outside_roots<'a>(paths: &[&'a Path], roots: &[PathBuf]) -> Vec<&'a Path> {
+    paths.iter()
+        .filter(|path| !roots.iter().any(|root| path.starts_with(root)))
+        .cloned()
+        .collect()
+}
+
+// refuses to run a destructive action on more than `limit` files or on anything that resolves
+// outside the search roots, unless --force was given. a typo in a pattern or a symlinked
+// directory shouldn't be able to touch the whole disk
+pub fn check(files: &[&MyFile], roots: &[PathBuf], limit: usize, force: bool) -> Result<(), String> {
+    if force {
+        return Ok(());
+    }
+    if files.len() > limit {
+        return Err(format!("action would affect {} files (more than --max-changes {}), pass --force to proceed",
+                           files.len(),
+                           limit));
+    }
+    let roots: Vec<PathBuf> = roots.iter().filter_map(|r| fs::canonicalize(r).ok()).collect();
+    // a file whose location can't be resolved can't be shown to be inside the roots, so it holds
+    // the action back like one that is outside them
+    let mut locations = Vec::with_capacity(files.len());
+    for file in files {
+        match real_location(&file.path) {
+            Some(location) => {
+                locations.push(location);
+            },
+            None => {
+                return Err(format!("could not resolve where {} is to check it is inside the search directories, \
+                                    pass --force to proceed",
+                                   file.path.display()));
+            },
+        }
+    }
+    let locations: Vec<&Path> = locations.iter().map(|p| p.as_path()).collect();
+    if let Some(outside) = outside_roots(&locations, &roots).first() {
+        return Err(format!("action would affect {} which is outside the search directories, pass --force to proceed",
+                           outside.display()));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_outside_roots() {
+    let roots = vec![PathBuf::from("/home/me/projects"), PathBuf::from("/srv")];
+    let paths = [
+        Path::new("/home/me/projects/a.txt"),
+        Path::new("/home/me/projects-old/b.txt"),
+        Path::new("/srv/c.txt"),
+        Path::new("/etc/passwd"),
+    ];
+    assert_eq!(outside_roots(&paths, &roots), vec![Path::new("/home/me/projects-old/b.txt"), Path::new("/etc/passwd")]);
+}
+
+#[test]
+fn test_check() {
+    let root = crate::temp::TestDir::new("safety");
+    fs::create_dir_all(root.join("sub")).unwrap();
+    let file = |path: PathBuf| MyFile { path, ..Default::default() };
+    let inside = file(root.join("sub/a.txt"));
+    let outside = file(std::env::temp_dir().join("b.txt"));
+    let unresolvable = file(root.join("gone/c.txt"));
+    let roots = vec![root.to_path_buf()];
+
+    assert_eq!(check(&[&inside], &roots, 10, false), Ok(()));
+    assert!(check(&[&inside, &outside], &roots, 10, false).is_err());
+    assert!(check(&[&inside, &unresolvable], &roots, 10, false).unwrap_err().contains("could not resolve"));
+    assert!(check(&[&inside, &inside], &roots, 1, false).is_err());
+    assert_eq!(check(&[&outside, &unresolvable], &roots, 1, true), Ok(()));
+}
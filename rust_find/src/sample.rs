@@ -0,0 +1,94 @@
+// splitmix64, which is plenty for picking samples and needs no dependency
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    // seeded from the clock and pid, for runs without --seed
+    pub fn from_entropy() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Rng(nanos ^ ((std::process::id() as u64) << 32))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in 0..n, without the bias of a plain modulo
+    fn below(&mut self, n: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < zone {
+                return x % n;
+            }
+        }
+    }
+}
+
+// n items picked uniformly from items in one pass (reservoir sampling, algorithm R), kept in the
+// order they came in
+pub fn reservoir<T>(items: impl IntoIterator<Item = T>, n: usize, rng: &mut Rng) -> Vec<T> {
+    let mut kept: Vec<(usize, T)> = Vec::with_capacity(n);
+    for (i, item) in items.into_iter().enumerate() {
+        if kept.len() < n {
+            kept.push((i, item));
+        } else {
+            let j = rng.below(i as u64 + 1) as usize;
+            if j < n {
+                kept[j] = (i, item);
+            }
+        }
+    }
+    kept.sort_by_key(|(i, _)| *i);
+    kept.into_iter().map(|(_, item)| item).collect()
+}
+
+#[test]
+fn test_reservoir() {
+    let sample = reservoir(0..1000, 10, &mut Rng::new(42));
+    assert_eq!(sample.len(), 10);
+    assert!(sample.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(reservoir(0..1000, 10, &mut Rng::new(42)), sample);
+    assert_ne!(reservoir(0..1000, 10, &mut Rng::new(43)), sample);
+    assert_eq!(reservoir(0..5, 10, &mut Rng::new(42)), vec![0, 1, 2, 3, 4]);
+    assert_eq!(reservoir(0..5, 0, &mut Rng::new(42)), Vec::<i32>::new());
+
+    // every item is about equally likely to be picked
+    let mut counts = [0; 10];
+    let mut rng = Rng::new(7);
+    for _ in 0..10_000 {
+        for i in reservoir(0..10, 3, &mut rng) {
+            counts[i] += 1;
+        }
+    }
+    assert!(counts.iter().all(|&c| (2700..3300).contains(&c)), "{:?}", counts);
+}
+
+#[test]
+fn test_rng_edge_cases() {
+    // the same seed gives the same stream, and a zero seed is as good as any other
+    let (mut a, mut b) = (Rng::new(0), Rng::new(0));
+    let first: Vec<u64> = (0..4).map(|_| a.next_u64()).collect();
+    assert_eq!(first, (0..4).map(|_| b.next_u64()).collect::<Vec<u64>>());
+    assert!(first.iter().all(|&x| x != 0));
+    // below stays in range even next to the edges
+    let mut rng = Rng::new(u64::MAX);
+    assert!((0..100).all(|_| rng.below(1) == 0));
+    assert!((0..100).all(|_| rng.below(u64::MAX) < u64::MAX));
+    assert!((0..100).all(|_| rng.below(3) < 3));
+
+    // a sample of everything is everything, in order, and nothing to pick from picks nothing
+    assert_eq!(reservoir(0..5, 5, &mut Rng::new(1)), vec![0, 1, 2, 3, 4]);
+    assert_eq!(reservoir(std::iter::empty::<u8>(), 3, &mut Rng::new(1)), Vec::<u8>::new());
+    assert_eq!(reservoir(vec!["only"], 1, &mut Rng::new(1)), vec!["only"]);
+}
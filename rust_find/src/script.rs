@@ -0,0 +1,84 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::MyFile;
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+// wraps s in single quotes so the shell takes it literally, closing and reopening the quotes
+// around any single quote inside it
+pub fn shell_quote(s: &[u8]) -> Vec<u8> {
+    let mut quoted = Vec::with_capacity(s.len() + 2);
+    quoted.push(b'\'');
+    for &b in s {
+        if b == b'\'' {
+            quoted.extend_from_slice(b"'\\''");
+        } else {
+            quoted.push(b);
+        }
+    }
+    quoted.push(b'\'');
+    quoted
+}
+
+// replaces every {} in the template with the quoted path, or appends it if there is no {}
+pub fn command_line(template: &str, path: &Path) -> Vec<u8> {
+    command_line_all(template, &[path])
+}
+
+// the same for a batch of paths, which take the place of {} together, separated by spaces
+pub fn command_line_all(template: &str, paths: &[&Path]) -> Vec<u8> {
+    let mut quoted = Vec::new();
+    for (i, path) in paths.iter().enumerate() {
+        if i > 0 {
+            quoted.push(b' ');
+        }
+        quoted.extend_from_slice(&shell_quote(&path_bytes(path)));
+    }
+    let mut line = Vec::new();
+    let mut parts = template.split("{}");
+    line.extend_from_slice(parts.next().unwrap_or("").as_bytes());
+    let mut substituted = false;
+    for part in parts {
+        line.extend_from_slice(&quoted);
+        line.extend_from_slice(part.as_bytes());
+        substituted = true;
+    }
+    if !substituted {
+        line.push(b' ');
+        line.extend_from_slice(&quoted);
+    }
+    line
+}
+
+pub fn write_script(out: &mut impl Write, template: &str, files: &[&MyFile]) -> io::Result<()> {
+    writeln!(out, "#!/bin/sh")?;
+    writeln!(out, "# generated by rust-find, {} command(s). review before running", files.len())?;
+    for file in files {
+        out.write_all(&command_line(template, &file.path))?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_command_line() {
+    assert_eq!(shell_quote(b"plain.txt"), b"'plain.txt'".to_vec());
+    assert_eq!(shell_quote(b"it's $HOME"), b"'it'\\''s $HOME'".to_vec());
+
+    let path = Path::new("/tmp/a b/it's.txt");
+    assert_eq!(command_line("rm -f {}", path), b"rm -f '/tmp/a b/it'\\''s.txt'".to_vec());
+    assert_eq!(command_line("cp {} {}.bak", Path::new("x")), b"cp 'x' 'x'.bak".to_vec());
+    assert_eq!(command_line("rm -f", Path::new("x")), b"rm -f 'x'".to_vec());
+    assert_eq!(command_line_all("tar cf a.tar {} --", &[Path::new("x"), Path::new("y z")]),
+               b"tar cf a.tar 'x' 'y z' --".to_vec());
+}
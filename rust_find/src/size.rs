@@ -0,0 +1,109 @@
+use std::fmt;
+use std::str::FromStr;
+
+// a size in bytes, given as a plain count or with a unit: k, M, G, T (or kB, MB, ...) are powers
+// of 1000 and Ki, Mi, Gi, Ti (or KiB, MiB, ...) powers of 1024. units ignore case and may follow
+// a fraction, like 1.5G
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size(pub u64);
+
+fn unit_bytes(unit: &str) -> Option<u64> {
+    let unit = unit.to_lowercase();
+    let unit = unit.strip_suffix('b').unwrap_or(&unit);
+    let (prefix, binary) = match unit.strip_suffix('i') {
+        Some(prefix) => (prefix, true),
+        None => (unit, false),
+    };
+    let power = match prefix {
+        "" if !binary => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        "p" => 5,
+        _ => {
+            return None;
+        },
+    };
+    let base: u64 = if binary { 1024 } else { 1000 };
+    Some(base.pow(power))
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid size '{}', expected a number of bytes like 4096, 500k, 10M or 1.5GiB", s);
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let unit = unit_bytes(unit.trim()).ok_or_else(invalid)?;
+        // whole numbers are kept exact, fractions only need to be close
+        if let Ok(count) = number.parse::<u64>() {
+            return count.checked_mul(unit).map(Size).ok_or_else(invalid);
+        }
+        let count = number.parse::<f64>().map_err(|_| invalid())?;
+        let bytes = (count * unit as f64).round();
+        if !bytes.is_finite() || bytes >= u64::MAX as f64 {
+            return Err(invalid());
+        }
+        Ok(Size(bytes as u64))
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!("4096".parse::<Size>(), Ok(Size(4096)));
+    assert_eq!("12B".parse::<Size>(), Ok(Size(12)));
+    assert_eq!("500k".parse::<Size>(), Ok(Size(500_000)));
+    assert_eq!("500K".parse::<Size>(), Ok(Size(500_000)));
+    assert_eq!("10MB".parse::<Size>(), Ok(Size(10_000_000)));
+    assert_eq!("10Mi".parse::<Size>(), Ok(Size(10 * 1024 * 1024)));
+    assert_eq!("2GiB".parse::<Size>(), Ok(Size(2 * 1024 * 1024 * 1024)));
+    assert_eq!("1.5G".parse::<Size>(), Ok(Size(1_500_000_000)));
+    assert_eq!("1.5 KiB".parse::<Size>(), Ok(Size(1536)));
+    assert_eq!("3T".parse::<Size>(), Ok(Size(3_000_000_000_000)));
+
+    assert!("".parse::<Size>().is_err());
+    assert!("k".parse::<Size>().is_err());
+    assert!("10x".parse::<Size>().is_err());
+    assert!("10i".parse::<Size>().is_err());
+    assert!("-5k".parse::<Size>().is_err());
+    assert!("1.2.3M".parse::<Size>().is_err());
+    assert!("99999999999P".parse::<Size>().is_err());
+}
+
+#[test]
+fn test_parse_size_edge_cases() {
+    // every unit spelling, in any case
+    assert_eq!("1kb".parse::<Size>(), Ok(Size(1000)));
+    assert_eq!("1KIB".parse::<Size>(), Ok(Size(1024)));
+    assert_eq!("1ki".parse::<Size>(), Ok(Size(1024)));
+    assert_eq!("1P".parse::<Size>(), Ok(Size(1_000_000_000_000_000)));
+    assert_eq!("1PiB".parse::<Size>(), Ok(Size(1 << 50)));
+    assert_eq!("0".parse::<Size>(), Ok(Size(0)));
+    assert_eq!(" 7 ".parse::<Size>(), Ok(Size(7)));
+    // the largest whole number is exact, and fractions round to the nearest byte
+    assert_eq!("18446744073709551615".parse::<Size>(), Ok(Size(u64::MAX)));
+    assert!("18446744073709551616".parse::<Size>().is_err());
+    assert_eq!("0.5".parse::<Size>(), Ok(Size(1)));
+    assert_eq!("0.0001k".parse::<Size>(), Ok(Size(0)));
+    assert_eq!(".5k".parse::<Size>(), Ok(Size(500)));
+    assert_eq!("1.".parse::<Size>(), Ok(Size(1)));
+
+    assert!(".".parse::<Size>().is_err());
+    assert!("1bb".parse::<Size>().is_err());
+    assert!("1ib".parse::<Size>().is_err());
+    assert!("1 k b".parse::<Size>().is_err());
+    assert!("1e3".parse::<Size>().is_err());
+    assert!("+5".parse::<Size>().is_err());
+    assert!("20000000P".parse::<Size>().is_err());
+    let error = "5q".parse::<Size>().unwrap_err();
+    assert!(error.contains("'5q'"), "{}", error);
+}
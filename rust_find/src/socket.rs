@@ -0,0 +1,73 @@
+use std::io;
+use std::path::Path;
+
+#[cfg(unix)]
+use crate::format::Format;
+use crate::format::LineFormat;
+use crate::MyFile;
+
+// streams one json record per match to a listening unix socket, e.g. an indexer started with
+// `socat UNIX-LISTEN:/tmp/find.sock -`. always json so consumers don't have to guess the format
+#[cfg(unix)]
+pub fn send(path: &Path, files: &[&MyFile], line_format: LineFormat) -> io::Result<()> {
+    use std::io::{BufWriter, Write};
+    use std::os::unix::net::UnixStream;
+
+    let line_format = LineFormat { format: Format::Json, number: false, porcelain: false, ..line_format };
+    let mut stream = BufWriter::new(UnixStream::connect(path)?);
+    for (i, file) in files.iter().enumerate() {
+        stream.write_all(line_format.line(i, file).as_bytes())?;
+        stream.write_all(b"\n")?;
+    }
+    stream.flush()
+}
+
+#[cfg(not(unix))]
+pub fn send(_path: &Path, _files: &[&MyFile], _line_format: LineFormat) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "unix sockets are not supported on this platform"))
+}
+
+#[cfg(unix)]
+#[test]
+fn test_send() {
+    use std::io::Read;
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+
+    let dir = crate::temp::TestDir::new("socket");
+    let path = dir.join("find.sock");
+    let listener = UnixListener::bind(&path).unwrap();
+    let reader = std::thread::spawn(move || {
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            let mut text = String::new();
+            listener.accept().unwrap().0.read_to_string(&mut text).unwrap();
+            received.push(text);
+        }
+        received
+    });
+
+    let files: Vec<MyFile> = ["a b", "c\"d"].iter().map(|p| MyFile { path: PathBuf::from(p), ..Default::default() }).collect();
+    let files: Vec<&MyFile> = files.iter().collect();
+    let line_format = LineFormat {
+        format: Format::Text,
+        schema_version: 1,
+        show_attrs: false,
+        number: true,
+        show_link_targets: false,
+        porcelain: true,
+    };
+    send(&path, &files, line_format).unwrap();
+    // no matches still connects, and sends nothing
+    send(&path, &[], line_format).unwrap();
+    let received = reader.join().unwrap();
+    // one json record per line whatever the output format is
+    let lines: Vec<&str> = received[0].lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("{\"path\":\"a b\"") && lines[0].ends_with('}'), "{}", lines[0]);
+    assert!(lines[1].starts_with("{\"path\":\"c\\\"d\""), "{}", lines[1]);
+    assert!(received[0].ends_with('\n'));
+    assert_eq!(received[1], "");
+
+    assert!(send(&dir.join("missing.sock"), &files, line_format).is_err());
+}
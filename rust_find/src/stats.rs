@@ -0,0 +1,142 @@
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+
+use crate::MyFile;
+
+// one row of --stats. stages group filters by what they look at, so all the time filters share
+// a row
+struct Stage {
+    name: &'static str,
+    time: Duration,
+    checked: usize,
+    rejected: usize,
+}
+
+#[derive(Default)]
+pub struct Stats {
+    walk: Option<(Duration, usize)>,
+    stages: Vec<Stage>,
+    // how many matches each -p pattern accounts for
+    patterns: Vec<(String, usize)>,
+}
+
+impl Stats {
+    pub fn walk(&mut self, time: Duration, found: usize) {
+        self.walk = Some((time, found));
+    }
+
+    // runs one filter and charges its time and rejections to the named stage
+    pub fn stage<'a>(&mut self,
+                     name: &'static str,
+                     files: Vec<&'a MyFile>,
+                     filter: impl FnOnce(&[&'a MyFile]) -> Vec<&'a MyFile>) -> Vec<&'a MyFile> {
+        let start = Instant::now();
+        let kept = filter(&files);
+        let time = start.elapsed();
+        let index = match self.stages.iter().position(|s| s.name == name) {
+            Some(i) => {
+                i
+            },
+            None => {
+                self.stages.push(Stage { name, time: Duration::ZERO, checked: 0, rejected: 0 });
+                self.stages.len() - 1
+            },
+        };
+        let stage = &mut self.stages[index];
+        stage.time += time;
+        stage.checked += files.len();
+        stage.rejected += files.len() - kept.len();
+        kept
+    }
+
+    pub fn patterns(&mut self, counts: Vec<(String, usize)>) {
+        self.patterns = counts;
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("{:<8} {:>10} {:>10} {:>10}", "stage", "time", "checked", "rejected")];
+        if let Some((time, found)) = self.walk {
+            lines.push(format!("{:<8} {:>10} {:>10} {:>10}", "walk", millis(time), found, "-"));
+        }
+        for stage in &self.stages {
+            lines.push(format!("{:<8} {:>10} {:>10} {:>10}", stage.name, millis(stage.time), stage.checked, stage.rejected));
+        }
+        if !self.patterns.is_empty() {
+            lines.push(format!("{:>10}  {}", "matches", "pattern"));
+            for (pattern, count) in &self.patterns {
+                lines.push(format!("{:>10}  {}", count, pattern));
+            }
+        }
+        lines
+    }
+}
+
+// how many of the names each pattern matches, in the order they were given. a name can count
+// toward several patterns. invalid patterns have been warned about already and are left out.
+// names are added one at a time, so a streamed run can count without keeping them
+pub struct PatternCounts {
+    regexes: Vec<Regex>,
+    counts: Vec<usize>,
+}
+
+impl PatternCounts {
+    pub fn new(patterns: &[String]) -> PatternCounts {
+        let regexes: Vec<Regex> = patterns.iter().filter_map(|p| Regex::new(p).ok()).collect();
+        PatternCounts { counts: vec![0; regexes.len()], regexes }
+    }
+
+    pub fn add(&mut self, name: &str) {
+        for (count, regex) in self.counts.iter_mut().zip(&self.regexes) {
+            if regex.is_match(name) {
+                *count += 1;
+            }
+        }
+    }
+
+    pub fn counts(self) -> Vec<(String, usize)> {
+        self.regexes.iter().map(|r| r.as_str().to_string()).zip(self.counts).collect()
+    }
+}
+
+pub fn pattern_counts<'a>(names: impl IntoIterator<Item = &'a str>, patterns: &[String]) -> Vec<(String, usize)> {
+    let mut counts = PatternCounts::new(patterns);
+    for name in names {
+        counts.add(name);
+    }
+    counts.counts()
+}
+
+fn millis(time: Duration) -> String {
+    format!("{:.1}ms", time.as_secs_f64() * 1000.0)
+}
+
+#[test]
+fn test_stage_counts() {
+    use std::path::PathBuf;
+
+    let small = MyFile { path: PathBuf::from("a"), size_bytes: 1, ..Default::default() };
+    let big = MyFile { path: PathBuf::from("b"), size_bytes: 100, ..Default::default() };
+    let mut stats = Stats::default();
+
+    let kept = stats.stage("size", vec![&small, &big], |f| f.iter().filter(|f| f.size_bytes > 10).copied().collect());
+    let kept = stats.stage("size", kept, |f| f.iter().filter(|f| f.size_bytes < 1000).copied().collect());
+    assert_eq!(kept, vec![&big]);
+
+    let lines = stats.lines();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].starts_with("size "));
+    assert!(lines[1].ends_with("          3          1"));
+}
+
+#[test]
+fn test_pattern_counts() {
+    let patterns = vec![String::from(r"\.rs$"), String::from("(unclosed"), String::from("^main"), String::from(r"\.py$")];
+    let counts = pattern_counts(["main.rs", "lib.rs", "main.c"], &patterns);
+    assert_eq!(counts, vec![(String::from(r"\.rs$"), 2), (String::from("^main"), 2), (String::from(r"\.py$"), 0)]);
+
+    let mut stats = Stats::default();
+    stats.patterns(counts);
+    let lines = stats.lines();
+    assert_eq!(lines.last().map(String::as_str), Some("         0  \\.py$"));
+}
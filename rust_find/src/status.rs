@@ -0,0 +1,160 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Instant;
+
+use crate::{i18n, output};
+
+// set from the signal handler, polled by the walker. the handler itself can't safely print, so
+// it only raises this flag
+static DUMP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// while set, the walker waits in poll. the lock and condvar only serve the waiting
+static PAUSED: AtomicBool = AtomicBool::new(false);
+static PAUSE_LOCK: Mutex<()> = Mutex::new(());
+static WOKEN: Condvar = Condvar::new();
+
+// global rather than per walk so the crash report can include them too
+static DIRS: AtomicU64 = AtomicU64::new(0);
+static FILES: AtomicU64 = AtomicU64::new(0);
+
+// directories and files seen so far
+pub fn counts() -> (u64, u64) {
+    (DIRS.load(Ordering::Relaxed), FILES.load(Ordering::Relaxed))
+}
+
+#[cfg(unix)]
+extern "C" fn request_dump(_: libc::c_int) {
+    DUMP_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+// lets `kill -USR1 <pid>` print a snapshot of a running scan without interrupting it
+#[cfg(unix)]
+pub fn install_handler() {
+    let handler = request_dump as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGUSR1, handler);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install_handler() {}
+
+// a snapshot is printed even while paused
+#[cfg_attr(not(unix), allow(dead_code))]
+pub fn show_status() {
+    DUMP_REQUESTED.store(true, Ordering::Relaxed);
+    let _guard = PAUSE_LOCK.lock().unwrap();
+    WOKEN.notify_all();
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+pub fn pause() {
+    if !PAUSED.swap(true, Ordering::Relaxed) {
+        eprintln!("{}", i18n::tr("paused, press r to resume"));
+    }
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+pub fn resume() {
+    let _guard = PAUSE_LOCK.lock().unwrap();
+    if PAUSED.swap(false, Ordering::Relaxed) {
+        eprintln!("{}", i18n::tr("resumed"));
+    }
+    WOKEN.notify_all();
+}
+
+pub struct Status {
+    start: Instant,
+}
+
+impl Status {
+    pub fn new() -> Self {
+        Status {
+            start: Instant::now(),
+        }
+    }
+
+    pub fn enter_dir(&mut self, dir: &Path) {
+        DIRS.fetch_add(1, Ordering::Relaxed);
+        self.poll(dir);
+    }
+
+    pub fn add_file(&mut self, dir: &Path) {
+        FILES.fetch_add(1, Ordering::Relaxed);
+        self.poll(dir);
+    }
+
+    fn poll(&self, dir: &Path) {
+        self.dump_if_requested(dir);
+        if !PAUSED.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut guard = PAUSE_LOCK.lock().unwrap();
+        while PAUSED.load(Ordering::Relaxed) {
+            guard = WOKEN.wait(guard).unwrap();
+            self.dump_if_requested(dir);
+        }
+    }
+
+    fn dump_if_requested(&self, dir: &Path) {
+        if DUMP_REQUESTED.swap(false, Ordering::Relaxed) {
+            let (dirs, files) = counts();
+            let elapsed = self.start.elapsed().as_secs_f64();
+            if output::is_plain() {
+                let dirs = output::count(dirs, i18n::tr("directory"), i18n::tr("directories"));
+                let files = output::count(files, i18n::tr("file"), i18n::tr("files"));
+                eprintln!("{}",
+                          i18n::trf("Status: scanning {}. {} and {} so far, {} seconds elapsed.",
+                                    &[&dir.display(), &dirs, &files, &format!("{:.0}", elapsed)]));
+            } else {
+                eprintln!("{}",
+                          i18n::trf("status: scanning {} ({} dirs, {} files, {}s elapsed)",
+                                    &[&dir.display(), &dirs, &files, &format!("{:.1}", elapsed)]));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_counts() {
+    // the counts are shared with every other scan in the process, so they can only be checked
+    // for growing
+    let mut status = Status::new();
+    let (dirs, files) = counts();
+    status.enter_dir(Path::new("d"));
+    status.add_file(Path::new("d"));
+    status.add_file(Path::new("d"));
+    let (new_dirs, new_files) = counts();
+    assert!(new_dirs > dirs && new_files >= files + 2);
+}
+
+#[test]
+fn test_pause() {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    pause();
+    // pausing twice doesn't need resuming twice
+    pause();
+    let done = Arc::new(AtomicUsize::new(0));
+    let scanned = Arc::clone(&done);
+    let scan = std::thread::spawn(move || {
+        let mut status = Status::new();
+        status.add_file(Path::new("d"));
+        scanned.fetch_add(1, Ordering::Relaxed);
+    });
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(done.load(Ordering::Relaxed), 0);
+    // a snapshot doesn't resume the scan
+    show_status();
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(done.load(Ordering::Relaxed), 0);
+    resume();
+    scan.join().unwrap();
+    assert_eq!(done.load(Ordering::Relaxed), 1);
+    // resuming what isn't paused does nothing
+    resume();
+    assert!(!PAUSED.load(Ordering::Relaxed));
+}
@@ -0,0 +1,205 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+// this run's directory for temporary files. created on first use, so runs that need none don't
+// touch $TMPDIR at all
+static DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+static KEEP: AtomicBool = AtomicBool::new(false);
+// files being written next to where they'll be renamed to, removed along with DIR if the run ends
+// before they are
+static PARTIAL: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+// removes the directory when main returns. process::exit and signals skip destructors, so on
+// unix those are covered separately by init
+pub struct Cleanup;
+
+impl Drop for Cleanup {
+    fn drop(&mut self) {
+        cleanup();
+    }
+}
+
+// anything that needs a temporary file puts it in here. it is only ever used by this process,
+// so names inside it don't have to be unique across runs
+pub fn dir() -> io::Result<PathBuf> {
+    let mut dir = DIR.lock().unwrap();
+    if let Some(dir) = &*dir {
+        return Ok(dir.clone());
+    }
+    let path = std::env::temp_dir().join(format!("rust-find-{}-{}", std::process::id(), crate::time::now()));
+    create_private(&path)?;
+    *dir = Some(path.clone());
+    Ok(path)
+}
+
+// where to write a file that is then renamed over path, so an interruption leaves the old path
+// intact. it has to sit on the same filesystem for the rename, so it can't go in dir(), but it is
+// cleaned up with it
+pub fn partial(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    let mut tracked = PARTIAL.lock().unwrap();
+    if !tracked.contains(&partial) {
+        tracked.push(partial.clone());
+    }
+    partial
+}
+
+// points a child's temporary files at this run's directory, so they go away with the rest
+pub fn for_child(command: &mut Command) -> &mut Command {
+    if let Ok(dir) = dir() {
+        command.env("TMPDIR", dir);
+    }
+    command
+}
+
+#[cfg(unix)]
+fn create_private(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new().mode(0o700).create(path)
+}
+
+#[cfg(not(unix))]
+fn create_private(path: &Path) -> io::Result<()> {
+    fs::create_dir(path)
+}
+
+// try_lock, since this can run on the signal thread while another thread is creating the
+// directory. it then just doesn't exist yet as far as cleaning up is concerned
+fn cleanup() {
+    let dir = match DIR.try_lock() {
+        Ok(mut dir) => {
+            dir.take()
+        },
+        Err(_) => {
+            None
+        },
+    };
+    let partial = match PARTIAL.try_lock() {
+        Ok(mut partial) => {
+            std::mem::take(&mut *partial)
+        },
+        Err(_) => {
+            Vec::new()
+        },
+    };
+    remove(dir, &partial, KEEP.load(Ordering::Relaxed));
+}
+
+// partial files that are still there were interrupted, the rest were renamed into place
+fn remove(dir: Option<PathBuf>, partial: &[PathBuf], keep: bool) {
+    for path in partial {
+        let _ = fs::remove_file(path);
+    }
+    if let Some(dir) = dir {
+        if keep {
+            eprintln!("{} {}", crate::i18n::tr("kept temporary files in"), dir.display());
+        } else {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn cleanup_at_exit() {
+    cleanup();
+}
+
+// has to run before any other thread is started. SIGINT, SIGTERM and SIGHUP are blocked in every
+// thread and taken by one that waits for them, so ctrl-c cleans up instead of killing the process
+// wherever it happens to be. children started with Command get the default mask back
+#[cfg(unix)]
+pub fn init(keep: bool) -> Cleanup {
+    KEEP.store(keep, Ordering::Relaxed);
+    unsafe {
+        libc::atexit(cleanup_at_exit);
+    }
+    let mut signals: libc::sigset_t = unsafe { std::mem::zeroed() };
+    unsafe {
+        libc::sigemptyset(&mut signals);
+        for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+            libc::sigaddset(&mut signals, signal);
+        }
+        libc::pthread_sigmask(libc::SIG_BLOCK, &signals, std::ptr::null_mut());
+    }
+    std::thread::spawn(move || {
+        let mut signal = 0;
+        if unsafe { libc::sigwait(&signals, &mut signal) } == 0 {
+            cleanup();
+            std::process::exit(128 + signal);
+        }
+    });
+    Cleanup
+}
+
+#[cfg(not(unix))]
+pub fn init(keep: bool) -> Cleanup {
+    KEEP.store(keep, Ordering::Relaxed);
+    Cleanup
+}
+
+// a directory of a test's own under $TMPDIR, removed with whatever is in it when the test is done,
+// including when it fails an assertion partway through
+#[cfg(test)]
+pub struct TestDir(PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+    pub fn new(name: &str) -> TestDir {
+        let dir = std::env::temp_dir().join(format!("rust-find-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        TestDir(dir)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<Path> for TestDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn test_cleanup() {
+    // the globals are shared with every other test, so this works on a directory of its own
+    let dir = std::env::temp_dir().join(format!("rust-find-cleanup-{}", std::process::id()));
+    create_private(&dir).unwrap();
+    fs::write(dir.join("scratch"), "").unwrap();
+    let target = dir.with_extension("target");
+    let written = partial(&target);
+    assert!(PARTIAL.lock().unwrap().contains(&written));
+    fs::write(&written, "").unwrap();
+    fs::rename(&written, &target).unwrap();
+    let interrupted = partial(&dir.with_extension("other"));
+    fs::write(&interrupted, "").unwrap();
+
+    remove(Some(dir.clone()), &[], true);
+    assert!(dir.join("scratch").exists());
+    remove(Some(dir.clone()), &[written, interrupted.clone()], false);
+    assert!(!dir.exists());
+    assert!(!interrupted.exists());
+    // what made it into place stays
+    assert!(target.exists());
+    fs::remove_file(target).unwrap();
+}
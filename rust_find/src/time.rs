@@ -0,0 +1,631 @@
+use std::fmt;
+use std::fs::Metadata;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const SECS_PER_DAY: i64 = 86400;
+
+// a calendar date as given on the command line (YYYY-MM-DD). it only becomes a point in time once
+// we know whether it is meant in local time or UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Date {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    // days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    pub fn days_since_epoch(&self) -> i64 {
+        let month = self.month as i64;
+        let year = if month <= 2 { self.year - 1 } else { self.year };
+        let era = year.div_euclid(400);
+        let yoe = year - era * 400;
+        let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+
+    pub fn from_days_since_epoch(days: i64) -> Self {
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        Date { year, month, day }
+    }
+
+    pub fn next(&self) -> Self {
+        Date::from_days_since_epoch(self.days_since_epoch() + 1)
+    }
+
+    // 0 is sunday
+    pub fn weekday(&self) -> u32 {
+        (self.days_since_epoch() + 4).rem_euclid(7) as u32
+    }
+
+    // seconds since the epoch at the start of this day
+    pub fn midnight(&self, utc: bool) -> i64 {
+        self.at(0, utc)
+    }
+
+    // seconds since the epoch at secs into this day
+    pub fn at(&self, secs: i64, utc: bool) -> i64 {
+        if utc {
+            self.days_since_epoch() * SECS_PER_DAY + secs
+        } else {
+            local_time(self, secs)
+        }
+    }
+
+    // the day a timestamp falls on
+    pub fn of(timestamp: i64, utc: bool) -> Self {
+        if utc {
+            Date::from_days_since_epoch(timestamp.div_euclid(SECS_PER_DAY))
+        } else {
+            local_date(timestamp)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn local_time(date: &Date, secs: i64) -> i64 {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = (date.year - 1900) as libc::c_int;
+    tm.tm_mon = date.month as libc::c_int - 1;
+    tm.tm_mday = date.day as libc::c_int;
+    tm.tm_hour = (secs / 3600) as libc::c_int;
+    tm.tm_min = (secs / 60 % 60) as libc::c_int;
+    tm.tm_sec = (secs % 60) as libc::c_int;
+    tm.tm_isdst = -1;
+    unsafe { libc::mktime(&mut tm) as i64 }
+}
+
+// no portable way to ask for the local offset without extra crates, so fall back to UTC
+#[cfg(not(unix))]
+fn local_time(date: &Date, secs: i64) -> i64 {
+    date.at(secs, true)
+}
+
+#[cfg(unix)]
+fn local_date(timestamp: i64) -> Date {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let t = timestamp as libc::time_t;
+    if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+        return Date::of(timestamp, true);
+    }
+    Date { year: tm.tm_year as i64 + 1900, month: tm.tm_mon as u32 + 1, day: tm.tm_mday as u32 }
+}
+
+#[cfg(not(unix))]
+fn local_date(timestamp: i64) -> Date {
+    Date::of(timestamp, true)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid date '{}', expected YYYY-MM-DD", s);
+        let mut parts = s.trim().splitn(3, '-');
+        let year = parts.next().and_then(|p| p.parse::<i64>().ok()).ok_or_else(invalid)?;
+        let month = parts.next().and_then(|p| p.parse::<u32>().ok()).ok_or_else(invalid)?;
+        let day = parts.next().and_then(|p| p.parse::<u32>().ok()).ok_or_else(invalid)?;
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return Err(invalid());
+        }
+        Ok(Date { year, month, day })
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+// an inclusive range of calendar days, e.g. 2024-01-01..2024-03-31
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    pub first: Date,
+    pub last: Date,
+}
+
+impl DateRange {
+    // half-open [start, end) in seconds since the epoch, covering all of the last day
+    pub fn bounds(&self, utc: bool) -> (i64, i64) {
+        (self.first.midnight(utc), self.last.next().midnight(utc))
+    }
+}
+
+impl FromStr for DateRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (first, last) = s.split_once("..")
+            .ok_or_else(|| format!("invalid date range '{}', expected FIRST..LAST", s))?;
+        let range = DateRange {
+            first: first.parse()?,
+            last: last.parse()?,
+        };
+        if range.last.days_since_epoch() < range.first.days_since_epoch() {
+            return Err(format!("invalid date range '{}', end is before start", s));
+        }
+        Ok(range)
+    }
+}
+
+impl fmt::Display for DateRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}..{}", self.first, self.last)
+    }
+}
+
+// a moment given as a day and a time of day, e.g. 2024-03-01T17:00, 2024-03-01 17:00:30Z or
+// 2024-03-01T17:00+02:00. without an offset it is local time, or UTC with --utc
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub date: Date,
+    // seconds into the day
+    pub secs: i64,
+    // seconds east of UTC, when given
+    pub offset: Option<i64>,
+}
+
+impl DateTime {
+    pub fn timestamp(&self, utc: bool) -> i64 {
+        match self.offset {
+            Some(offset) => {
+                self.date.at(self.secs, true) - offset
+            },
+            None => {
+                self.date.at(self.secs, utc)
+            },
+        }
+    }
+}
+
+// HH:MM or HH:MM:SS, in seconds
+fn parse_clock(s: &str) -> Option<i64> {
+    let mut parts = s.split(':');
+    let hours = parts.next()?.parse::<i64>().ok().filter(|h| (0..24).contains(h))?;
+    let minutes = parts.next()?.parse::<i64>().ok().filter(|m| (0..60).contains(m))?;
+    let seconds = match parts.next() {
+        Some(p) => p.parse::<i64>().ok().filter(|s| (0..60).contains(s))?,
+        None => 0,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(hours * 3600 + minutes * 60 + seconds)
+}
+
+// Z, +HH:MM, +HHMM or +HH (or - for west of UTC), in seconds
+fn parse_offset(s: &str) -> Option<i64> {
+    if s == "Z" || s == "z" {
+        return Some(0);
+    }
+    let sign = match s.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => {
+            return None;
+        },
+    };
+    let digits: String = s[1..].chars().filter(|&c| c != ':').collect();
+    if digits.len() != 2 && digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours = digits[..2].parse::<i64>().ok().filter(|&h| h <= 14)?;
+    let minutes = digits[2..].parse::<i64>().unwrap_or(0);
+    if minutes >= 60 {
+        return None;
+    }
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+impl FromStr for DateTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid time '{}', expected YYYY-MM-DDTHH:MM[:SS] with an optional Z or +HH:MM", s);
+        let (date, time) = s.trim().split_once(['T', ' ']).ok_or_else(invalid)?;
+        let date = date.parse::<Date>()?;
+        let split = time.find(['Z', 'z', '+', '-']).unwrap_or(time.len());
+        let (clock, offset) = time.split_at(split);
+        let secs = parse_clock(clock).ok_or_else(invalid)?;
+        let offset = if offset.is_empty() { None } else { Some(parse_offset(offset).ok_or_else(invalid)?) };
+        Ok(DateTime { date, secs, offset })
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}T{:02}:{:02}:{:02}", self.date, self.secs / 3600, self.secs / 60 % 60, self.secs % 60)?;
+        match self.offset {
+            Some(0) => {
+                write!(f, "Z")
+            },
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                write!(f, "{}{:02}:{:02}", sign, offset.abs() / 3600, offset.abs() / 60 % 60)
+            },
+            None => {
+                Ok(())
+            },
+        }
+    }
+}
+
+// when during the week something happened, for --modified-on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeOfWeek {
+    Weekend,
+    Weekday,
+    // start hour inclusive, end hour exclusive. wraps past midnight when start > end, so 18-8
+    // covers the evening and the early morning
+    Hours(u32, u32),
+}
+
+impl TimeOfWeek {
+    pub fn contains(&self, timestamp: i64, utc: bool) -> bool {
+        let (weekday, hour) = weekday_and_hour(timestamp, utc);
+        match *self {
+            TimeOfWeek::Weekend => weekday == 0 || weekday == 6,
+            TimeOfWeek::Weekday => weekday != 0 && weekday != 6,
+            TimeOfWeek::Hours(start, end) if start <= end => hour >= start && hour < end,
+            TimeOfWeek::Hours(start, end) => hour >= start || hour < end,
+        }
+    }
+}
+
+impl FromStr for TimeOfWeek {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid value '{}', expected weekend, weekday or an hour range like 18-8", s);
+        match s.trim() {
+            "weekend" => Ok(TimeOfWeek::Weekend),
+            "weekday" => Ok(TimeOfWeek::Weekday),
+            hours => {
+                let (start, end) = hours.split_once('-').ok_or_else(invalid)?;
+                let start = start.parse::<u32>().map_err(|_| invalid())?;
+                let end = end.parse::<u32>().map_err(|_| invalid())?;
+                if start > 23 || end > 24 || start == end {
+                    return Err(invalid());
+                }
+                Ok(TimeOfWeek::Hours(start, end))
+            },
+        }
+    }
+}
+
+impl fmt::Display for TimeOfWeek {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeOfWeek::Weekend => write!(f, "weekend"),
+            TimeOfWeek::Weekday => write!(f, "weekday"),
+            TimeOfWeek::Hours(start, end) => write!(f, "{}-{}", start, end),
+        }
+    }
+}
+
+// day of the week (0 is sunday) and hour of the day for a timestamp
+pub fn weekday_and_hour(timestamp: i64, utc: bool) -> (u32, u32) {
+    if utc {
+        let days = timestamp.div_euclid(SECS_PER_DAY);
+        let weekday = (days + 4).rem_euclid(7) as u32;
+        let hour = (timestamp.rem_euclid(SECS_PER_DAY) / 3600) as u32;
+        (weekday, hour)
+    } else {
+        local_weekday_and_hour(timestamp)
+    }
+}
+
+#[cfg(unix)]
+fn local_weekday_and_hour(timestamp: i64) -> (u32, u32) {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let t = timestamp as libc::time_t;
+    if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+        return weekday_and_hour(timestamp, true);
+    }
+    (tm.tm_wday as u32, tm.tm_hour as u32)
+}
+
+#[cfg(not(unix))]
+fn local_weekday_and_hour(timestamp: i64) -> (u32, u32) {
+    weekday_and_hour(timestamp, true)
+}
+
+// a length of time like 30m, 36h, 2d or 1w, or a number of business days like 5bd
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeSpan {
+    Secs(i64),
+    // monday to friday. holidays aren't known, so they count as business days
+    BusinessDays(i64),
+}
+
+// the day before, or the friday before if that is a weekend
+fn previous_business_day(day: Date) -> Date {
+    let mut day = Date::from_days_since_epoch(day.days_since_epoch() - 1);
+    while day.weekday() == 0 || day.weekday() == 6 {
+        day = Date::from_days_since_epoch(day.days_since_epoch() - 1);
+    }
+    day
+}
+
+impl TimeSpan {
+    // the moment this long before now. business days are counted back on the calendar, local or
+    // UTC, and end at the same time of day as now, so 1bd on a monday morning is friday morning
+    pub fn before(&self, now: i64, utc: bool) -> i64 {
+        match *self {
+            TimeSpan::Secs(secs) => {
+                now - secs
+            },
+            TimeSpan::BusinessDays(days) => {
+                let today = Date::of(now, utc);
+                if days == 0 {
+                    return now;
+                }
+                // the first step back always lands on a weekday, and from a weekday every 5
+                // business days are a week, so only the rest is counted a day at a time
+                let day = previous_business_day(today);
+                let weeks = (days - 1) / 5;
+                let day = Date::from_days_since_epoch(day.days_since_epoch() - weeks * 7);
+                let day = (0..(days - 1) % 5).fold(day, |day, _| previous_business_day(day));
+                day.at(now - today.midnight(utc), utc)
+            },
+        }
+    }
+}
+
+impl FromStr for TimeSpan {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid duration '{}', expected a number followed by s, m, h, d, w or bd", s);
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+        let (count, unit) = s.split_at(split);
+        let count = count.parse::<i64>().map_err(|_| invalid())?;
+        let unit_secs = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 3600,
+            "d" => SECS_PER_DAY,
+            "w" => 7 * SECS_PER_DAY,
+            // a business day is never more than a week back, which keeps the date in range
+            "bd" => {
+                return count.checked_mul(7 * SECS_PER_DAY).map(|_| TimeSpan::BusinessDays(count)).ok_or_else(invalid);
+            },
+            _ => {
+                return Err(invalid());
+            },
+        };
+        Ok(TimeSpan::Secs(count.checked_mul(unit_secs).ok_or_else(invalid)?))
+    }
+}
+
+impl fmt::Display for TimeSpan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeSpan::Secs(secs) => {
+                // in the largest unit it is a whole number of
+                let units = [("w", 7 * SECS_PER_DAY), ("d", SECS_PER_DAY), ("h", 3600), ("m", 60)];
+                match units.iter().find(|(_, unit)| *secs != 0 && secs % unit == 0) {
+                    Some((name, unit)) => write!(f, "{}{}", secs / unit, name),
+                    None => write!(f, "{}s", secs),
+                }
+            },
+            TimeSpan::BusinessDays(days) => write!(f, "{}bd", days),
+        }
+    }
+}
+
+// a point in time given as a day (2023-01-01, from its midnight), a moment on a day
+// (2023-01-01T09:30+01:00) or as how long ago (2d, 5bd)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeBound {
+    Date(Date),
+    At(DateTime),
+    Ago(TimeSpan),
+}
+
+impl TimeBound {
+    pub fn timestamp(&self, now: i64, utc: bool) -> i64 {
+        match self {
+            TimeBound::Date(date) => {
+                date.midnight(utc)
+            },
+            TimeBound::At(time) => {
+                time.timestamp(utc)
+            },
+            TimeBound::Ago(span) => {
+                span.before(now, utc)
+            },
+        }
+    }
+}
+
+impl FromStr for TimeBound {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().contains(['T', ' ']) {
+            s.parse().map(TimeBound::At)
+        } else if s.contains('-') {
+            s.parse().map(TimeBound::Date)
+        } else {
+            s.parse().map(TimeBound::Ago)
+        }
+    }
+}
+
+impl fmt::Display for TimeBound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeBound::Date(date) => write!(f, "{}", date),
+            TimeBound::At(time) => write!(f, "{}", time),
+            TimeBound::Ago(span) => write!(f, "{}", span),
+        }
+    }
+}
+
+pub fn now() -> i64 {
+    to_timestamp(SystemTime::now())
+}
+
+pub fn to_timestamp(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => {
+            d.as_secs() as i64
+        },
+        Err(e) => {
+            -(e.duration().as_secs() as i64)
+        },
+    }
+}
+
+pub fn modified(metadata: &Metadata) -> i64 {
+    metadata.modified().map(to_timestamp).unwrap_or(0)
+}
+
+pub fn accessed(metadata: &Metadata) -> i64 {
+    metadata.accessed().map(to_timestamp).unwrap_or(0)
+}
+
+// None where the filesystem (or kernel) doesn't record when a file was created
+pub fn created(metadata: &Metadata) -> Option<i64> {
+    metadata.created().ok().map(to_timestamp)
+}
+
+#[test]
+fn test_days_since_epoch() {
+    let epoch = Date { year: 1970, month: 1, day: 1 };
+    assert_eq!(epoch.days_since_epoch(), 0);
+    let leap = Date { year: 2024, month: 2, day: 29 };
+    assert_eq!(leap.days_since_epoch(), 19782);
+    assert_eq!(Date::from_days_since_epoch(19782), leap);
+    assert_eq!(leap.next(), Date { year: 2024, month: 3, day: 1 });
+    let before = Date { year: 1969, month: 12, day: 31 };
+    assert_eq!(before.days_since_epoch(), -1);
+    assert_eq!(Date::from_days_since_epoch(-1), before);
+}
+
+#[test]
+fn test_parse_date_range() {
+    let range: DateRange = "2024-01-01..2024-03-31".parse().unwrap();
+    assert_eq!(range.first, Date { year: 2024, month: 1, day: 1 });
+    assert_eq!(range.last, Date { year: 2024, month: 3, day: 31 });
+    assert_eq!(range.bounds(true), (1704067200, 1711929600));
+
+    assert!("2024-01-01".parse::<DateRange>().is_err());
+    assert!("2024-03-31..2024-01-01".parse::<DateRange>().is_err());
+    assert!("2023-02-29..2023-03-01".parse::<DateRange>().is_err());
+    assert!("2024-13-01..2024-13-02".parse::<DateRange>().is_err());
+}
+
+#[test]
+fn test_time_of_week() {
+    // 2024-01-06 was a saturday
+    let saturday_noon = 1704542400;
+    let monday_3am = saturday_noon + 2 * SECS_PER_DAY - 9 * 3600;
+    assert_eq!(weekday_and_hour(saturday_noon, true), (6, 12));
+    assert_eq!(weekday_and_hour(monday_3am, true), (1, 3));
+
+    assert!(TimeOfWeek::Weekend.contains(saturday_noon, true));
+    assert!(!TimeOfWeek::Weekday.contains(saturday_noon, true));
+    assert!(TimeOfWeek::Weekday.contains(monday_3am, true));
+
+    let after_hours: TimeOfWeek = "18-8".parse().unwrap();
+    assert_eq!(after_hours, TimeOfWeek::Hours(18, 8));
+    assert!(after_hours.contains(monday_3am, true));
+    assert!(!after_hours.contains(saturday_noon, true));
+    assert!("9-17".parse::<TimeOfWeek>().unwrap().contains(saturday_noon, true));
+
+    assert!("weekends".parse::<TimeOfWeek>().is_err());
+    assert!("25-3".parse::<TimeOfWeek>().is_err());
+    assert!("8-8".parse::<TimeOfWeek>().is_err());
+}
+
+#[test]
+fn test_parse_time_span() {
+    assert_eq!("30s".parse::<TimeSpan>(), Ok(TimeSpan::Secs(30)));
+    assert_eq!("90m".parse::<TimeSpan>(), Ok(TimeSpan::Secs(5400)));
+    assert_eq!("24h".parse::<TimeSpan>(), Ok(TimeSpan::Secs(SECS_PER_DAY)));
+    assert_eq!("2d".parse::<TimeSpan>(), Ok(TimeSpan::Secs(2 * SECS_PER_DAY)));
+    assert_eq!("1w".parse::<TimeSpan>(), Ok(TimeSpan::Secs(7 * SECS_PER_DAY)));
+    assert_eq!("5bd".parse::<TimeSpan>(), Ok(TimeSpan::BusinessDays(5)));
+    assert!("2".parse::<TimeSpan>().is_err());
+    assert!("d".parse::<TimeSpan>().is_err());
+    assert!("2y".parse::<TimeSpan>().is_err());
+    assert!("-2d".parse::<TimeSpan>().is_err());
+    assert!("2b".parse::<TimeSpan>().is_err());
+    assert!("9999999999999999bd".parse::<TimeSpan>().is_err());
+}
+
+#[test]
+fn test_business_days() {
+    // 2024-01-08 was a monday
+    let monday_9am = Date { year: 2024, month: 1, day: 8 }.at(9 * 3600, true);
+    let friday_9am = monday_9am - 3 * SECS_PER_DAY;
+    assert_eq!(TimeSpan::BusinessDays(0).before(monday_9am, true), monday_9am);
+    assert_eq!(TimeSpan::BusinessDays(1).before(monday_9am, true), friday_9am);
+    assert_eq!(TimeSpan::BusinessDays(5).before(monday_9am, true), monday_9am - 7 * SECS_PER_DAY);
+    assert_eq!(TimeSpan::BusinessDays(6).before(monday_9am, true), friday_9am - 7 * SECS_PER_DAY);
+    // from a weekend, the first business day back is friday
+    let sunday_noon = monday_9am - 21 * 3600;
+    assert_eq!(TimeSpan::BusinessDays(1).before(sunday_noon, true), sunday_noon - 2 * SECS_PER_DAY);
+    assert_eq!(TimeSpan::BusinessDays(5).before(sunday_noon, true), sunday_noon - 6 * SECS_PER_DAY);
+    assert_eq!(TimeSpan::Secs(60).before(monday_9am, true), monday_9am - 60);
+
+    // whole weeks at once give the same day as stepping back one business day at a time
+    for start in 0..7 {
+        let now = monday_9am + start * SECS_PER_DAY;
+        let mut day = Date::of(now, true);
+        for days in 0..30 {
+            assert_eq!(TimeSpan::BusinessDays(days).before(now, true), day.at(9 * 3600, true), "{}bd from day {}", days, start);
+            day = previous_business_day(day);
+        }
+    }
+    // a thousand years of business days are exactly 52000 weeks
+    let years_back = TimeSpan::BusinessDays(5 * 52 * 1000).before(monday_9am, true);
+    assert_eq!(years_back, monday_9am - 52 * 1000 * 7 * SECS_PER_DAY);
+}
+
+#[test]
+fn test_parse_time_bound() {
+    let day = Date { year: 2023, month: 1, day: 1 };
+    assert_eq!("2023-01-01".parse::<TimeBound>(), Ok(TimeBound::Date(day)));
+    assert_eq!("2d".parse::<TimeBound>(), Ok(TimeBound::Ago(TimeSpan::Secs(2 * SECS_PER_DAY))));
+    assert_eq!("5bd".parse::<TimeBound>(), Ok(TimeBound::Ago(TimeSpan::BusinessDays(5))));
+    assert_eq!(TimeBound::Date(day).timestamp(0, true), 1672531200);
+    assert_eq!(TimeBound::Ago(TimeSpan::Secs(60)).timestamp(1000, true), 940);
+    assert!("2023-13-01".parse::<TimeBound>().is_err());
+    assert!("yesterday".parse::<TimeBound>().is_err());
+
+    let at = |s: &str| s.parse::<TimeBound>().unwrap().timestamp(0, true);
+    assert_eq!(at("2023-01-01T09:30"), 1672531200 + 9 * 3600 + 30 * 60);
+    assert_eq!(at("2023-01-01 09:30:15Z"), 1672531200 + 9 * 3600 + 30 * 60 + 15);
+    assert_eq!(at("2023-01-01T09:30+02:00"), 1672531200 + 7 * 3600 + 30 * 60);
+    assert_eq!(at("2023-01-01T09:30-0530"), 1672531200 + 15 * 3600);
+    // an offset wins over --utc or local time
+    let offset: TimeBound = "2023-01-01T00:00+01".parse().unwrap();
+    assert_eq!(offset.timestamp(0, false), offset.timestamp(0, true));
+    assert!("2023-01-01T25:00".parse::<TimeBound>().is_err());
+    assert!("2023-01-01T09:30+2".parse::<TimeBound>().is_err());
+    assert!("2023-01-01T09".parse::<TimeBound>().is_err());
+}
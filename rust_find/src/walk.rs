@@ -0,0 +1,940 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::OsStr;
+use std::fs;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+use regex::Regex;
+
+use crate::archive;
+use crate::checkpoint;
+use crate::config::{self, PathConfig};
+use crate::containers;
+use crate::fds;
+use crate::image;
+use crate::ignore::{self, Ignore};
+use crate::output;
+use crate::status::Status;
+use crate::MyFile;
+
+// how the walker descends, as opposed to which of the files it finds match
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    // files whose name matches none of these are dropped before they are stat'ed, which is most of
+    // the cost of a scan. the name filter still runs afterwards, so this only saves work and never
+    // changes the result
+    pub names: Option<Vec<Regex>>,
+    // files less deep than this are not collected, but their directories are still walked
+    pub min_depth: usize,
+    // directories whose files would be deeper than this are not read at all
+    pub max_depth: Option<usize>,
+    // each directory yields at most this many files, counting those that pass the checks above.
+    // the rest of its files aren't even stat'ed
+    pub max_per_dir: Option<usize>,
+    // descend into symlinks to directories. off by default like find, since a link back up the
+    // tree would otherwise never end
+    pub follow: bool,
+    // directories are read by this many threads at once. with more than one, the order files are
+    // found in changes from run to run
+    pub threads: usize,
+    // inside git repositories, skip what .gitignore, .ignore and the global git excludes ignore
+    pub ignore: bool,
+    // walk dotfiles and dot-directories, which are skipped otherwise like in fd
+    pub hidden: bool,
+    // only walk the part of the tree that belongs to this shard
+    pub shard: Option<Shard>,
+    // directories whose name matches one of these are not walked at all
+    pub exclude_dirs: Vec<Regex>,
+    // leave out container runtime storage and virtual machine disk images
+    pub skip_container_storage: bool,
+    // don't descend into directories on another filesystem than their root, like find -xdev
+    pub one_file_system: bool,
+    // which queued directory is read next
+    pub traversal: Traversal,
+    // read the entries of each directory in name order instead of the order the filesystem
+    // returns them in, so the output is the same on every run
+    pub sorted: bool,
+    // also list the members of zip and tar archives, as if they were directories
+    pub search_archives: bool,
+    // also yield the directories below the roots, with how many entries each holds
+    pub dirs: bool,
+    // with threads, how many directories' worth of results can wait for the consumer before the
+    // workers block. unbounded if None, so a slow consumer lets them pile up
+    pub buffer_size: Option<usize>,
+    // mount points not to descend into, the virtual filesystems. a root is walked even if it is one
+    pub skip_mounts: Vec<PathBuf>,
+    // how many files the walk may hold open at once, instead of the process limit
+    pub max_open_files: Option<usize>,
+    // settings for subtrees from config.toml, which take the place of the ones above there
+    pub paths: Vec<PathConfig>,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        WalkOptions {
+            names: None,
+            min_depth: 0,
+            max_depth: None,
+            max_per_dir: None,
+            follow: false,
+            threads: 1,
+            ignore: true,
+            hidden: false,
+            shard: None,
+            exclude_dirs: Vec::new(),
+            skip_container_storage: false,
+            one_file_system: false,
+            traversal: Traversal::Dfs,
+            sorted: false,
+            search_archives: false,
+            dirs: false,
+            buffer_size: None,
+            skip_mounts: Vec::new(),
+            max_open_files: None,
+            paths: Vec::new(),
+        }
+    }
+}
+
+// the order directories are walked in. files always come out before the subdirectories of the
+// directory they are in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Traversal {
+    // finish each subdirectory before moving on to the next one
+    Dfs,
+    // everything at one depth before anything deeper
+    Bfs,
+}
+
+impl std::str::FromStr for Traversal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dfs" => Ok(Traversal::Dfs),
+            "bfs" => Ok(Traversal::Bfs),
+            _ => Err(format!("invalid traversal '{}', expected dfs or bfs", s)),
+        }
+    }
+}
+
+// one of n disjoint parts of the tree, for --shard i/n. the entries directly inside each root are
+// dealt out by a hash of their name, and everything below an entry goes with it. the hash only
+// depends on the name, so machines that mount the tree in different places still agree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    // 1-based
+    pub index: u64,
+    pub count: u64,
+}
+
+impl Shard {
+    pub fn contains(&self, name: &[u8]) -> bool {
+        fnv1a(name) % self.count == self.index - 1
+    }
+}
+
+impl std::str::FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid shard '{}', expected i/n with 1 <= i <= n", s);
+        let (index, count) = s.split_once('/').ok_or_else(invalid)?;
+        let index = index.trim().parse::<u64>().map_err(|_| invalid())?;
+        let count = count.trim().parse::<u64>().map_err(|_| invalid())?;
+        if index == 0 || index > count {
+            return Err(invalid());
+        }
+        Ok(Shard { index, count })
+    }
+}
+
+// stable across runs, platforms and versions, unlike std's hasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+// a path the walker had to skip, for --include-errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+// compared by path, since stat'ing the mount point of a hung network filesystem would hang too.
+// only directories named like one of the mounts cost a lookup of where they are
+fn is_skipped_mount(dir: &Path, name: &OsStr, mounts: &[PathBuf]) -> bool {
+    if !mounts.iter().any(|mount| mount.file_name() == Some(name)) {
+        return false;
+    }
+    fs::canonicalize(dir).is_ok_and(|dir| mounts.contains(&dir.join(name)))
+}
+
+// device and inode, which identify a directory however it was reached
+#[cfg(unix)]
+fn dir_id(dir: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(dir).ok().map(|m| (m.dev(), m.ino()))
+}
+
+// stable std has no file ids outside unix, so on windows a directory is known by its path with
+// symlinks and junctions resolved, hashed into the same shape. everything is on device 0, which
+// is why main refuses --one-file-system there
+#[cfg(not(unix))]
+fn dir_id(dir: &Path) -> Option<(u64, u64)> {
+    use std::hash::{Hash, Hasher};
+    let canonical = fs::canonicalize(dir).ok()?;
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    canonical.hash(&mut hasher);
+    Some((0, hasher.finish()))
+}
+
+// a directory waiting to be read
+struct Pending {
+    dir: PathBuf,
+    depth: usize,
+    // the ignore files of the directories above it, if it is in a git repository
+    ignore: Option<Arc<Ignore>>,
+    // the device of the root it was found under, with --one-file-system
+    device: Option<u64>,
+    // only collect its files, its subdirectories are queued already (see --resume)
+    files_only: bool,
+}
+
+// a directory a worker has taken off the queue whose files haven't all reached the consumer yet
+struct InFlight {
+    depth: usize,
+    device: Option<u64>,
+    files_only: bool,
+    // once its subdirectories are queued, only its files would have to be read again
+    subdirs_queued: bool,
+}
+
+// directories waiting to be read, and how many workers are reading one right now. the walk is
+// over once nothing is queued and nobody is busy, since only a busy worker can queue more
+struct Queue {
+    // new directories go to the back. dfs takes them from the back too, like a stack, bfs from
+    // the front
+    pending: VecDeque<Pending>,
+    busy: usize,
+    // kept for --checkpoint, so directories being read aren't lost from the frontier
+    in_flight: HashMap<PathBuf, InFlight>,
+}
+
+impl Queue {
+    // dirs are queued so that with one thread, they are walked in the order given
+    fn push(&mut self, mut dirs: Vec<Pending>, traversal: Traversal) {
+        if traversal == Traversal::Dfs {
+            dirs.reverse();
+        }
+        self.pending.extend(dirs);
+    }
+
+    fn pop(&mut self, traversal: Traversal) -> Option<Pending> {
+        match traversal {
+            Traversal::Dfs => {
+                self.pending.pop_back()
+            },
+            Traversal::Bfs => {
+                self.pending.pop_front()
+            },
+        }
+    }
+}
+
+// state shared by all workers of one walk
+struct Shared {
+    options: WalkOptions,
+    queue: Mutex<Queue>,
+    changed: Condvar,
+    visited: Mutex<HashSet<(u64, u64)>>,
+    // set once whoever consumes the walk has seen enough
+    stopped: AtomicBool,
+    // whether directories can be reached twice, through links or overlapping roots
+    track_visited: bool,
+    // whether the consumer has taken every file of the directories it started on, which is when
+    // the frontier describes exactly what is left
+    between_dirs: AtomicBool,
+    // the user's global git excludes, read once for every repository found
+    excludes: String,
+    // taken while a directory is read, so threads can't run out of file descriptors
+    fds: fds::Budget,
+    // for each of options.paths with a threads setting, taken while reading a directory there
+    subtrees: Vec<Option<fds::Budget>>,
+}
+
+type Item = Result<MyFile, WalkError>;
+
+// what reading one directory turned up, in the order it was found
+#[derive(Default)]
+struct Found {
+    items: VecDeque<Item>,
+}
+
+impl Found {
+    // a directory below the roots, for WalkOptions::dirs
+    fn directory(&mut self, path: PathBuf, depth: usize, entries: usize, options: &WalkOptions) {
+        if depth < options.min_depth {
+            return;
+        }
+        if let Some(names) = &options.names {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            if !names.iter().any(|regex| regex.is_match(name)) {
+                return;
+            }
+        }
+        match MyFile::from_path(path) {
+            Ok(f) => {
+                self.items.push_back(Ok(MyFile { depth, entries, ..f }));
+            },
+            Err(path) => {
+                output::warn_error("could not access file", path.display());
+                self.items.push_back(Err(WalkError { path, message: "could not access file".to_string() }));
+            },
+        }
+    }
+
+    fn error(&mut self, msg: &'static str, path: &Path, detail: impl std::fmt::Display) {
+        output::warn_error(msg, format!("{}: {}", path.display(), detail));
+        self.items.push_back(Err(WalkError { path: path.to_path_buf(), message: format!("{}: {}", msg, detail) }));
+    }
+}
+
+enum Source {
+    // one thread: directories are read on demand by whoever pulls from the iterator
+    Local(Status),
+    // worker threads send what they find one directory at a time
+    Workers(Receiver<(PathBuf, VecDeque<Item>)>),
+}
+
+// the files below some roots, found lazily. only the directories still to be read and the entries
+// of the current one are held in memory, never the whole tree
+pub(crate) struct Files {
+    shared: Arc<Shared>,
+    source: Source,
+    found: Found,
+    // the directory found came from, with worker threads
+    current: Option<PathBuf>,
+}
+
+// lets worker threads stop early instead of walking the rest of the tree for nobody
+impl Drop for Files {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::Relaxed);
+        self.shared.changed.notify_all();
+    }
+}
+
+impl Iterator for Files {
+    type Item = Item;
+
+    fn next(&mut self) -> Option<Item> {
+        loop {
+            if let Some(item) = self.found.items.pop_front() {
+                if self.found.items.is_empty() {
+                    if let Some(dir) = self.current.take() {
+                        self.shared.queue.lock().unwrap().in_flight.remove(&dir);
+                    }
+                    self.shared.between_dirs.store(true, Ordering::Relaxed);
+                }
+                return Some(item);
+            }
+            self.shared.between_dirs.store(false, Ordering::Relaxed);
+            match &mut self.source {
+                Source::Local(status) => {
+                    let pending = self.shared.queue.lock().unwrap().pop(self.shared.options.traversal)?;
+                    let subdirs = read_dir(pending, &self.shared, status, &mut self.found);
+                    queue_subdirs(&self.shared, subdirs, None);
+                },
+                Source::Workers(receiver) => {
+                    let (dir, items) = receiver.recv().ok()?;
+                    self.current = Some(dir);
+                    self.found.items = items;
+                },
+            }
+            if self.found.items.is_empty() {
+                self.shared.between_dirs.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+pub fn walk(dirs: Vec<PathBuf>, options: &WalkOptions) -> Files {
+    let mut found = Found::default();
+    let mut roots = Vec::new();
+    let excludes = if options.ignore { ignore::global_excludes() } else { String::new() };
+    for dir in dirs {
+        if let Some(reference) = image::reference(&dir) {
+            match image::list(reference, options) {
+                Ok(files) => {
+                    found.items.extend(files.into_iter().map(Ok));
+                },
+                Err(e) => {
+                    found.error("could not read image", &dir, e);
+                },
+            }
+            continue;
+        }
+        if !dir.exists() {
+            if !output::errors_as_records() {
+                output::warn("no such file or directory", dir.display());
+                output::note("skipping search in directory", dir.display());
+            }
+            found.items.push_back(Err(WalkError { path: dir, message: "no such file or directory".to_string() }));
+            continue;
+        }
+        let ignore = if options.ignore { ignore::above(&dir, &excludes) } else { None };
+        let device = if options.one_file_system { dir_id(&dir).map(|(device, _)| device) } else { None };
+        roots.push(Pending { dir, depth: 1, ignore, device, files_only: false });
+    }
+    let canonical: Vec<Option<PathBuf>> = roots.iter().map(|root| fs::canonicalize(&root.dir).ok()).collect();
+    let roots: Vec<Pending> = roots.into_iter()
+        .zip(repeated_roots(&canonical))
+        .filter(|(root, repeated)| {
+            if *repeated {
+                output::warn("skipping search root given more than once", root.dir.display());
+            }
+            !repeated
+        })
+        .map(|(root, _)| root)
+        .collect();
+    // nested roots, and bind mounts, overlap, which only remembering directories catches
+    let track_visited = follows_anywhere(options) || roots.len() > 1;
+    let mut queue = Queue { pending: VecDeque::new(), busy: 0, in_flight: HashMap::new() };
+    queue.push(roots, options.traversal);
+    start(queue, found, options, excludes, track_visited)
+}
+
+// picks up a walk from a --checkpoint, in the order it was saved in. the ignore files above each
+// directory are read again, what --follow had visited is forgotten
+pub fn resume(entries: Vec<checkpoint::Entry>, options: &WalkOptions) -> Files {
+    let excludes = if options.ignore { ignore::global_excludes() } else { String::new() };
+    let pending = entries.into_iter()
+        .map(|entry| {
+            let ignore = if options.ignore { ignore::above(&entry.dir, &excludes) } else { None };
+            Pending { dir: entry.dir, depth: entry.depth, ignore, device: entry.device, files_only: entry.files_only }
+        })
+        .collect();
+    let queue = Queue { pending, busy: 0, in_flight: HashMap::new() };
+    start(queue, Found::default(), options, excludes, follows_anywhere(options))
+}
+
+fn start(queue: Queue, found: Found, options: &WalkOptions, excludes: String, track_visited: bool) -> Files {
+    let shared = Arc::new(Shared {
+        options: options.clone(),
+        queue: Mutex::new(queue),
+        changed: Condvar::new(),
+        visited: Mutex::new(HashSet::new()),
+        stopped: AtomicBool::new(false),
+        track_visited,
+        between_dirs: AtomicBool::new(found.items.is_empty()),
+        excludes,
+        fds: fds::budget(options.max_open_files),
+        subtrees: options.paths.iter().map(|p| p.threads.map(fds::Budget::new)).collect(),
+    });
+    if options.threads <= 1 {
+        return Files { shared, source: Source::Local(Status::new()), found, current: None };
+    }
+
+    // a worker blocked on a full channel holds no lock, and fails out of send once Files is dropped
+    let receiver = match options.buffer_size {
+        Some(size) => {
+            let (sender, receiver) = mpsc::sync_channel(size);
+            spawn_workers(&shared, move |dir, items| sender.send((dir, items)).is_ok());
+            receiver
+        },
+        None => {
+            let (sender, receiver) = mpsc::channel();
+            spawn_workers(&shared, move |dir, items| sender.send((dir, items)).is_ok());
+            receiver
+        },
+    };
+    Files { shared, source: Source::Workers(receiver), found, current: None }
+}
+
+fn spawn_workers(shared: &Arc<Shared>, send: impl Fn(PathBuf, VecDeque<Item>) -> bool + Clone + Send + 'static) {
+    for _ in 0..shared.options.threads {
+        let shared = Arc::clone(shared);
+        let send = send.clone();
+        std::thread::spawn(move || work(&shared, send));
+    }
+}
+
+// what is left of a walk, for --checkpoint. lives on after the walk has been boxed up as a plain
+// iterator
+pub(crate) struct Frontier(Arc<Shared>);
+
+impl Files {
+    pub(crate) fn frontier(&self) -> Frontier {
+        Frontier(Arc::clone(&self.shared))
+    }
+}
+
+impl Frontier {
+    // the directories still to be read. None while the consumer is partway through the files of
+    // a directory, since a checkpoint then would either lose or repeat some of them
+    pub(crate) fn entries(&self) -> Option<Vec<checkpoint::Entry>> {
+        if !self.0.between_dirs.load(Ordering::Relaxed) {
+            return None;
+        }
+        let queue = self.0.queue.lock().unwrap();
+        let pending = queue.pending.iter().map(|p| checkpoint::Entry {
+            dir: p.dir.clone(),
+            depth: p.depth,
+            device: p.device,
+            files_only: p.files_only,
+        });
+        let in_flight = queue.in_flight.iter().map(|(dir, f)| checkpoint::Entry {
+            dir: dir.clone(),
+            depth: f.depth,
+            device: f.device,
+            files_only: f.files_only || f.subdirs_queued,
+        });
+        Some(pending.chain(in_flight).collect())
+    }
+}
+
+// files named one per line (e.g. by git ls-files or another find) instead of found by walking.
+// lines are taken as raw bytes like roots_from does, so a name that isn't utf-8 is reported on its
+// own rather than ending the list. a read error does end it, with a warning
+pub(crate) fn from_lines(lines: impl BufRead) -> impl Iterator<Item = Item> {
+    lines.split(b'\n')
+        .map_while(|line| {
+            line.map_err(|e| output::warn("could not read the file list", e)).ok()
+        })
+        .filter_map(|mut line| {
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            (!line.is_empty()).then(|| path_from_bytes(line))
+        })
+        .map(|path| {
+            MyFile::from_path(path).map_err(|path| {
+                output::warn_error("could not access file", path.display());
+                WalkError { path, message: "could not access file".to_string() }
+            })
+        })
+}
+
+// directories to walk, read from another tool one at a time as they arrive. entries are separated
+// by newlines or NUL bytes, so both `find -type d` and `find -print0` work
+pub(crate) fn roots_from(mut input: impl BufRead) -> impl Iterator<Item = PathBuf> {
+    std::iter::from_fn(move || {
+        loop {
+            let mut entry = Vec::new();
+            loop {
+                let buf = input.fill_buf().ok()?;
+                if buf.is_empty() {
+                    break;
+                }
+                match buf.iter().position(|&b| b == b'\n' || b == 0) {
+                    Some(end) => {
+                        entry.extend_from_slice(&buf[..end]);
+                        input.consume(end + 1);
+                        break;
+                    },
+                    None => {
+                        entry.extend_from_slice(buf);
+                        let len = buf.len();
+                        input.consume(len);
+                    },
+                }
+            }
+            if entry.last() == Some(&b'\r') {
+                entry.pop();
+            }
+            if !entry.is_empty() {
+                return Some(path_from_bytes(entry));
+            }
+            if input.fill_buf().ok()?.is_empty() {
+                return None;
+            }
+        }
+    })
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+// everything a walk found, split into files and the paths that couldn't be read
+pub(crate) fn collect(items: impl Iterator<Item = Item>) -> (Vec<MyFile>, Vec<WalkError>) {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    for item in items {
+        match item {
+            Ok(file) => {
+                files.push(file);
+            },
+            Err(e) => {
+                errors.push(e);
+            },
+        }
+    }
+    (files, errors)
+}
+
+// which roots repeat an earlier one. takes the canonical paths, None where a root couldn't be
+// resolved. a root inside another is kept, since the outer walk may never reach it (it can be
+// hidden, excluded or ignored there), and remembering visited directories keeps the two from
+// listing anything twice
+fn repeated_roots(canonical: &[Option<PathBuf>]) -> Vec<bool> {
+    canonical.iter()
+        .enumerate()
+        .map(|(i, root)| root.is_some() && canonical[..i].contains(root))
+        .collect()
+}
+
+// from is the directory they were found in when a worker read it
+fn queue_subdirs(shared: &Shared, subdirs: Vec<Pending>, from: Option<&Path>) {
+    let mut queue = shared.queue.lock().unwrap();
+    queue.push(subdirs, shared.options.traversal);
+    if let Some(in_flight) = from.and_then(|dir| queue.in_flight.get_mut(dir)) {
+        in_flight.subdirs_queued = true;
+    }
+}
+
+// takes directories off the queue until the walk is over or the consumer has stopped listening
+// (e.g. --limit was reached)
+fn work(shared: &Shared, send: impl Fn(PathBuf, VecDeque<Item>) -> bool) {
+    let mut status = Status::new();
+    loop {
+        let pending = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if shared.stopped.load(Ordering::Relaxed) {
+                    return;
+                }
+                if let Some(next) = queue.pop(shared.options.traversal) {
+                    queue.busy += 1;
+                    let in_flight = InFlight {
+                        depth: next.depth,
+                        device: next.device,
+                        files_only: next.files_only,
+                        subdirs_queued: false,
+                    };
+                    queue.in_flight.insert(next.dir.clone(), in_flight);
+                    break next;
+                }
+                if queue.busy == 0 {
+                    return;
+                }
+                queue = shared.changed.wait(queue).unwrap();
+            }
+        };
+        let dir = pending.dir.clone();
+        let mut found = Found::default();
+        let subdirs = read_dir(pending, shared, &mut status, &mut found);
+        queue_subdirs(shared, subdirs, Some(&dir));
+        if found.items.is_empty() {
+            shared.queue.lock().unwrap().in_flight.remove(&dir);
+        } else if !send(dir, found.items) {
+            shared.stopped.store(true, Ordering::Relaxed);
+        }
+        shared.queue.lock().unwrap().busy -= 1;
+        shared.changed.notify_all();
+    }
+}
+
+// whether symlinks are followed anywhere, which is what can lead back into a directory
+fn follows_anywhere(options: &WalkOptions) -> bool {
+    options.follow || options.paths.iter().any(|p| p.follow == Some(true))
+}
+
+// collects the files directly inside dir and returns the subdirectories to walk next
+fn read_dir(pending: Pending, shared: &Shared, status: &mut Status, found: &mut Found) -> Vec<Pending> {
+    let options = &shared.options;
+    let Pending { dir, depth, ignore, device, files_only } = pending;
+    let dir = dir.as_path();
+    let mut subdirs = Vec::new();
+    // only links and overlapping roots can lead back into a directory we are already in, so
+    // otherwise there is nothing to remember
+    if shared.track_visited {
+        if let Some(id) = dir_id(dir) {
+            if !shared.visited.lock().unwrap().insert(id) {
+                output::warn("skipping directory already visited", dir.display());
+                return subdirs;
+            }
+        }
+    }
+    let subtree = config::for_dir(&options.paths, dir);
+    let follow = subtree.and_then(|i| options.paths[i].follow).unwrap_or(options.follow);
+    // the subtree's turn comes first, so waiting for it doesn't hold up a file descriptor
+    let _turn = subtree.and_then(|i| shared.subtrees[i].as_ref()).map(|budget| budget.acquire());
+    let _permit = shared.fds.acquire();
+    status.enter_dir(dir);
+    let mut entries: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => {
+            entries.collect()
+        },
+        Err(e) => {
+            found.error("could not read directory", dir, e);
+            return subdirs;
+        },
+    };
+    if options.sorted {
+        entries.sort_by_key(|e| e.as_ref().ok().map(|e| e.file_name()));
+    }
+    let dir_entries = entries.len();
+    // the directory is listed when it is read, which is when its entries are known. roots aren't
+    if options.dirs && depth > 1 && !files_only {
+        found.directory(dir.to_path_buf(), depth - 1, dir_entries, options);
+    }
+    let ignore = if options.ignore {
+        let has = |name: &str| entries.iter().any(|e| e.as_ref().is_ok_and(|e| e.file_name() == name));
+        ignore::enter(ignore.as_ref(), dir, has, &shared.excludes)
+    } else {
+        None
+    };
+    let mut files = 0;
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => {
+                entry
+            },
+            Err(e) => {
+                found.error("could not read directory", dir, e);
+                continue;
+            },
+        };
+        if !options.hidden && entry.file_name().as_encoded_bytes().starts_with(b".") {
+            continue;
+        }
+        if depth == 1 && options.shard.is_some_and(|shard| !shard.contains(entry.file_name().as_encoded_bytes())) {
+            continue;
+        }
+        // file_type comes from the directory entry itself where the platform fills it in
+        // (d_type on linux and macos), so only entries it can't classify cost a stat. with
+        // --follow, symlinks still need one to see whether they point at a directory
+        let is_dir = match entry.file_type() {
+            Ok(t) if t.is_symlink() => {
+                follow && entry.path().is_dir()
+            },
+            Ok(t) => {
+                t.is_dir()
+            },
+            Err(_) => {
+                entry.path().is_dir()
+            },
+        };
+        let path = entry.path();
+        if ignore.as_ref().is_some_and(|ignore| ignore.is_ignored(&path, is_dir)) {
+            continue;
+        }
+        if !is_dir {
+            status.add_file(dir);
+            // before the archive's own depth and name checks, which its members get on their own
+            if options.search_archives && archive::is_archive(&entry.file_name().to_string_lossy()) {
+                match archive::list(&path, depth, options) {
+                    Ok(members) => {
+                        found.items.extend(members.into_iter().map(Ok));
+                    },
+                    Err(e) => {
+                        found.error("could not read archive", &path, e);
+                    },
+                }
+            }
+            if depth < options.min_depth {
+                continue;
+            }
+            if options.skip_container_storage && containers::is_disk_image(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+            if let Some(names) = &options.names {
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                if !names.iter().any(|regex| regex.is_match(name)) {
+                    continue;
+                }
+            }
+            if options.max_per_dir.is_some_and(|max| files >= max) {
+                continue;
+            }
+            match MyFile::from_path(path) {
+                Ok(f) => {
+                    files += 1;
+                    found.items.push_back(Ok(MyFile { depth, dir_entries, ..f }));
+                },
+                Err(path) => {
+                    if !output::errors_as_records() {
+                        output::warn("could not access file", path.display());
+                        output::note("skipping search in directory", path.display());
+                    }
+                    found.items.push_back(Err(WalkError { path, message: "could not access file".to_string() }));
+                },
+            }
+            continue;
+        }
+        if files_only {
+            continue;
+        }
+        let name = entry.file_name();
+        if options.exclude_dirs.iter().any(|regex| regex.is_match(&name.to_string_lossy())) {
+            continue;
+        }
+        if is_skipped_mount(dir, &name, &options.skip_mounts) {
+            continue;
+        }
+        if containers::is_storage_dir(&path) {
+            if options.skip_container_storage {
+                continue;
+            }
+            output::warn("walking container storage, which --skip-container-storage leaves out", path.display());
+        }
+        // a mount point is the only place the device can change, but telling one apart costs the
+        // same stat as just comparing
+        let other_device = || device.is_some() && dir_id(&path).map(|(device, _)| device) != device;
+        if options.max_depth.is_some_and(|max| depth + 1 > max) {
+            // not walked, but still within --max-depth itself
+            if options.dirs && !other_device() {
+                let entries = fs::read_dir(&path).map(|entries| entries.count()).unwrap_or(0);
+                found.directory(path, depth, entries, options);
+            }
+            continue;
+        }
+        if other_device() {
+            continue;
+        }
+        subdirs.push(Pending { dir: path, depth: depth + 1, ignore: ignore.clone(), device, files_only: false });
+    }
+    subdirs
+}
+
+#[test]
+fn test_queue_order() {
+    let pending = |name: &str| Pending { dir: PathBuf::from(name), depth: 1, ignore: None, device: None, files_only: false };
+    let order = |traversal| {
+        let mut queue = Queue { pending: VecDeque::new(), busy: 0, in_flight: HashMap::new() };
+        queue.push(vec![pending("a"), pending("b")], traversal);
+        let mut walked = Vec::new();
+        while let Some(next) = queue.pop(traversal) {
+            if next.dir == Path::new("a") {
+                queue.push(vec![pending("a/1"), pending("a/2")], traversal);
+            }
+            walked.push(next.dir.to_string_lossy().into_owned());
+        }
+        walked
+    };
+    assert_eq!(order(Traversal::Dfs), vec!["a", "a/1", "a/2", "b"]);
+    assert_eq!(order(Traversal::Bfs), vec!["a", "b", "a/1", "a/2"]);
+    assert_eq!("bfs".parse::<Traversal>(), Ok(Traversal::Bfs));
+    assert!("random".parse::<Traversal>().is_err());
+}
+
+#[test]
+fn test_roots_from() {
+    let roots: Vec<PathBuf> = roots_from(&b"/srv\n\nsrc/a b\r\n/tmp\0/var\0"[..]).collect();
+    assert_eq!(roots, vec![PathBuf::from("/srv"), PathBuf::from("src/a b"), PathBuf::from("/tmp"), PathBuf::from("/var")]);
+    assert_eq!(roots_from(&b"last"[..]).collect::<Vec<PathBuf>>(), vec![PathBuf::from("last")]);
+    assert_eq!(roots_from(&b""[..]).count(), 0);
+}
+
+#[test]
+fn test_hidden() {
+    let dir = crate::temp::TestDir::new("hidden");
+    fs::create_dir_all(dir.join(".cache")).unwrap();
+    for file in ["a", ".DS_Store", ".cache/b"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+    let found = |hidden| {
+        let mut names: Vec<String> = walk(vec![dir.to_path_buf()], &WalkOptions { hidden, ..Default::default() })
+            .filter_map(Result::ok)
+            .map(|f| f.path.strip_prefix(&dir).unwrap().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    };
+    assert_eq!(found(false), vec!["a"]);
+    assert_eq!(found(true), vec![".DS_Store", ".cache/b", "a"]);
+}
+
+#[test]
+fn test_from_lines() {
+    let dir = crate::temp::TestDir::new("lines");
+    fs::write(dir.join("a"), "").unwrap();
+    fs::write(dir.join("b"), "").unwrap();
+    let mut input = Vec::new();
+    input.extend_from_slice(format!("{}\r\n\n", dir.join("a").display()).as_bytes());
+    input.extend_from_slice(dir.as_os_str().to_string_lossy().as_bytes());
+    input.extend_from_slice(b"/caf\xe9\n");
+    input.extend_from_slice(dir.join("b").display().to_string().as_bytes());
+    let items: Vec<Item> = from_lines(&input[..]).collect();
+    // the line that isn't utf-8 fails on its own and the one after it is still read
+    assert_eq!(items.len(), 3);
+    assert_eq!(items[0].as_ref().unwrap().path, dir.join("a"));
+    assert!(items[1].is_err());
+    assert_eq!(items[2].as_ref().unwrap().path, dir.join("b"));
+}
+
+#[test]
+fn test_repeated_roots() {
+    let roots: Vec<Option<PathBuf>> = ["/home/me/projects", "/home/me", "/home/mel", "/home/me", "/srv"].iter()
+        .map(|r| Some(PathBuf::from(r)))
+        .chain([None, None])
+        .collect();
+    assert_eq!(repeated_roots(&roots), vec![false, false, false, true, false, false, false]);
+}
+
+#[test]
+fn test_nested_roots() {
+    let dir = crate::temp::TestDir::new("nested");
+    fs::create_dir_all(dir.join(".config/app")).unwrap();
+    fs::create_dir_all(dir.join("src")).unwrap();
+    for file in ["a", ".config/app/b", "src/c"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+    let found = |roots: &[&str]| {
+        let roots = roots.iter().map(|r| dir.join(r)).collect();
+        let mut names: Vec<String> = walk(roots, &WalkOptions::default())
+            .filter_map(Result::ok)
+            .map(|f| f.path.strip_prefix(&dir).unwrap().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    };
+    // the outer root skips .config as hidden, so only the inner root finds b
+    assert_eq!(found(&["", ".config"]), vec![".config/app/b", "a", "src/c"]);
+    // where the outer root reaches the inner one, nothing is listed twice
+    assert_eq!(found(&["", "src"]), vec!["a", "src/c"]);
+    assert_eq!(found(&["src", "", "src"]), vec!["a", "src/c"]);
+}
+
+#[test]
+fn test_shard() {
+    assert_eq!("2/4".parse::<Shard>(), Ok(Shard { index: 2, count: 4 }));
+    assert!("0/4".parse::<Shard>().is_err());
+    assert!("5/4".parse::<Shard>().is_err());
+    assert!("2".parse::<Shard>().is_err());
+
+    assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    // every name lands in exactly one shard
+    let shards: Vec<Shard> = (1..=3).map(|index| Shard { index, count: 3 }).collect();
+    for name in ["src", "target", "node_modules", "README.md", ".git", "docs"] {
+        assert_eq!(shards.iter().filter(|s| s.contains(name.as_bytes())).count(), 1);
+    }
+}
+
+#[test]
+fn test_max_per_dir() {
+    let dir = crate::temp::TestDir::new("max-per-dir");
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    for file in ["a", "b", "c", "d", "e", "sub/f", "sub/g", "sub/h"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+    let found: Vec<PathBuf> = walk(vec![dir.to_path_buf()], &WalkOptions { max_per_dir: Some(2), ..Default::default() })
+        .filter_map(Result::ok)
+        .map(|f| f.path)
+        .collect();
+    // the limit counts files only, so sub is still walked
+    assert_eq!(found.iter().filter(|path| path.parent() == Some(&*dir)).count(), 2);
+    assert_eq!(found.iter().filter(|path| path.parent() == Some(&dir.join("sub"))).count(), 2);
+}
@@ -0,0 +1,172 @@
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use crate::format::{Format, LineFormat};
+use crate::MyFile;
+
+// a plain http:// endpoint split into what we need to talk to it. there is no tls crate in this
+// build, so https endpoints have to go through a local forwarding proxy
+#[derive(Debug, PartialEq, Eq)]
+pub struct Endpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl std::str::FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("https://") {
+            return Err(format!("'{}' needs tls, which this build doesn't support. use an http:// proxy", s));
+        }
+        let rest = s.strip_prefix("http://")
+            .ok_or_else(|| format!("invalid url '{}', expected http://HOST[:PORT]/PATH", s))?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((h, p)) => {
+                (h, p.parse::<u16>().map_err(|_| format!("invalid port in '{}'", s))?)
+            },
+            None => {
+                (authority, 80)
+            },
+        };
+        if host.is_empty() {
+            return Err(format!("invalid url '{}', missing host", s));
+        }
+        Ok(Endpoint { host: host.to_string(), port, path: path.to_string() })
+    }
+}
+
+// POSTs the matches as json arrays of at most batch_size records, one request per batch
+pub fn post(endpoint: &Endpoint, files: &[&MyFile], line_format: LineFormat, batch_size: usize) -> io::Result<()> {
+    let line_format = LineFormat { format: Format::Json, number: false, porcelain: false, ..line_format };
+    for (n, batch) in files.chunks(batch_size.max(1)).enumerate() {
+        let records: Vec<String> = batch.iter().enumerate()
+            .map(|(i, file)| line_format.line(n * batch_size + i, file))
+            .collect();
+        send(endpoint, &format!("[{}]", records.join(",")))?;
+    }
+    Ok(())
+}
+
+fn send(endpoint: &Endpoint, body: &str) -> io::Result<()> {
+    let mut stream = TcpStream::connect((endpoint.host.as_str(), endpoint.port))?;
+    stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+    write!(stream, "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+           endpoint.path, endpoint.host, body.len(), body)?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    let response = String::from_utf8_lossy(&response);
+    let status = response.lines().next().unwrap_or("");
+    match status.split(' ').nth(1) {
+        Some(code) if code.starts_with('2') => {
+            Ok(())
+        },
+        _ => {
+            Err(io::Error::other(format!("server answered '{}'", status)))
+        },
+    }
+}
+
+#[test]
+fn test_parse_endpoint() {
+    assert_eq!("http://localhost:8080/hooks/find".parse::<Endpoint>(),
+               Ok(Endpoint { host: "localhost".to_string(), port: 8080, path: "/hooks/find".to_string() }));
+    assert_eq!("http://example.com".parse::<Endpoint>(),
+               Ok(Endpoint { host: "example.com".to_string(), port: 80, path: "/".to_string() }));
+    assert!("https://example.com/".parse::<Endpoint>().is_err());
+    assert!("ftp://example.com/".parse::<Endpoint>().is_err());
+    assert!("http://example.com:http/".parse::<Endpoint>().is_err());
+    assert!("http://:80/".parse::<Endpoint>().is_err());
+}
+
+#[test]
+fn test_parse_endpoint_edge_cases() {
+    assert_eq!("http://127.0.0.1:0/a?b=c/d".parse::<Endpoint>(),
+               Ok(Endpoint { host: "127.0.0.1".to_string(), port: 0, path: "/a?b=c/d".to_string() }));
+    assert_eq!("http://h/".parse::<Endpoint>().map(|e| e.port), Ok(80));
+    assert!("http://h:/".parse::<Endpoint>().is_err());
+    assert!("http://h:65536/".parse::<Endpoint>().is_err());
+    assert!("http://".parse::<Endpoint>().is_err());
+    assert!("http:///path".parse::<Endpoint>().is_err());
+    assert!("HTTP://h/".parse::<Endpoint>().is_err());
+    assert!("h:80/".parse::<Endpoint>().is_err());
+    // https is refused with a hint rather than as a bad url
+    assert!("https://h/".parse::<Endpoint>().unwrap_err().contains("proxy"));
+}
+
+#[test]
+fn test_post() {
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+
+    // the endpoint is the listener's own address, taken while it is bound, so nothing else can
+    // be given the port in between
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    // a stand-in server that keeps each request and answers with the next status
+    let statuses = ["200 OK", "204 No Content", "200 OK", "500 Internal Server Error"];
+    let server = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for status in statuses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            // the request is complete once the body is as long as Content-Length says
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length = head.lines()
+                        .find_map(|l| l.strip_prefix("Content-Length: "))
+                        .and_then(|l| l.parse::<usize>().ok())
+                        .unwrap();
+                    if body.len() >= length {
+                        break;
+                    }
+                }
+            }
+            write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+            requests.push(String::from_utf8(request).unwrap());
+        }
+        requests
+    });
+
+    let endpoint: Endpoint = format!("http://{}/hook", address).parse().unwrap();
+    let files: Vec<MyFile> = ["a", "b", "c"].iter().map(|p| MyFile { path: PathBuf::from(p), ..Default::default() }).collect();
+    let files: Vec<&MyFile> = files.iter().collect();
+    let line_format = LineFormat {
+        format: Format::Text,
+        schema_version: 1,
+        show_attrs: false,
+        number: true,
+        show_link_targets: false,
+        porcelain: true,
+    };
+    // batches of two, then a batch size of 0 taken as 1, then nothing to send at all
+    post(&endpoint, &files, line_format, 2).unwrap();
+    post(&endpoint, &files[..1], line_format, 0).unwrap();
+    post(&endpoint, &[], line_format, 2).unwrap();
+    // a server error fails the post
+    let error = post(&endpoint, &files[..1], line_format, 2).unwrap_err();
+    assert!(error.to_string().contains("500"), "{}", error);
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].starts_with("POST /hook HTTP/1.1\r\nHost: 127.0.0.1\r\n"), "{}", requests[0]);
+    // the records are json whatever the output format is
+    let bodies: Vec<&str> = requests.iter().map(|r| r.split_once("\r\n\r\n").unwrap().1).collect();
+    assert!(bodies[0].starts_with("[{\"path\":\"a\"") && bodies[0].contains("},{\"path\":\"b\""), "{}", bodies[0]);
+    assert!(bodies[1].starts_with("[{\"path\":\"c\"") && bodies[1].ends_with("}]"), "{}", bodies[1]);
+    assert!(bodies[2].starts_with("[{\"path\":\"a\"") && !bodies[2].contains("},{"), "{}", bodies[2]);
+    assert_eq!(requests.len(), 4);
+}
@@ -0,0 +1,110 @@
+use std::io;
+use std::path::Path;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::ffi::CString;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn c_string(bytes: &[u8]) -> io::Result<CString> {
+    CString::new(bytes)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a nul byte"))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn c_args(path: &Path, name: &str) -> io::Result<(CString, CString)> {
+    use std::os::unix::ffi::OsStrExt;
+    Ok((c_string(path.as_os_str().as_bytes())?, c_string(name.as_bytes())?))
+}
+
+// errno for "attribute not set" differs between the two
+#[cfg(target_os = "linux")]
+const NO_ATTR: libc::c_int = libc::ENODATA;
+#[cfg(target_os = "macos")]
+const NO_ATTR: libc::c_int = libc::ENOATTR;
+
+// missing attributes and filesystems/namespaces without xattr support both count as "not set"
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn not_set(e: &io::Error) -> bool {
+    e.raw_os_error() == Some(NO_ATTR) || e.raw_os_error() == Some(libc::ENOTSUP)
+}
+
+// whether the extended attribute `name` is set on path itself (symlinks are not followed)
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn has(path: &Path, name: &str) -> io::Result<bool> {
+    let (path, name) = c_args(path, name)?;
+    #[cfg(target_os = "linux")]
+    let size = unsafe { libc::lgetxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+    #[cfg(target_os = "macos")]
+    let size = unsafe {
+        libc::getxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0, 0, libc::XATTR_NOFOLLOW)
+    };
+    if size >= 0 {
+        return Ok(true);
+    }
+    let e = io::Error::last_os_error();
+    if not_set(&e) {
+        return Ok(false);
+    }
+    Err(e)
+}
+
+// removes `name` from path, returning whether it was set in the first place
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn remove(path: &Path, name: &str) -> io::Result<bool> {
+    let (path, name) = c_args(path, name)?;
+    #[cfg(target_os = "linux")]
+    let ret = unsafe { libc::lremovexattr(path.as_ptr(), name.as_ptr()) };
+    #[cfg(target_os = "macos")]
+    let ret = unsafe { libc::removexattr(path.as_ptr(), name.as_ptr(), libc::XATTR_NOFOLLOW) };
+    if ret == 0 {
+        return Ok(true);
+    }
+    let e = io::Error::last_os_error();
+    if not_set(&e) {
+        return Ok(false);
+    }
+    Err(e)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn has(_path: &Path, _name: &str) -> io::Result<bool> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "extended attributes are not supported on this platform"))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn remove(_path: &Path, _name: &str) -> io::Result<bool> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "extended attributes are not supported on this platform"))
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_has_and_remove() {
+    let dir = crate::temp::TestDir::new("xattr");
+    let file = dir.join("file");
+    std::fs::write(&file, "").unwrap();
+    let name = "user.rust-find-test";
+
+    // nothing set yet, so there is nothing to remove either
+    assert!(!has(&file, name).unwrap());
+    assert!(!remove(&file, name).unwrap());
+    assert!(has(&dir.join("missing"), name).is_err());
+    assert!(remove(&dir.join("missing"), name).is_err());
+    assert_eq!(has(Path::new("a\0b"), name).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(has(&file, "user.a\0b").unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+    // some filesystems (older tmpfs among them) don't take user attributes at all
+    let (path, c_name) = c_args(&file, name).unwrap();
+    let set = unsafe { libc::lsetxattr(path.as_ptr(), c_name.as_ptr(), b"1".as_ptr().cast(), 1, 0) };
+    if set == 0 {
+        assert!(has(&file, name).unwrap());
+        assert!(remove(&file, name).unwrap());
+        assert!(!has(&file, name).unwrap());
+    } else {
+        assert!(not_set(&io::Error::last_os_error()));
+    }
+
+    // a symlink's own attributes are looked at, not its target's
+    let link = dir.join("link");
+    std::os::unix::fs::symlink(dir.join("missing"), &link).unwrap();
+    assert!(!has(&link, name).unwrap());
+}